@@ -0,0 +1,5263 @@
+// Swap entry points take every lock parameter as its own JSON argument
+#![allow(clippy::too_many_arguments)]
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::json_types::U128;
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, CurveType, Gas, NearToken, PanicOnDefault, Promise, PromiseOrValue, PromiseResult, PublicKey, CryptoHash, log, require};
+use std::str::FromStr;
+
+type Balance = u128;
+
+// Define our own chain ID types for 1inch fusion integration
+#[derive(
+    BorshDeserialize, BorshSerialize, serde::Serialize, serde::Deserialize,
+    Clone, Debug, PartialEq, Eq,
+)]
+#[serde(crate = "near_sdk::serde", rename_all = "lowercase")]
+pub enum NetworkId {
+    Mainnet,
+    Testnet,
+}
+
+#[derive(
+    BorshDeserialize, BorshSerialize, serde::Serialize, serde::Deserialize,
+    Clone, Debug, PartialEq, Eq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChainId {
+    pub network_id: NetworkId,
+    pub chain_id: u64,
+}
+
+// Canonical string form of a chain, e.g. `mainnet:1` for Ethereum
+impl std::fmt::Display for ChainId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let network = match self.network_id {
+            NetworkId::Mainnet => "mainnet",
+            NetworkId::Testnet => "testnet",
+        };
+        write!(f, "{}:{}", network, self.chain_id)
+    }
+}
+
+impl FromStr for ChainId {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (network, chain_id) = value.split_once(':').ok_or("Expected <network>:<chain_id>")?;
+        let network_id = match network {
+            "mainnet" => NetworkId::Mainnet,
+            "testnet" => NetworkId::Testnet,
+            _ => return Err(format!("Unknown network {}", network)),
+        };
+        let chain_id = chain_id.parse().map_err(|_| format!("Invalid chain id {}", chain_id))?;
+        Ok(Self::new(network_id, chain_id))
+    }
+}
+
+impl ChainId {
+    pub fn new(network_id: NetworkId, chain_id: u64) -> Self {
+        Self { network_id, chain_id }
+    }
+    
+    pub fn ethereum_mainnet() -> Self {
+        Self {
+            network_id: NetworkId::Mainnet,
+            chain_id: 1,
+        }
+    }
+    
+    pub fn ethereum_sepolia() -> Self {
+        Self {
+            network_id: NetworkId::Testnet,
+            chain_id: 11155111,
+        }
+    }
+    
+    pub fn near_mainnet() -> Self {
+        Self {
+            network_id: NetworkId::Mainnet,
+            chain_id: 0,
+        }
+    }
+    
+    pub fn near_testnet() -> Self {
+        Self {
+            network_id: NetworkId::Testnet,
+            chain_id: 0,
+        }
+    }
+}
+
+/// Swap parameters passed as the `msg` of `ft_transfer_call`
+#[derive(serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapMsg {
+    // The Merkle root of the secrets when `parts_count` is set
+    #[serde(alias = "secret_merkle_root")]
+    pub secret_hash: CryptoHash,
+    pub recipient: AccountId,
+    pub timeout_hours: u64,
+    // Canonical `ChainId` of a supported chain, e.g. `mainnet:1`
+    pub target_chain: String,
+    pub target_address: String,
+    // Staged timelocks, replacing `timeout_hours` when set
+    #[serde(default)]
+    pub timelocks: Option<Timelocks>,
+    #[serde(default)]
+    pub hash_algorithm: Option<HashAlgorithm>,
+    #[serde(default)]
+    pub relayer: Option<AccountId>,
+    // Split the lock into this many equal parts, each unlocked by its own secret
+    #[serde(default)]
+    pub parts_count: u32,
+    // Dutch auction deciding the share of the amount the recipient gets at withdrawal
+    #[serde(default)]
+    pub auction: Option<AuctionDetails>,
+    // Makes the lock's order hash unique, the block timestamp if not given
+    #[serde(default)]
+    pub salt: Option<u64>,
+}
+
+/// One lock of `initiate_swap_batch`, with the same parameters as `initiate_swap`
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InitiateSwapArgs {
+    pub secret_hash: CryptoHash,
+    pub recipient: AccountId,
+    pub amount: U128,
+    pub timeout_hours: u64,
+    pub target_chain: String,
+    pub target_address: String,
+    #[serde(default)]
+    pub hash_algorithm: Option<HashAlgorithm>,
+    #[serde(default)]
+    pub relayer: Option<AccountId>,
+    // Makes the lock's order hash unique, the block timestamp if not given
+    #[serde(default)]
+    pub salt: Option<u64>,
+}
+
+/// Chain-agnostic fields a lock's order hash is derived from, see `compute_order_hash`
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OrderHashParams {
+    pub secret_hash: CryptoHash,
+    // Account or address locking the funds
+    pub maker: String,
+    // Account or address the funds are locked for
+    pub taker: String,
+    pub amount: U128,
+    // Canonical `ChainId` string, e.g. `mainnet:1`
+    pub target_chain: String,
+    pub salt: u64,
+    // Defaults to sha256, like the lock's secret
+    #[serde(default)]
+    pub hash_algorithm: Option<HashAlgorithm>,
+}
+
+/// Dutch auction over the share of a lock paid to the recipient, 1inch Fusion style.
+/// Rates are fractions of the locked amount scaled by `RATE_PRECISION`, moving linearly
+/// from `start_rate` to `end_rate` so resolvers compete to fill as early as they can
+#[derive(
+    BorshDeserialize, BorshSerialize, serde::Serialize, serde::Deserialize,
+    Clone, Copy, Debug, PartialEq, Eq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionDetails {
+    // Timestamp the auction starts at, in nanoseconds
+    pub start_time: u64,
+    // Time for the rate to move from `start_rate` to `end_rate`, in seconds
+    pub duration: u64,
+    pub start_rate: U128,
+    pub end_rate: U128,
+}
+
+impl AuctionDetails {
+    /// Rate at `timestamp`, interpolated linearly and held at the ends outside the auction
+    pub fn rate_at(&self, timestamp: u64) -> Balance {
+        let (start_rate, end_rate) = (self.start_rate.0, self.end_rate.0);
+        let duration = self.duration * 1_000_000_000;
+        let elapsed = Balance::from(timestamp.saturating_sub(self.start_time).min(duration));
+        let duration = Balance::from(duration);
+        // Rates are at most `RATE_PRECISION`, so the products can't overflow
+        if end_rate >= start_rate {
+            start_rate + (end_rate - start_rate) * elapsed / duration
+        } else {
+            start_rate - (start_rate - end_rate) * elapsed / duration
+        }
+    }
+}
+
+/// Stage offsets of a lock, in seconds from its creation. The cancellation
+/// stage starts at the lock's `endtime`, which `extend_timelock` can push back
+#[derive(
+    BorshDeserialize, BorshSerialize, serde::Serialize, serde::Deserialize,
+    Clone, Copy, Debug, PartialEq, Eq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Timelocks {
+    // End of the finality lock, start of the recipient's exclusive withdrawal window
+    pub withdrawal: u64,
+    // Start of the window where anyone holding the secret can withdraw to the recipient
+    pub public_withdrawal: u64,
+    // Start of the cancellation stage, when the sender can refund
+    pub cancellation: u64,
+}
+
+impl Timelocks {
+    /// Plain HTLC behavior: only the recipient can withdraw, until the sender can refund
+    pub fn exclusive_until(cancellation: u64) -> Self {
+        Self { withdrawal: 0, public_withdrawal: cancellation, cancellation }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SwapStage {
+    FinalityLock,
+    ExclusiveWithdrawal,
+    PublicWithdrawal,
+    Cancellation,
+}
+
+/// Overall state of a lock contract, derived from its fields and the current time
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SwapStatus {
+    Pending { remaining_seconds: u64 },
+    Withdrawn,
+    Refunded,
+    // Pending but past `endtime`, so the sender can refund
+    Expired,
+}
+
+/// Asset held by a lock contract
+#[derive(
+    BorshDeserialize, BorshSerialize, serde::Serialize, serde::Deserialize,
+    Clone, Copy, Debug, PartialEq, Eq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum TokenType {
+    // The fungible token this HTLC escrows
+    Ft,
+    // Native NEAR, attached to `initiate_swap_near`
+    Near,
+}
+
+/// Hash function the secret is locked with. EVM HTLCs (1inch Fusion included) use keccak256
+#[derive(
+    BorshDeserialize, BorshSerialize, serde::Serialize, serde::Deserialize,
+    Clone, Copy, Debug, PartialEq, Eq, Default,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Keccak256,
+}
+
+impl HashAlgorithm {
+    pub fn hash(&self, preimage: &[u8]) -> CryptoHash {
+        let hash = match self {
+            HashAlgorithm::Sha256 => env::sha256(preimage),
+            HashAlgorithm::Keccak256 => env::keccak256(preimage),
+        };
+        hash.try_into().expect("Invalid hash length")
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LockContract {
+    pub secret_hash: CryptoHash,
+    pub recipient: AccountId,
+    pub sender: AccountId,
+    pub amount: Balance,
+    pub endtime: u64,
+    pub withdrawn: bool,
+    pub refunded: bool,
+    pub preimage: String,
+    pub target_chain: String,
+    pub target_address: String,
+    pub hash_algorithm: HashAlgorithm,
+    // Total time added to `endtime` by `extend_timelock`, in nanoseconds
+    pub extension: u64,
+    // Set by the recipient to let the sender cancel before `endtime`
+    pub cancellation_approved: bool,
+    // Relayer paid `relayer_fee_bps` of the amount on withdrawal
+    pub relayer: Option<AccountId>,
+    pub relayer_fee_bps: u16,
+    pub token_type: TokenType,
+    // Token held by a `TokenType::Ft` lock
+    pub token: Option<AccountId>,
+    pub created_at: u64,
+    pub timelocks: Timelocks,
+    // Number of parts of a Merkle-tree lock, 0 for a single secret
+    pub parts_count: u32,
+    // Parts of a Merkle-tree lock already withdrawn
+    pub claimed_parts: Vec<u32>,
+    pub claimed_amount: Balance,
+    pub auction: Option<AuctionDetails>,
+}
+
+impl LockContract {
+    /// Amount unlocked by one part, the last part also getting the rounding remainder
+    pub fn part_amount(&self, part_index: u32) -> Balance {
+        let part = self.amount / Balance::from(self.parts_count);
+        if part_index == self.parts_count - 1 {
+            self.amount - part * Balance::from(self.parts_count - 1)
+        } else {
+            part
+        }
+    }
+
+    /// Stage of the swap at `timestamp`
+    pub fn stage(&self, timestamp: u64) -> SwapStage {
+        let offset = |seconds: u64| self.created_at + seconds * 1_000_000_000;
+        if timestamp < offset(self.timelocks.withdrawal) {
+            SwapStage::FinalityLock
+        } else if timestamp < offset(self.timelocks.public_withdrawal).min(self.endtime) {
+            SwapStage::ExclusiveWithdrawal
+        } else if timestamp < self.endtime {
+            SwapStage::PublicWithdrawal
+        } else {
+            SwapStage::Cancellation
+        }
+    }
+}
+
+/// Order signed off-chain by a maker and filled by a resolver with `fill_signed_order`.
+/// The maker signs the Borsh encoding of `(htlc_account_id, order)` with ed25519
+#[derive(
+    BorshDeserialize, BorshSerialize, serde::Serialize, serde::Deserialize,
+    Clone, Debug, PartialEq, Eq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SignedOrder {
+    pub maker: AccountId,
+    // Pulled from the maker with `transfer_from`, so it must support allowances like UnrealToken
+    pub token: AccountId,
+    pub amount: U128,
+    pub secret_hash: CryptoHash,
+    pub target_chain: String,
+    // Where the maker receives the other side of the swap
+    pub target_address: String,
+    pub timeout_hours: u64,
+    // Last timestamp the order can be filled at, in nanoseconds
+    pub deadline: u64,
+    // Unique per maker, so an order can only be filled once
+    pub salt: u64,
+}
+
+/// NEAR a relayer posted with a `complete_swap`, held until the swap is settled
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SafetyDeposit {
+    pub relayer: AccountId,
+    pub amount: Balance,
+}
+
+/// A transfer out of a lock on withdrawal, kept when it fails so it can be retried
+#[derive(
+    BorshDeserialize, BorshSerialize, serde::Serialize, serde::Deserialize,
+    Clone, Debug, PartialEq, Eq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Payout {
+    pub receiver_id: AccountId,
+    pub amount: U128,
+}
+
+/// Record of a cross-chain swap minted by `complete_swap`
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CompletedSwap {
+    pub source_chain: String,
+    pub source_address: String,
+    pub destination: AccountId,
+    pub amount: Balance,
+    pub preimage: String,
+    pub completed_at: u64,
+    pub relayer: AccountId,
+}
+
+/// Arbiter's ruling on a relayer's `complete_swap`
+#[derive(
+    BorshDeserialize, BorshSerialize, serde::Serialize, serde::Deserialize,
+    Clone, Debug, PartialEq, Eq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Adjudication {
+    // The completion was invalid, so the safety deposit went to the recovery address
+    Slashed { reason: String },
+    // The completion was valid, so the safety deposit went back to the relayer
+    Vindicated,
+}
+
+/// Implementation of Hash Time Locked Contract for UnrealToken on NEAR
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct UnrealHTLC {
+    // Token locked when `initiate_swap` doesn't name one, the UnrealToken contract by default
+    token: AccountId,
+    // Owner of the HTLC contract
+    owner_id: AccountId,
+    // Locked contracts by ID
+    lock_contracts: UnorderedMap<CryptoHash, LockContract>,
+    // Chain signature relayers - addresses allowed to complete cross-chain swaps, per chain
+    relayers: LookupMap<(AccountId, ChainId), bool>,
+    // Relayers trusted on every chain, which is what the original relayer set meant
+    all_chain_relayers: LookupMap<AccountId, bool>,
+    // Number of chains each relayer is authorized for, besides `all_chain_relayers`
+    relayer_chain_counts: LookupMap<AccountId, u32>,
+    // Accounts that are relayers for at least one chain, so they can be listed
+    relayer_accounts: UnorderedSet<AccountId>,
+    // Cross-chain completions already minted, keyed by their computed ID
+    completed_swaps: UnorderedMap<CryptoHash, CompletedSwap>,
+    // IDs of the pending lock contracts of each sender and recipient
+    by_sender: LookupMap<AccountId, Vector<CryptoHash>>,
+    by_recipient: LookupMap<AccountId, Vector<CryptoHash>>,
+    // NEAR a relayer must attach to `complete_swap`
+    safety_deposit_amount: Balance,
+    // Safety deposits awaiting the arbiter's ruling, keyed by completed swap ID
+    safety_deposits: LookupMap<CryptoHash, SafetyDeposit>,
+    // Share of the amount paid to a swap's relayer on withdrawal, in basis points
+    relayer_fee_bps: u16,
+    // Chain signatures MPC contract signing `execute_on_evm` transactions
+    mpc_signer: AccountId,
+    // Key derivation path of each relayer, `DEFAULT_DERIVATION_PATH` if unset
+    derivation_paths: LookupMap<AccountId, String>,
+    // Amount held by unsettled locks, per token and in native NEAR
+    total_locked: LookupMap<AccountId, Balance>,
+    total_locked_near: Balance,
+    // Number of locks neither withdrawn nor refunded
+    active_swap_count: u64,
+    // Locks with an outgoing call whose callback hasn't run yet
+    in_flight: LookupMap<CryptoHash, bool>,
+    // Receives the funds of locks recovered with `emergency_recover`
+    recovery_address: AccountId,
+    // Chains swaps can target
+    supported_chains: UnorderedSet<ChainId>,
+    // Tokens locks can hold besides the default token, so any contract can't open locks
+    // by calling `ft_on_transfer`
+    supported_tokens: UnorderedSet<AccountId>,
+    // Bounds on the time until a lock can be refunded
+    min_timeout_hours: u64,
+    max_timeout_hours: u64,
+    // Salts of the signed orders each maker had filled
+    filled_orders: LookupMap<(AccountId, u64), bool>,
+    // Keys makers registered for signing orders
+    order_keys: LookupMap<AccountId, PublicKey>,
+    // Share of each `ft_transfer_call` deposit skimmed for the relayer pool, in basis points
+    protocol_fee_bps: u16,
+    // Receives the protocol fee
+    fee_treasury: AccountId,
+    // Smallest amount a lock can hold. Partial fill remainders below it are swept to the
+    // fee treasury on refund rather than paid out
+    min_swap_amount: Balance,
+    // Secret hashes known to be compromised, which new locks can't use
+    blocked_secret_hashes: LookupMap<CryptoHash, bool>,
+    // Resolvers allowed to fill orders, a role apart from the relayers completing swaps
+    resolvers: LookupMap<AccountId, bool>,
+    // Rules on disputed completions, kept apart from the owner
+    arbiter: Option<AccountId>,
+    // Arbiter rulings, keyed by completed swap ID
+    adjudications: LookupMap<CryptoHash, Adjudication>,
+    // Payouts that failed, awaiting `retry_failed_payouts`, keyed by lock ID
+    failed_payouts: LookupMap<CryptoHash, Vec<Payout>>,
+}
+
+#[near_bindgen]
+impl UnrealHTLC {
+    #[init]
+    pub fn new() -> Self {
+        require!(!env::state_exists(), "Already initialized");
+        
+        // Hardcoded token account ID for the Unreal Token contract
+        //TODO: refactor lator on to init arg
+        let token_account_id = "token.unrealai.near".parse().unwrap();
+        
+        let mut this = Self {
+            token: token_account_id,
+            owner_id: env::predecessor_account_id(),
+            lock_contracts: UnorderedMap::new(b"l"),
+            relayers: LookupMap::new(b"R"),
+            all_chain_relayers: LookupMap::new(b"r"),
+            relayer_chain_counts: LookupMap::new(b"n"),
+            relayer_accounts: UnorderedSet::new(b"A"),
+            completed_swaps: UnorderedMap::new(b"C"),
+            by_sender: LookupMap::new(b"s"),
+            by_recipient: LookupMap::new(b"p"),
+            safety_deposit_amount: 0,
+            safety_deposits: LookupMap::new(b"d"),
+            relayer_fee_bps: 0,
+            mpc_signer: DEFAULT_MPC_SIGNER.parse().unwrap(),
+            derivation_paths: LookupMap::new(b"k"),
+            total_locked: LookupMap::new(b"t"),
+            total_locked_near: 0,
+            active_swap_count: 0,
+            in_flight: LookupMap::new(b"i"),
+            recovery_address: env::predecessor_account_id(),
+            supported_chains: UnorderedSet::new(b"h"),
+            supported_tokens: UnorderedSet::new(b"T"),
+            min_timeout_hours: MIN_TIMEOUT_HOURS,
+            max_timeout_hours: MAX_TIMEOUT_HOURS,
+            filled_orders: LookupMap::new(b"o"),
+            order_keys: LookupMap::new(b"O"),
+            protocol_fee_bps: 0,
+            fee_treasury: env::predecessor_account_id(),
+            min_swap_amount: 0,
+            blocked_secret_hashes: LookupMap::new(b"B"),
+            resolvers: LookupMap::new(b"W"),
+            arbiter: None,
+            adjudications: LookupMap::new(b"a"),
+            failed_payouts: LookupMap::new(b"f"),
+        };
+        this.supported_chains.insert(&ChainId::ethereum_mainnet());
+        this.supported_chains.insert(&ChainId::ethereum_sepolia());
+        this
+    }
+    
+    /// Add an account as a relayer for chain signatures on `chain_id`
+    pub fn add_relayer(&mut self, account_id: AccountId, chain_id: ChainId) {
+        self.assert_owner();
+        let key = (account_id.clone(), chain_id.clone());
+        if self.relayers.insert(&key, &true).is_none() {
+            let count = self.relayer_chain_counts.get(&account_id).unwrap_or(0);
+            self.relayer_chain_counts.insert(&account_id, &(count + 1));
+        }
+        self.relayer_accounts.insert(&account_id);
+        log!("Added relayer: {} for chain {}", account_id, chain_id);
+    }
+    
+    /// Remove a relayer from `chain_id`
+    pub fn remove_relayer(&mut self, account_id: AccountId, chain_id: ChainId) {
+        self.assert_owner();
+        let key = (account_id.clone(), chain_id.clone());
+        if self.relayers.remove(&key).is_some() {
+            let count = self.relayer_chain_counts.get(&account_id).unwrap_or(1) - 1;
+            if count == 0 {
+                self.relayer_chain_counts.remove(&account_id);
+            } else {
+                self.relayer_chain_counts.insert(&account_id, &count);
+            }
+        }
+        self.internal_sync_relayer_account(&account_id);
+        log!("Removed relayer: {} for chain {}", account_id, chain_id);
+    }
+    
+    /// Authorize a relayer on every chain at once
+    pub fn add_relayer_for_all_chains(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.all_chain_relayers.insert(&account_id, &true);
+        self.relayer_accounts.insert(&account_id);
+        log!("Added relayer: {} for all chains", account_id);
+    }
+    
+    /// Remove a relayer's authorization for every chain. Per-chain authorizations are kept
+    pub fn remove_relayer_for_all_chains(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.all_chain_relayers.remove(&account_id);
+        self.internal_sync_relayer_account(&account_id);
+        log!("Removed relayer: {} for all chains", account_id);
+    }
+    
+    /// Check if an account is a relayer for `chain_id`
+    pub fn is_relayer_for(&self, account_id: AccountId, chain_id: ChainId) -> bool {
+        self.all_chain_relayers.get(&account_id).unwrap_or(false)
+            || self.relayers.get(&(account_id, chain_id)).unwrap_or(false)
+    }
+    
+    /// Check if an account is a relayer for at least one chain
+    pub fn is_relayer(&self, account_id: &AccountId) -> bool {
+        self.all_chain_relayers.get(account_id).unwrap_or(false)
+            || self.relayer_chain_counts.get(account_id).unwrap_or(0) > 0
+    }
+
+    /// List the accounts that are relayers for at least one chain
+    pub fn get_relayers(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<AccountId> {
+        self.relayer_accounts
+            .iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+
+    /// Get the number of accounts that are relayers for at least one chain
+    pub fn relayer_count(&self) -> u64 {
+        self.relayer_accounts.len()
+    }
+
+    // Drop an account from the listed relayers once it isn't a relayer for any chain
+    fn internal_sync_relayer_account(&mut self, account_id: &AccountId) {
+        if !self.is_relayer(account_id) {
+            self.relayer_accounts.remove(account_id);
+        }
+    }
+
+    /// Block a secret hash whose secret is known, e.g. published by a misconfigured client
+    pub fn block_secret_hash(&mut self, secret_hash: CryptoHash) {
+        self.assert_owner();
+        self.blocked_secret_hashes.insert(&secret_hash, &true);
+        log!("Blocked secret hash: {}", hex::encode(secret_hash));
+    }
+
+    /// Allow a blocked secret hash again
+    pub fn unblock_secret_hash(&mut self, secret_hash: CryptoHash) {
+        self.assert_owner();
+        self.blocked_secret_hashes.remove(&secret_hash);
+        log!("Unblocked secret hash: {}", hex::encode(secret_hash));
+    }
+
+    /// Check if new locks are rejected for a secret hash
+    pub fn is_secret_hash_blocked(&self, secret_hash: CryptoHash) -> bool {
+        secret_hash == [0; 32] || self.blocked_secret_hashes.get(&secret_hash).unwrap_or(false)
+    }
+
+    /// Whitelist a resolver, allowed to fill signed orders and partial fill locks
+    pub fn add_resolver(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.resolvers.insert(&account_id, &true);
+        log!("Added resolver: {}", account_id);
+    }
+
+    /// Remove a resolver from the whitelist
+    pub fn remove_resolver(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.resolvers.remove(&account_id);
+        log!("Removed resolver: {}", account_id);
+    }
+
+    /// Check if an account is a whitelisted resolver
+    pub fn is_resolver(&self, account_id: AccountId) -> bool {
+        self.resolvers.get(&account_id).unwrap_or(false)
+    }
+
+    /// Get the share of each withdrawal paid to the swap's relayer, in basis points
+    pub fn get_relayer_fee_bps(&self) -> u16 {
+        self.relayer_fee_bps
+    }
+
+    /// Set the relayer fee for swaps initiated from now on
+    pub fn set_relayer_fee_bps(&mut self, fee_bps: u16) {
+        self.assert_owner();
+        require!(fee_bps <= MAX_FEE_BPS, "Fee cannot exceed 100%");
+        self.relayer_fee_bps = fee_bps;
+        log!("Relayer fee set to {} bps", fee_bps);
+    }
+
+    /// Get the share of each `ft_transfer_call` deposit paid to the fee treasury, in basis points
+    pub fn get_protocol_fee_bps(&self) -> u16 {
+        self.protocol_fee_bps
+    }
+
+    /// Get the account receiving the protocol fee
+    pub fn get_fee_treasury(&self) -> AccountId {
+        self.fee_treasury.clone()
+    }
+
+    /// Set the protocol fee taken from swaps initiated through `ft_transfer_call`, and the
+    /// account receiving it. The treasury must be registered with every token swapped
+    pub fn set_protocol_fee(&mut self, fee_bps: u16, fee_treasury: AccountId) {
+        self.assert_owner();
+        require!(fee_bps <= MAX_PROTOCOL_FEE_BPS, "Protocol fee cannot exceed 1%");
+        self.protocol_fee_bps = fee_bps;
+        self.fee_treasury = fee_treasury;
+        log!("Protocol fee set to {} bps, paid to {}", fee_bps, self.fee_treasury);
+    }
+
+    /// Get the smallest amount a swap can lock
+    pub fn min_swap_amount(&self) -> U128 {
+        U128(self.min_swap_amount)
+    }
+
+    /// Set the smallest amount a swap can lock, after the protocol fee. Also the threshold
+    /// under which the unclaimed remainder of a partial fill lock is swept to the fee treasury
+    pub fn set_min_swap_amount(&mut self, amount: U128) {
+        self.assert_owner();
+        self.min_swap_amount = amount.0;
+        log!("Minimum swap amount set to {}", amount.0);
+    }
+
+    /// Get the token locked by `initiate_swap` when none is given
+    pub fn get_default_token(&self) -> AccountId {
+        self.token.clone()
+    }
+
+    /// Set the token locked by `initiate_swap` when none is given
+    pub fn set_default_token(&mut self, token: AccountId) {
+        self.assert_owner();
+        self.token = token;
+        log!("Default token set to {}", self.token);
+    }
+
+    /// Get the minimum and maximum time until a lock can be refunded, in hours
+    pub fn get_timeout_bounds(&self) -> (u64, u64) {
+        (self.min_timeout_hours, self.max_timeout_hours)
+    }
+
+    /// Set the minimum and maximum time until a lock can be refunded, in hours
+    pub fn set_timeout_bounds(&mut self, min_timeout_hours: u64, max_timeout_hours: u64) {
+        self.assert_owner();
+        require!(min_timeout_hours > 0, "Minimum timeout must be at least 1 hour");
+        require!(min_timeout_hours <= max_timeout_hours, "Minimum timeout exceeds the maximum");
+        self.min_timeout_hours = min_timeout_hours;
+        self.max_timeout_hours = max_timeout_hours;
+        log!("Timeout bounds set to {}..={} hours", min_timeout_hours, max_timeout_hours);
+    }
+
+    /// Get the chains swaps can target
+    pub fn supported_chains(&self) -> Vec<ChainId> {
+        self.supported_chains.to_vec()
+    }
+
+    /// Allow swaps to target `chain_id`
+    pub fn add_supported_chain(&mut self, chain_id: ChainId) {
+        self.assert_owner();
+        log!("Added supported chain {}", chain_id);
+        self.supported_chains.insert(&chain_id);
+    }
+
+    /// Stop new swaps from targeting `chain_id`, existing locks are unaffected
+    pub fn remove_supported_chain(&mut self, chain_id: ChainId) {
+        self.assert_owner();
+        log!("Removed supported chain {}", chain_id);
+        self.supported_chains.remove(&chain_id);
+    }
+
+    /// Get the tokens locks can hold besides the default token
+    pub fn supported_tokens(&self) -> Vec<AccountId> {
+        self.supported_tokens.to_vec()
+    }
+
+    /// Whether new locks can hold `token`, which the default token always can
+    pub fn is_supported_token(&self, token: AccountId) -> bool {
+        token == self.token || self.supported_tokens.contains(&token)
+    }
+
+    /// Allow locks to hold `token`
+    pub fn add_supported_token(&mut self, token: AccountId) {
+        self.assert_owner();
+        log!("Added supported token {}", token);
+        self.supported_tokens.insert(&token);
+    }
+
+    /// Stop new locks from holding `token`, existing locks are unaffected
+    pub fn remove_supported_token(&mut self, token: AccountId) {
+        self.assert_owner();
+        log!("Removed supported token {}", token);
+        self.supported_tokens.remove(&token);
+    }
+
+    /// Get the account receiving funds recovered with `emergency_recover`
+    pub fn get_recovery_address(&self) -> AccountId {
+        self.recovery_address.clone()
+    }
+
+    /// Set the account receiving funds recovered with `emergency_recover`
+    pub fn set_recovery_address(&mut self, recovery_address: AccountId) {
+        self.assert_owner();
+        self.recovery_address = recovery_address;
+        log!("Recovery address set to {}", self.recovery_address);
+    }
+
+    /// Locks tokens sent with `ft_transfer_call` on a supported NEP-141 token, with the swap
+    /// parameters JSON-encoded in `msg` (see `SwapMsg`). The protocol fee is sent to the
+    /// fee treasury and the rest locked. If the swap can't be created, the call panics
+    /// and the token refunds the sender. So it does with the fee if its transfer fails
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let swap: SwapMsg = near_sdk::serde_json::from_str(&msg).expect("Invalid swap message");
+        let token = env::predecessor_account_id();
+        let (fee, locked) = split_relayer_fee(amount.0, self.protocol_fee_bps);
+        let lock_contract_id = self.internal_create_lock(
+            swap.secret_hash,
+            swap.recipient.clone(),
+            locked,
+            swap.timeout_hours,
+            swap.target_chain,
+            swap.target_address,
+            swap.hash_algorithm,
+            swap.relayer,
+            TokenType::Ft,
+            Some(token.clone()),
+            sender_id.clone(),
+            swap.timelocks,
+            swap.parts_count,
+            swap.auction,
+            swap.salt.unwrap_or_else(env::block_timestamp),
+        );
+        log!(
+            "Swap initiated with ID: {}, from: {}, to: {}, amount: {}, protocol fee: {}, token: {}",
+            hex::encode(lock_contract_id),
+            sender_id,
+            swap.recipient,
+            locked,
+            fee,
+            token
+        );
+        
+        if fee == 0 {
+            // Keep the whole amount
+            return PromiseOrValue::Value(U128(0));
+        }
+        ext_fungible_token::ext(token)
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(
+                self.fee_treasury.clone(),
+                fee.into(),
+                Some(format!("Protocol fee for swap {}", hex::encode(lock_contract_id))),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_PROTOCOL_FEE_CALLBACK)
+                    .on_protocol_fee_transfer(lock_contract_id, U128(fee)),
+            )
+            .into()
+    }
+
+    /// Callback of the protocol fee transfer of `ft_on_transfer`, returning the amount the
+    /// token refunds to the sender. A failed transfer leaves the fee with the HTLC and
+    /// outside of any lock, so it's returned
+    #[private]
+    pub fn on_protocol_fee_transfer(&mut self, lock_contract_id: CryptoHash, fee: U128) -> U128 {
+        if is_promise_success(0) {
+            return U128(0);
+        }
+        log!(
+            "Protocol fee transfer failed for swap {}, refunding {} to the sender",
+            hex::encode(lock_contract_id),
+            fee.0
+        );
+        fee
+    }
+
+    /// Deprecated: the token rejects the HTLC transferring to itself, so this could never
+    /// receive the tokens. Call `ft_transfer_call` on the token with a `SwapMsg` instead
+    #[payable]
+    #[allow(unused_variables)]
+    pub fn initiate_swap(
+        &mut self,
+        secret_hash: CryptoHash,
+        recipient: AccountId,
+        amount: U128,
+        timeout_hours: u64,
+        target_chain: String,
+        target_address: String,
+        hash_algorithm: Option<HashAlgorithm>,
+        relayer: Option<AccountId>,
+        token: Option<AccountId>,
+    ) -> CryptoHash {
+        env::panic_str("initiate_swap is deprecated, call ft_transfer_call on the token with a SwapMsg")
+    }
+
+    /// Initiates a cross-chain swap by locking the attached NEAR in the contract
+    #[payable]
+    pub fn initiate_swap_near(
+        &mut self,
+        secret_hash: CryptoHash,
+        recipient: AccountId,
+        timeout_hours: u64,
+        target_chain: String,
+        target_address: String,
+        hash_algorithm: Option<HashAlgorithm>,
+        relayer: Option<AccountId>,
+    ) -> CryptoHash {
+        let amount = env::attached_deposit().as_yoctonear();
+        let lock_contract_id = self.internal_create_lock(
+            secret_hash,
+            recipient.clone(),
+            amount,
+            timeout_hours,
+            target_chain,
+            target_address,
+            hash_algorithm,
+            relayer,
+            TokenType::Near,
+            None,
+            env::predecessor_account_id(),
+            None,
+            0,
+            None,
+            env::block_timestamp(),
+        );
+        
+        log!(
+            "Swap initiated with ID: {}, from: {}, to: {}, amount: {} yoctoNEAR",
+            hex::encode(lock_contract_id),
+            env::predecessor_account_id(),
+            recipient,
+            amount
+        );
+        
+        lock_contract_id
+    }
+
+    /// Locks several amounts of `token` (the default token if not given) from the caller in
+    /// one transaction, returning the lock IDs in the order of `swaps`. The total is pulled
+    /// once from the caller's allowance to this contract; if that fails or credits less than
+    /// the total, every lock of the batch is dropped. Any invalid entry fails the whole batch
+    pub fn initiate_swap_batch(
+        &mut self,
+        swaps: Vec<InitiateSwapArgs>,
+        token: Option<AccountId>,
+    ) -> Vec<CryptoHash> {
+        require!(!swaps.is_empty(), "No swaps given");
+        require!(swaps.len() <= MAX_SWAP_BATCH, "Too many swaps in batch");
+        let sender = env::predecessor_account_id();
+        let token = token.unwrap_or_else(|| self.token.clone());
+        
+        let mut seen = std::collections::HashSet::new();
+        let mut total: Balance = 0;
+        for swap in &swaps {
+            let target_chain = ChainId::from_str(&swap.target_chain)
+                .unwrap_or_else(|e| env::panic_str(&e))
+                .to_string();
+            let lock_contract_id = order_hash(&OrderHashParams {
+                secret_hash: swap.secret_hash,
+                maker: sender.to_string(),
+                taker: swap.recipient.to_string(),
+                amount: swap.amount,
+                target_chain,
+                salt: swap.salt.unwrap_or_else(env::block_timestamp),
+                hash_algorithm: swap.hash_algorithm,
+            });
+            require!(seen.insert(lock_contract_id), "Duplicate swap in batch");
+            total = total
+                .checked_add(swap.amount.0)
+                .unwrap_or_else(|| env::panic_str("Batch amount overflow"));
+        }
+        
+        let lock_contract_ids: Vec<CryptoHash> = swaps
+            .into_iter()
+            .map(|swap| {
+                let lock_contract_id = self.internal_create_lock(
+                    swap.secret_hash,
+                    swap.recipient,
+                    swap.amount.0,
+                    swap.timeout_hours,
+                    swap.target_chain,
+                    swap.target_address,
+                    swap.hash_algorithm,
+                    swap.relayer,
+                    TokenType::Ft,
+                    Some(token.clone()),
+                    sender.clone(),
+                    None,
+                    0,
+                    None,
+                    swap.salt.unwrap_or_else(env::block_timestamp),
+                );
+                // Nothing can be done with the lock until the tokens arrived
+                self.in_flight.insert(&lock_contract_id, &true);
+                lock_contract_id
+            })
+            .collect();
+        
+        let callback_gas = Gas::from_gas(
+            GAS_FOR_BATCH_CALLBACK.as_gas() * lock_contract_ids.len() as u64
+                + GAS_FOR_FT_TRANSFER.as_gas(),
+        );
+        ext_allowance_token::ext(token)
+            .with_static_gas(remaining_gas(callback_gas, GAS_FOR_TRANSFER_FROM))
+            .transfer_from(sender.clone(), env::current_account_id(), U128(total))
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(callback_gas)
+                    .on_swap_batch_funded(lock_contract_ids.clone()),
+            );
+        
+        log!(
+            "Swap batch of {} locks initiated from: {}, total amount: {}",
+            lock_contract_ids.len(),
+            sender,
+            total
+        );
+        lock_contract_ids
+    }
+
+    /// Callback of `initiate_swap_batch` pulling the caller's tokens. Unless the whole total
+    /// arrived, every lock of the batch is dropped and whatever did arrive is sent back.
+    /// Returns whether the locks are funded
+    #[private]
+    pub fn on_swap_batch_funded(&mut self, lock_contract_ids: Vec<CryptoHash>) -> bool {
+        for lock_contract_id in &lock_contract_ids {
+            self.in_flight.remove(lock_contract_id);
+        }
+        let lock_contracts: Vec<LockContract> =
+            lock_contract_ids.iter().filter_map(|id| self.lock_contracts.get(id)).collect();
+        let total: Balance = lock_contracts.iter().map(|lock_contract| lock_contract.amount).sum();
+        let received = transferred_amount();
+        if received == Some(total) {
+            return true;
+        }
+        
+        // A token charging a transfer fee credits less than the batch was created for
+        if let (Some(received), Some(lock_contract)) = (received, lock_contracts.first()) {
+            self.internal_return_short_funding(lock_contract, received);
+        }
+        for lock_contract_id in &lock_contract_ids {
+            self.internal_drop_lock(lock_contract_id);
+        }
+        log!("Swap batch funding failed, dropped {} locks", lock_contract_ids.len());
+        false
+    }
+
+    /// Get the key registered by `maker` for signing orders
+    pub fn get_order_key(&self, maker: AccountId) -> Option<PublicKey> {
+        self.order_keys.get(&maker)
+    }
+
+    /// Register the ed25519 key the caller signs orders with, `None` removes it.
+    /// Implicit accounts can always sign with the key they are named after
+    pub fn set_order_key(&mut self, public_key: Option<PublicKey>) {
+        let maker = env::predecessor_account_id();
+        match public_key {
+            Some(public_key) => {
+                assert_ed25519(&public_key);
+                self.order_keys.insert(&maker, &public_key);
+            }
+            None => {
+                self.order_keys.remove(&maker);
+            }
+        }
+        log!("Order key updated for {}", maker);
+    }
+
+    /// Check if the order of `maker` with `salt` was filled
+    pub fn is_order_filled(&self, maker: AccountId, salt: u64) -> bool {
+        self.filled_orders.get(&(maker, salt)).unwrap_or(false)
+    }
+
+    /// Fill an order signed off-chain by its maker, locking the maker's tokens with the
+    /// caller as recipient. `public_key` is `ed25519:<base58>` and `signature` the hex encoded
+    /// signature. The tokens are pulled from the maker's allowance to this contract; if that
+    /// fails or credits less than the order's amount, the lock is dropped and the order can be
+    /// filled again
+    pub fn fill_signed_order(
+        &mut self,
+        order: SignedOrder,
+        signature: String,
+        public_key: String,
+    ) -> CryptoHash {
+        require!(self.is_resolver(env::predecessor_account_id()), "Not a whitelisted resolver");
+        require!(env::block_timestamp() <= order.deadline, "Order expired");
+        let order_key = (order.maker.clone(), order.salt);
+        require!(self.filled_orders.get(&order_key).is_none(), "Order already filled");
+        
+        let public_key: PublicKey =
+            public_key.parse().unwrap_or_else(|_| env::panic_str("Invalid public key"));
+        assert_ed25519(&public_key);
+        let key_bytes: [u8; 32] = public_key.as_bytes()[1..]
+            .try_into()
+            .unwrap_or_else(|_| env::panic_str("Invalid public key"));
+        require!(
+            self.order_keys.get(&order.maker).as_ref() == Some(&public_key)
+                || order.maker.as_str() == hex::encode(key_bytes),
+            "Public key is not authorized for maker"
+        );
+        let signature: [u8; 64] = decode_hex(&signature, "Invalid signature")
+            .try_into()
+            .unwrap_or_else(|_| env::panic_str("Invalid signature"));
+        let message = borsh::to_vec(&(env::current_account_id(), &order)).unwrap();
+        require!(env::ed25519_verify(&signature, &message, &key_bytes), "Invalid signature");
+        self.filled_orders.insert(&order_key, &true);
+        
+        let lock_contract_id = self.internal_create_lock(
+            order.secret_hash,
+            env::predecessor_account_id(),
+            order.amount.0,
+            order.timeout_hours,
+            order.target_chain,
+            order.target_address,
+            None,
+            None,
+            TokenType::Ft,
+            Some(order.token.clone()),
+            order.maker.clone(),
+            None,
+            0,
+            None,
+            order.salt,
+        );
+        // Nothing can be done with the lock until the tokens arrived
+        self.in_flight.insert(&lock_contract_id, &true);
+        
+        ext_allowance_token::ext(order.token)
+            .with_static_gas(remaining_gas(GAS_FOR_ORDER_CALLBACK, GAS_FOR_TRANSFER_FROM))
+            .transfer_from(order.maker.clone(), env::current_account_id(), order.amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ORDER_CALLBACK)
+                    .on_signed_order_funded(lock_contract_id, order.maker, order.salt),
+            );
+        
+        lock_contract_id
+    }
+
+    /// Callback of `fill_signed_order` pulling the maker's tokens. Unless the whole amount
+    /// arrived, the lock is dropped, whatever did arrive sent back and the order's salt freed.
+    /// Returns whether the lock is funded
+    #[private]
+    pub fn on_signed_order_funded(
+        &mut self,
+        lock_contract_id: CryptoHash,
+        maker: AccountId,
+        salt: u64,
+    ) -> bool {
+        self.in_flight.remove(&lock_contract_id);
+        let lock_contract = match self.lock_contracts.get(&lock_contract_id) {
+            Some(lock_contract) => lock_contract,
+            None => return false,
+        };
+        let received = transferred_amount();
+        if received == Some(lock_contract.amount) {
+            return true;
+        }
+        
+        // A token charging a transfer fee credits less than the order's amount
+        if let Some(received) = received {
+            self.internal_return_short_funding(&lock_contract, received);
+        }
+        self.internal_drop_lock(&lock_contract_id);
+        self.filled_orders.remove(&(maker.clone(), salt));
+        log!(
+            "Signed order funding failed with ID: {}, maker: {}, salt: {}",
+            hex::encode(lock_contract_id),
+            maker,
+            salt
+        );
+        false
+    }
+
+    // Send tokens that arrived short of what a lock was created for back to its sender
+    fn internal_return_short_funding(&self, lock_contract: &LockContract, amount: Balance) {
+        if amount == 0 {
+            return;
+        }
+        ext_fungible_token::ext(
+            lock_contract.token.clone().expect("Token locks always record their token"),
+        )
+        .with_attached_deposit(ONE_YOCTO)
+        .with_static_gas(GAS_FOR_FT_TRANSFER)
+        .ft_transfer(
+            lock_contract.sender.clone(),
+            amount.into(),
+            Some("Short swap funding returned".to_string()),
+        );
+    }
+
+    // Forget a lock whose funds never arrived
+    fn internal_drop_lock(&mut self, lock_contract_id: &CryptoHash) {
+        if let Some(mut lock_contract) = self.lock_contracts.remove(lock_contract_id) {
+            self.remove_from_indexes(lock_contract_id, &lock_contract);
+            lock_contract.refunded = true;
+            self.internal_release_locked(&lock_contract, lock_contract.amount);
+        }
+    }
+
+    // Helper to validate and store a new lock contract
+    fn internal_create_lock(
+        &mut self,
+        secret_hash: CryptoHash,
+        recipient: AccountId,
+        amount: Balance,
+        timeout_hours: u64,
+        target_chain: String,
+        target_address: String,
+        hash_algorithm: Option<HashAlgorithm>,
+        relayer: Option<AccountId>,
+        token_type: TokenType,
+        token: Option<AccountId>,
+        sender: AccountId,
+        timelocks: Option<Timelocks>,
+        parts_count: u32,
+        auction: Option<AuctionDetails>,
+        salt: u64,
+    ) -> CryptoHash {
+        require!(amount > 0, "Amount must be greater than 0");
+        require!(amount >= self.min_swap_amount, "Amount below the minimum swap amount");
+        // Anyone could withdraw a lock behind a zero or leaked secret hash right away
+        require!(!self.is_secret_hash_blocked(secret_hash), "Secret hash is blocked");
+        require!(amount >= Balance::from(parts_count), "Amount too small for the number of parts");
+        // Only chains the swap can be completed on, stored in canonical form
+        let target_chain_id = ChainId::from_str(&target_chain).unwrap_or_else(|e| env::panic_str(&e));
+        require!(self.supported_chains.contains(&target_chain_id), "Unsupported target chain");
+        let target_chain = target_chain_id.to_string();
+        if let Some(token) = &token {
+            require!(self.is_supported_token(token.clone()), "Unsupported token");
+        }
+        if let Some(relayer) = &relayer {
+            require!(self.is_relayer(relayer), "Not an authorized relayer");
+        }
+        if let Some(auction) = &auction {
+            require!(parts_count == 0, "Auctions are not supported for partial fill locks");
+            require!(auction.duration > 0, "Auction duration must be positive");
+            for rate in [auction.start_rate.0, auction.end_rate.0] {
+                require!(rate > 0 && rate <= RATE_PRECISION, "Auction rate out of range");
+            }
+        }
+        
+        let timelocks = timelocks
+            .unwrap_or_else(|| Timelocks::exclusive_until(timeout_hours.saturating_mul(3600)));
+        require!(
+            timelocks.withdrawal <= timelocks.public_withdrawal
+                && timelocks.public_withdrawal <= timelocks.cancellation,
+            "Timelock stages must be in order"
+        );
+        // The counterparty needs time to act, and state shouldn't stay locked forever
+        require!(
+            timelocks.cancellation >= self.min_timeout_hours * 3600,
+            "Timeout below the minimum"
+        );
+        require!(
+            timelocks.cancellation <= self.max_timeout_hours * 3600,
+            "Timeout above the maximum"
+        );
+        
+        // Calculate timeout timestamp (current timestamp + cancellation offset in nanoseconds)
+        let created_at = env::block_timestamp();
+        let endtime = created_at + timelocks.cancellation * 1_000_000_000;
+        
+        // The lock is keyed by its order hash, which the EVM side can derive as well
+        let lock_contract_id = order_hash(&OrderHashParams {
+            secret_hash,
+            maker: sender.to_string(),
+            taker: recipient.to_string(),
+            amount: U128(amount),
+            target_chain: target_chain.clone(),
+            salt,
+            hash_algorithm,
+        });
+        
+        // Make sure it doesn't already exist
+        require!(!self.has_lock_contract(lock_contract_id), "Lock contract already exists");
+        
+        // Create the lock contract
+        let lock_contract = LockContract {
+            secret_hash,
+            recipient,
+            sender,
+            amount,
+            endtime,
+            withdrawn: false,
+            refunded: false,
+            preimage: String::new(),
+            target_chain,
+            target_address,
+            hash_algorithm: hash_algorithm.unwrap_or_default(),
+            extension: 0,
+            cancellation_approved: false,
+            // The fee is fixed at initiation so it can't be changed at claim time
+            relayer_fee_bps: if relayer.is_some() { self.relayer_fee_bps } else { 0 },
+            relayer,
+            token_type,
+            token,
+            created_at,
+            timelocks,
+            parts_count,
+            claimed_parts: Vec::new(),
+            claimed_amount: 0,
+            auction,
+        };
+        
+        // Store the lock contract
+        self.lock_contracts.insert(&lock_contract_id, &lock_contract);
+        self.add_to_indexes(&lock_contract_id, &lock_contract);
+        self.internal_add_locked(&lock_contract);
+        
+        HtlcEvent::SwapInitiated(&[SwapInitiated {
+            lock_contract_id: hex::encode(lock_contract_id),
+            sender: &lock_contract.sender,
+            recipient: &lock_contract.recipient,
+            amount: U128(lock_contract.amount),
+            token: lock_contract.token.as_ref(),
+            secret_hash: hex::encode(lock_contract.secret_hash),
+            hash_algorithm: lock_contract.hash_algorithm,
+            endtime: lock_contract.endtime,
+            target_chain: &lock_contract.target_chain,
+            target_address: &lock_contract.target_address,
+        }])
+        .emit();
+        
+        lock_contract_id
+    }
+
+    /// Deprecated: callback of the `initiate_swap` flow. If the deposit failed, the lock is
+    /// dropped. Returns whether the lock is funded
+    #[private]
+    pub fn on_ft_transfer_call(
+        &mut self,
+        lock_contract_id: CryptoHash,
+        sender: AccountId,
+        recipient: AccountId,
+        amount: U128,
+    ) -> bool {
+        self.in_flight.remove(&lock_contract_id);
+        // Panicking would revert the guard release above and leave the lock guarded for good
+        if !is_promise_success(0) {
+            self.internal_drop_lock(&lock_contract_id);
+            log!("Token transfer failed for swap {}, dropped the lock", hex::encode(lock_contract_id));
+            return false;
+        }
+        
+        log!(
+            "Swap initiated with ID: {}, from: {}, to: {}, amount: {}",
+            hex::encode(lock_contract_id),
+            sender,
+            recipient,
+            amount.0
+        );
+        true
+    }
+
+    /// Withdraw tokens by revealing the secret
+    pub fn withdraw(
+        &mut self,
+        lock_contract_id: CryptoHash,
+        preimage: String,
+    ) -> bool {
+        // Verify the lock contract exists
+        require!(self.has_lock_contract(lock_contract_id), "Lock contract does not exist");
+        
+        self.assert_not_in_flight(&lock_contract_id);
+        
+        let lock_contract = self.lock_contracts.get(&lock_contract_id).unwrap();
+        assert_withdrawal_allowed(&lock_contract);
+        
+        self.internal_withdraw(lock_contract_id, lock_contract, preimage);
+        
+        true
+    }
+
+    /// Withdraw one part of a Merkle-tree lock by revealing that part's secret and its
+    /// proof against the root. Each part unlocks `amount / parts_count`. In the exclusive
+    /// window the recipient must also be a whitelisted resolver
+    pub fn withdraw_part(
+        &mut self,
+        lock_contract_id: CryptoHash,
+        part_index: u32,
+        preimage: String,
+        merkle_proof: Vec<CryptoHash>,
+    ) -> bool {
+        require!(self.has_lock_contract(lock_contract_id), "Lock contract does not exist");
+        
+        self.assert_not_in_flight(&lock_contract_id);
+        
+        let mut lock_contract = self.lock_contracts.get(&lock_contract_id).unwrap();
+        require!(lock_contract.parts_count > 0, "Not a partial fill lock");
+        assert_withdrawal_allowed(&lock_contract);
+        if lock_contract.stage(env::block_timestamp()) == SwapStage::ExclusiveWithdrawal {
+            require!(
+                self.is_resolver(env::predecessor_account_id()),
+                "Not a whitelisted resolver"
+            );
+        }
+        
+        require!(!lock_contract.withdrawn, "Already withdrawn");
+        require!(!lock_contract.refunded, "Already refunded");
+        require!(part_index < lock_contract.parts_count, "Invalid part index");
+        require!(!lock_contract.claimed_parts.contains(&part_index), "Part already claimed");
+        
+        // Verify the secret is the leaf at `part_index`
+        let leaf = merkle_leaf(lock_contract.hash_algorithm, part_index, preimage.as_bytes());
+        require!(
+            verify_merkle_proof(
+                lock_contract.hash_algorithm,
+                leaf,
+                &merkle_proof,
+                lock_contract.secret_hash
+            ),
+            "Invalid Merkle proof"
+        );
+        
+        // Update the lock contract, which is withdrawn once every part is claimed
+        let amount = lock_contract.part_amount(part_index);
+        lock_contract.claimed_parts.push(part_index);
+        lock_contract.claimed_amount += amount;
+        lock_contract.preimage = preimage;
+        lock_contract.withdrawn =
+            lock_contract.claimed_parts.len() == lock_contract.parts_count as usize;
+        self.lock_contracts.insert(&lock_contract_id, &lock_contract);
+        if lock_contract.withdrawn {
+            self.remove_from_indexes(&lock_contract_id, &lock_contract);
+        }
+        self.internal_release_locked(&lock_contract, amount);
+        
+        let (fee, payouts) = self.internal_pay_recipient(&lock_contract, amount);
+        self.internal_send_withdrawal(lock_contract_id, &lock_contract, payouts);
+        
+        log!(
+            "Swap part withdrawn with ID: {}, part: {}, preimage: {}, amount: {}, relayer fee: {}",
+            hex::encode(lock_contract_id),
+            part_index,
+            lock_contract.preimage,
+            amount,
+            fee
+        );
+        HtlcEvent::SwapWithdrawn(&[SwapWithdrawn {
+            lock_contract_id: hex::encode(lock_contract_id),
+            recipient: &lock_contract.recipient,
+            amount: U128(amount),
+            preimage: &lock_contract.preimage,
+            part_index: Some(part_index),
+            caller: &env::predecessor_account_id(),
+        }])
+        .emit();
+        
+        true
+    }
+
+    /// Withdraw a stuck swap to its recipient by revealing the secret. Anyone can call
+    /// this in the public withdrawal stage, once cancellation started only a refund is left
+    pub fn public_withdraw(&mut self, lock_contract_id: CryptoHash, preimage: String) -> bool {
+        require!(self.has_lock_contract(lock_contract_id), "Lock contract does not exist");
+        
+        let lock_contract = self.lock_contracts.get(&lock_contract_id).unwrap();
+        
+        match lock_contract.stage(env::block_timestamp()) {
+            SwapStage::PublicWithdrawal => {}
+            SwapStage::Cancellation => env::panic_str("Public withdrawal closed"),
+            _ => env::panic_str("Public withdrawal not open yet"),
+        }
+        
+        self.internal_withdraw(lock_contract_id, lock_contract, preimage);
+        
+        log!(
+            "Public withdrawal with ID: {}, triggered by: {}",
+            hex::encode(lock_contract_id),
+            env::predecessor_account_id()
+        );
+        
+        true
+    }
+
+    // Helper to check the secret, mark the lock withdrawn and pay out the recipient and relayer
+    fn internal_withdraw(
+        &mut self,
+        lock_contract_id: CryptoHash,
+        mut lock_contract: LockContract,
+        preimage: String,
+    ) {
+        self.assert_not_in_flight(&lock_contract_id);
+        
+        // Verify the contract is not already withdrawn or refunded
+        require!(!lock_contract.withdrawn, "Already withdrawn");
+        require!(!lock_contract.refunded, "Already refunded");
+        require!(lock_contract.parts_count == 0, "Use withdraw_part for partial fill locks");
+        
+        // Verify the secret hash matches
+        require!(
+            verify_preimage(&preimage, &lock_contract.secret_hash, lock_contract.hash_algorithm),
+            "Secret hash does not match"
+        );
+        
+        // Update the lock contract
+        lock_contract.preimage = preimage;
+        lock_contract.withdrawn = true;
+        self.lock_contracts.insert(&lock_contract_id, &lock_contract);
+        self.remove_from_indexes(&lock_contract_id, &lock_contract);
+        self.internal_release_locked(&lock_contract, lock_contract.amount);
+        
+        // With an auction, the recipient gets the current rate's share and the sender the rest
+        let amount = match &lock_contract.auction {
+            Some(auction) => {
+                let rate = auction.rate_at(env::block_timestamp());
+                apply_rate(lock_contract.amount, rate)
+            }
+            None => lock_contract.amount,
+        };
+        let (fee, mut payouts) = self.internal_pay_recipient(&lock_contract, amount);
+        if amount < lock_contract.amount {
+            payouts.push(Payout {
+                receiver_id: lock_contract.sender.clone(),
+                amount: U128(lock_contract.amount - amount),
+            });
+        }
+        self.internal_send_withdrawal(lock_contract_id, &lock_contract, payouts);
+        
+        log!(
+            "Swap withdrawn with ID: {}, preimage: {}, recipient: {}, amount: {}, relayer fee: {}",
+            hex::encode(lock_contract_id),
+            lock_contract.preimage,
+            lock_contract.recipient,
+            amount,
+            fee
+        );
+        HtlcEvent::SwapWithdrawn(&[SwapWithdrawn {
+            lock_contract_id: hex::encode(lock_contract_id),
+            recipient: &lock_contract.recipient,
+            amount: U128(amount),
+            preimage: &lock_contract.preimage,
+            part_index: None,
+            caller: &env::predecessor_account_id(),
+        }])
+        .emit();
+    }
+
+    /// Refund tokens to the sender if the timelock has expired
+    pub fn refund(
+        &mut self,
+        lock_contract_id: CryptoHash,
+    ) -> bool {
+        self.internal_refund_expired(lock_contract_id, None)
+    }
+
+    /// Refund tokens to `recipient` instead of the sender, e.g. when the sender's account is
+    /// compromised (only the sender, once the timelock has expired)
+    pub fn refund_to(&mut self, lock_contract_id: CryptoHash, recipient: AccountId) -> bool {
+        self.internal_refund_expired(lock_contract_id, Some(recipient))
+    }
+
+    // Helper to refund an expired lock to `refund_to`, the sender if not given
+    fn internal_refund_expired(
+        &mut self,
+        lock_contract_id: CryptoHash,
+        refund_to: Option<AccountId>,
+    ) -> bool {
+        // Verify the lock contract exists
+        require!(self.has_lock_contract(lock_contract_id), "Lock contract does not exist");
+        
+        let lock_contract = self.lock_contracts.get(&lock_contract_id).unwrap();
+        
+        // Verify the caller is the sender
+        require!(env::predecessor_account_id() == lock_contract.sender, "Not the sender");
+        
+        // Verify the contract is not already withdrawn or refunded
+        require!(!lock_contract.withdrawn, "Already withdrawn");
+        require!(!lock_contract.refunded, "Already refunded");
+        
+        // Verify the cancellation stage has started
+        require!(
+            lock_contract.stage(env::block_timestamp()) == SwapStage::Cancellation,
+            "Timelock not expired"
+        );
+        
+        self.internal_refund(lock_contract_id, lock_contract, refund_to);
+        
+        true
+    }
+
+    /// Consent to the sender cancelling the swap before the timelock expires (only the recipient)
+    pub fn approve_cancellation(&mut self, lock_contract_id: CryptoHash) {
+        require!(self.has_lock_contract(lock_contract_id), "Lock contract does not exist");
+        
+        let mut lock_contract = self.lock_contracts.get(&lock_contract_id).unwrap();
+        
+        require!(env::predecessor_account_id() == lock_contract.recipient, "Not the recipient");
+        require!(!lock_contract.withdrawn, "Already withdrawn");
+        require!(!lock_contract.refunded, "Already refunded");
+        
+        lock_contract.cancellation_approved = true;
+        self.lock_contracts.insert(&lock_contract_id, &lock_contract);
+        
+        log!(
+            "Swap cancellation approved with ID: {}, recipient: {}",
+            hex::encode(lock_contract_id),
+            lock_contract.recipient
+        );
+    }
+
+    /// Refund the sender right away once the recipient approved the cancellation (only the sender)
+    pub fn cancel_swap(&mut self, lock_contract_id: CryptoHash) -> bool {
+        require!(self.has_lock_contract(lock_contract_id), "Lock contract does not exist");
+        
+        let lock_contract = self.lock_contracts.get(&lock_contract_id).unwrap();
+        
+        require!(env::predecessor_account_id() == lock_contract.sender, "Not the sender");
+        require!(!lock_contract.withdrawn, "Already withdrawn");
+        require!(!lock_contract.refunded, "Already refunded");
+        require!(lock_contract.cancellation_approved, "Cancellation not approved by the recipient");
+        
+        self.internal_refund(lock_contract_id, lock_contract, None);
+        
+        true
+    }
+
+    // Helper to pay `amount` out to the recipient, minus the relayer fee recorded at initiation.
+    // Returns the fee and the payout
+    fn internal_pay_recipient(
+        &self,
+        lock_contract: &LockContract,
+        amount: Balance,
+    ) -> (Balance, Vec<Payout>) {
+        let (fee, remainder) = split_relayer_fee(amount, lock_contract.relayer_fee_bps);
+        let mut payouts =
+            vec![Payout { receiver_id: lock_contract.recipient.clone(), amount: U128(remainder) }];
+        if let (Some(relayer), true) = (&lock_contract.relayer, fee > 0) {
+            payouts.push(Payout { receiver_id: relayer.clone(), amount: U128(fee) });
+        }
+        (fee, payouts)
+    }
+
+    // Helper to send a withdrawal's payouts, guarded like `internal_guard_payout` by a
+    // callback that records the failed ones, or undoes the withdrawal if they all failed
+    fn internal_send_withdrawal(
+        &mut self,
+        lock_contract_id: CryptoHash,
+        lock_contract: &LockContract,
+        payouts: Vec<Payout>,
+    ) {
+        self.in_flight.insert(&lock_contract_id, &true);
+        self.internal_send_payouts(&lock_contract_id, lock_contract, &payouts).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_PAYOUT_CALLBACK)
+                .on_withdraw_transfer(lock_contract_id, payouts),
+        );
+    }
+
+    // Helper to send `payouts` at once, their promise results following their order
+    fn internal_send_payouts(
+        &self,
+        lock_contract_id: &CryptoHash,
+        lock_contract: &LockContract,
+        payouts: &[Payout],
+    ) -> Promise {
+        payouts
+            .iter()
+            .map(|payout| {
+                let receiver_id = payout.receiver_id.clone();
+                self.internal_payout(lock_contract_id, lock_contract, receiver_id, payout.amount.0)
+            })
+            .reduce(Promise::and)
+            .expect("A withdrawal pays out at least once")
+    }
+
+    // Helper to send part of a lock's funds out, in whatever the lock holds. Token transfers
+    // name the lock's escrow in their memo
+    fn internal_payout(
+        &self,
+        lock_contract_id: &CryptoHash,
+        lock_contract: &LockContract,
+        receiver_id: AccountId,
+        amount: Balance,
+    ) -> Promise {
+        match lock_contract.token_type {
+            TokenType::Ft => ext_fungible_token::ext(
+                lock_contract.token.clone().expect("Token locks always record their token"),
+            )
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(
+                receiver_id,
+                amount.into(),
+                Some(format!("Released from escrow {}", escrow_account_id(lock_contract_id))),
+            ),
+            TokenType::Near => {
+                Promise::new(receiver_id).transfer(NearToken::from_yoctonear(amount))
+            }
+        }
+    }
+
+    // Helper to mark a lock contract refunded and send the tokens back to the sender, or to
+    // `refund_to` if given. What's left of a partially filled lock goes to the fee treasury
+    // instead when it is below the minimum swap amount
+    fn internal_refund(
+        &mut self,
+        lock_contract_id: CryptoHash,
+        mut lock_contract: LockContract,
+        refund_to: Option<AccountId>,
+    ) {
+        self.assert_not_in_flight(&lock_contract_id);
+        
+        // Update the lock contract
+        lock_contract.refunded = true;
+        self.lock_contracts.insert(&lock_contract_id, &lock_contract);
+        self.remove_from_indexes(&lock_contract_id, &lock_contract);
+        
+        // Transfer the unclaimed tokens back to the sender
+        let amount = lock_contract.amount - lock_contract.claimed_amount;
+        self.internal_release_locked(&lock_contract, amount);
+        let is_dust = lock_contract.claimed_amount > 0 && amount < self.min_swap_amount;
+        let refund_to = if is_dust { Some(self.fee_treasury.clone()) } else { refund_to };
+        let receiver_id = refund_to.clone().unwrap_or_else(|| lock_contract.sender.clone());
+        let payout = Payout { receiver_id: receiver_id.clone(), amount: U128(amount) };
+        self.internal_guard_payout(lock_contract_id, &lock_contract, payout);
+        
+        log!(
+            "Swap refunded with ID: {}, sender: {}, to: {}, amount: {}",
+            hex::encode(lock_contract_id),
+            lock_contract.sender,
+            receiver_id,
+            amount
+        );
+        HtlcEvent::SwapRefunded(&[SwapRefunded {
+            lock_contract_id: hex::encode(lock_contract_id),
+            sender: &lock_contract.sender,
+            amount: U128(amount),
+            refunded_to: refund_to.as_ref(),
+        }])
+        .emit();
+    }
+
+    // Reentrancy guard. Every method paying out of a lock follows checks-effects-interactions:
+    // it validates the lock, records the withdrawal or refund, and only then schedules the
+    // transfer, so a call landing before the transfer resolves sees the lock as settled.
+    // On top of that, a lock with an outgoing call in flight rejects every state-mutating
+    // call until its callback ran, in case a later change breaks that ordering
+    fn assert_not_in_flight(&self, lock_contract_id: &CryptoHash) {
+        require!(!self.in_flight.get(lock_contract_id).unwrap_or(false), "Reentrant call");
+    }
+    
+    // Helper to send `payout` out of a settled lock, guarding the lock until it resolved
+    fn internal_guard_payout(
+        &mut self,
+        lock_contract_id: CryptoHash,
+        lock_contract: &LockContract,
+        payout: Payout,
+    ) -> Promise {
+        self.in_flight.insert(&lock_contract_id, &true);
+        let receiver_id = payout.receiver_id.clone();
+        self.internal_payout(&lock_contract_id, lock_contract, receiver_id, payout.amount.0).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_PAYOUT_CALLBACK)
+                .on_lock_payout(lock_contract_id, payout),
+        )
+    }
+    
+    /// Callback releasing the reentrancy guard of a lock once its refund or recovery
+    /// resolved. The lock stays settled if the transfer failed, so it is recorded for
+    /// `retry_failed_payouts` instead
+    #[private]
+    pub fn on_lock_payout(&mut self, lock_contract_id: CryptoHash, payout: Payout) -> bool {
+        self.in_flight.remove(&lock_contract_id);
+        if is_promise_success(0) {
+            return true;
+        }
+        
+        let mut recorded = self.failed_payouts.get(&lock_contract_id).unwrap_or_default();
+        recorded.push(payout);
+        self.failed_payouts.insert(&lock_contract_id, &recorded);
+        log!(
+            "Payout transfer failed for swap {}, recorded for retry_failed_payouts",
+            hex::encode(lock_contract_id)
+        );
+        false
+    }
+
+    /// Callback of a withdrawal's transfers, releasing the lock's reentrancy guard. If none
+    /// of them went through, e.g. because the token doesn't hold the locked funds, a full
+    /// withdrawal is undone so the recipient can retry. Otherwise undoing it would pay the
+    /// others twice, so the lock stays withdrawn and the failed payouts are recorded for
+    /// `retry_failed_payouts`. So are those of a part, which can't be reopened on its own
+    #[private]
+    pub fn on_withdraw_transfer(
+        &mut self,
+        lock_contract_id: CryptoHash,
+        payouts: Vec<Payout>,
+    ) -> bool {
+        self.internal_resolve_payouts(lock_contract_id, payouts, true)
+    }
+
+    /// Send the failed payouts of a withdrawn or refunded lock again, e.g. once their receiver
+    /// registered with the token. Anyone can call this, since the payouts only go to their receivers
+    pub fn retry_failed_payouts(&mut self, lock_contract_id: CryptoHash) -> bool {
+        require!(self.has_lock_contract(lock_contract_id), "Lock contract does not exist");
+        self.assert_not_in_flight(&lock_contract_id);
+        
+        let lock_contract = self.lock_contracts.get(&lock_contract_id).unwrap();
+        let payouts = self
+            .failed_payouts
+            .remove(&lock_contract_id)
+            .unwrap_or_else(|| env::panic_str("No failed payouts"));
+        self.in_flight.insert(&lock_contract_id, &true);
+        self.internal_send_payouts(&lock_contract_id, &lock_contract, &payouts).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_PAYOUT_CALLBACK)
+                .on_payout_retry(lock_contract_id, payouts),
+        );
+        true
+    }
+
+    /// Callback of `retry_failed_payouts`, recording the payouts that failed again
+    #[private]
+    pub fn on_payout_retry(&mut self, lock_contract_id: CryptoHash, payouts: Vec<Payout>) -> bool {
+        self.internal_resolve_payouts(lock_contract_id, payouts, false)
+    }
+
+    /// Get the failed payouts of a lock contract awaiting `retry_failed_payouts`
+    pub fn get_failed_payouts(&self, lock_contract_id: CryptoHash) -> Vec<Payout> {
+        self.failed_payouts.get(&lock_contract_id).unwrap_or_default()
+    }
+
+    // Helper releasing a lock's reentrancy guard once `payouts` resolved, in order, and
+    // recording the failed ones. A full withdrawal whose payouts all failed is undone instead
+    // if `revertible`
+    fn internal_resolve_payouts(
+        &mut self,
+        lock_contract_id: CryptoHash,
+        payouts: Vec<Payout>,
+        revertible: bool,
+    ) -> bool {
+        self.in_flight.remove(&lock_contract_id);
+        let payouts_count = payouts.len();
+        let failed: Vec<Payout> = payouts
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| !is_promise_success(*index as u64))
+            .map(|(_, payout)| payout)
+            .collect();
+        if failed.is_empty() {
+            return true;
+        }
+        
+        let mut lock_contract = self.lock_contracts.get(&lock_contract_id).unwrap();
+        let reverted =
+            revertible && lock_contract.parts_count == 0 && failed.len() == payouts_count;
+        if reverted {
+            lock_contract.withdrawn = false;
+            lock_contract.preimage = String::new();
+            self.lock_contracts.insert(&lock_contract_id, &lock_contract);
+            self.add_to_indexes(&lock_contract_id, &lock_contract);
+            self.internal_add_locked(&lock_contract);
+        } else {
+            let mut recorded = self.failed_payouts.get(&lock_contract_id).unwrap_or_default();
+            recorded.extend(failed.iter().cloned());
+            self.failed_payouts.insert(&lock_contract_id, &recorded);
+        }
+        
+        log!(
+            "Withdrawal transfer failed for swap {}, {} of {} transfers failed, reverted: {}",
+            hex::encode(lock_contract_id),
+            failed.len(),
+            payouts_count,
+            reverted
+        );
+        HtlcEvent::WithdrawFailed(&[WithdrawFailed {
+            lock_contract_id: hex::encode(lock_contract_id),
+            recipient: &lock_contract.recipient,
+            reverted,
+            failed_payouts: if reverted { &[] } else { &failed },
+        }])
+        .emit();
+        false
+    }
+
+    /// Last resort for funds stranded by a bug: send what's left of a lock that was neither
+    /// withdrawn nor refunded to the recovery address (only the owner). Only allowed
+    /// `EMERGENCY_RECOVERY_GRACE_DAYS` after the lock expired, leaving the sender ample
+    /// time to refund it first
+    pub fn emergency_recover(&mut self, lock_contract_id: CryptoHash) -> bool {
+        self.assert_owner();
+        require!(self.has_lock_contract(lock_contract_id), "Lock contract does not exist");
+        self.assert_not_in_flight(&lock_contract_id);
+        
+        let mut lock_contract = self.lock_contracts.get(&lock_contract_id).unwrap();
+        require!(!lock_contract.withdrawn, "Already withdrawn");
+        require!(!lock_contract.refunded, "Already refunded");
+        let grace_period = EMERGENCY_RECOVERY_GRACE_DAYS * 24 * 3600 * 1_000_000_000;
+        require!(
+            env::block_timestamp() >= lock_contract.endtime + grace_period,
+            "Emergency recovery grace period not over"
+        );
+        
+        // Settle the lock as refunded, to the recovery address instead of the sender
+        lock_contract.refunded = true;
+        self.lock_contracts.insert(&lock_contract_id, &lock_contract);
+        self.remove_from_indexes(&lock_contract_id, &lock_contract);
+        let amount = lock_contract.amount - lock_contract.claimed_amount;
+        self.internal_release_locked(&lock_contract, amount);
+        let payout = Payout { receiver_id: self.recovery_address.clone(), amount: U128(amount) };
+        self.internal_guard_payout(lock_contract_id, &lock_contract, payout);
+        
+        log!(
+            "EMERGENCY RECOVERY of lock contract {}: {} sent to {}",
+            hex::encode(lock_contract_id),
+            amount,
+            self.recovery_address
+        );
+        HtlcEvent::EmergencyRecovered(&[EmergencyRecovered {
+            lock_contract_id: hex::encode(lock_contract_id),
+            sender: &lock_contract.sender,
+            recovery_address: &self.recovery_address,
+            amount: U128(amount),
+        }])
+        .emit();
+        
+        true
+    }
+
+    /// Give the recipient more time before the lock can be refunded (only the sender).
+    /// Extensions add up to at most `MAX_TIMELOCK_EXTENSION_HOURS`
+    pub fn extend_timelock(&mut self, lock_contract_id: CryptoHash, additional_hours: u64) {
+        require!(self.has_lock_contract(lock_contract_id), "Lock contract does not exist");
+        
+        let mut lock_contract = self.lock_contracts.get(&lock_contract_id).unwrap();
+        
+        require!(env::predecessor_account_id() == lock_contract.sender, "Not the sender");
+        require!(!lock_contract.withdrawn, "Already withdrawn");
+        require!(!lock_contract.refunded, "Already refunded");
+        require!(env::block_timestamp() < lock_contract.endtime, "Timelock already expired");
+        require!(additional_hours > 0, "Extension must be greater than 0");
+        
+        let extension = additional_hours * 3600 * 1_000_000_000;
+        let total_extension = lock_contract.extension + extension;
+        require!(
+            total_extension <= MAX_TIMELOCK_EXTENSION_HOURS * 3600 * 1_000_000_000,
+            "Total extension exceeds the maximum"
+        );
+        
+        lock_contract.endtime += extension;
+        lock_contract.extension = total_extension;
+        self.lock_contracts.insert(&lock_contract_id, &lock_contract);
+        
+        log!(
+            "Timelock extended with ID: {}, by: {} hours, new endtime: {}",
+            hex::encode(lock_contract_id),
+            additional_hours,
+            lock_contract.endtime
+        );
+        HtlcEvent::TimelockExtended(&[TimelockExtended {
+            lock_contract_id: hex::encode(lock_contract_id),
+            additional_hours,
+            endtime: lock_contract.endtime,
+        }])
+        .emit();
+    }
+
+    /// Get the NEAR deposit a relayer has to attach to `complete_swap`
+    pub fn get_safety_deposit_amount(&self) -> U128 {
+        U128(self.safety_deposit_amount)
+    }
+
+    /// Set the NEAR deposit a relayer has to attach to `complete_swap`
+    pub fn set_safety_deposit_amount(&mut self, amount: U128) {
+        self.assert_owner();
+        self.safety_deposit_amount = amount.into();
+        log!("Safety deposit amount set to {}", amount.0);
+    }
+
+    /// Get the account adjudicating relayer completions
+    pub fn get_arbiter(&self) -> Option<AccountId> {
+        self.arbiter.clone()
+    }
+
+    /// Set the account adjudicating relayer completions, `None` removes it.
+    /// The arbiter can't be the owner, so the two roles stay separate
+    pub fn set_arbiter(&mut self, arbiter: Option<AccountId>) {
+        self.assert_owner();
+        require!(arbiter.as_ref() != Some(&self.owner_id), "The arbiter can't be the owner");
+        self.arbiter = arbiter;
+        log!("Arbiter set to {:?}", self.arbiter);
+    }
+
+    /// Get the arbiter's ruling on a completed swap
+    pub fn get_adjudication(&self, completed_id: CryptoHash) -> Option<Adjudication> {
+        self.adjudications.get(&completed_id)
+    }
+
+    /// Rule a relayer's completion invalid, forfeiting its safety deposit to the recovery
+    /// address - only callable by the arbiter
+    pub fn slash_relayer(&mut self, completed_id: CryptoHash, reason: String) -> Promise {
+        self.assert_arbiter();
+        let deposit = self.safety_deposits.remove(&completed_id).expect("No safety deposit for this swap");
+        
+        log!(
+            "Relayer slashed for ID: {}, relayer: {}, amount: {}, reason: {}",
+            hex::encode(completed_id),
+            deposit.relayer,
+            deposit.amount,
+            reason
+        );
+        HtlcEvent::RelayerSlashed(&[RelayerSlashed {
+            completed_id: hex::encode(completed_id),
+            relayer: &deposit.relayer,
+            amount: U128(deposit.amount),
+            reason: &reason,
+        }])
+        .emit();
+        self.adjudications.insert(&completed_id, &Adjudication::Slashed { reason });
+        
+        Promise::new(self.recovery_address.clone()).transfer(NearToken::from_yoctonear(deposit.amount))
+    }
+
+    /// Rule a relayer's completion valid, returning its safety deposit - only callable by
+    /// the arbiter
+    pub fn vindicate_relayer(&mut self, completed_id: CryptoHash) -> Promise {
+        self.assert_arbiter();
+        let deposit = self.safety_deposits.remove(&completed_id).expect("No safety deposit for this swap");
+        self.adjudications.insert(&completed_id, &Adjudication::Vindicated);
+        
+        log!(
+            "Relayer vindicated for ID: {}, relayer: {}, amount: {}",
+            hex::encode(completed_id),
+            deposit.relayer,
+            deposit.amount
+        );
+        HtlcEvent::RelayerVindicated(&[RelayerVindicated {
+            completed_id: hex::encode(completed_id),
+            relayer: &deposit.relayer,
+            amount: U128(deposit.amount),
+        }])
+        .emit();
+        
+        Promise::new(deposit.relayer).transfer(NearToken::from_yoctonear(deposit.amount))
+    }
+
+    /// Complete a cross-chain swap from another chain (to be called by relayer/oracle).
+    /// `source_chain` is a canonical `ChainId` string like `mainnet:1`, and the caller must
+    /// be a relayer for that chain.
+    /// The relayer has to attach at least `safety_deposit_amount` NEAR, held until the swap is settled.
+    /// Resolves to whether the mint went through
+    #[payable]
+    pub fn complete_swap(
+        &mut self,
+        source_chain: String,
+        source_address: String,
+        destination: AccountId,
+        amount: U128,
+        preimage: String,
+    ) -> Promise {
+        self.internal_complete_swap(
+            source_chain,
+            source_address,
+            destination,
+            amount,
+            preimage,
+            Vec::new(),
+        )
+    }
+
+    /// Complete a cross-chain swap filled by several cooperating relayers, paying them the
+    /// relayer fee out of `amount`. `relayer_shares` lists each relayer with its share in
+    /// basis points; the shares must add up to the current relayer fee and the caller must
+    /// be one of them. The destination gets the rest, and once its mint went through each
+    /// relayer's share is minted to it
+    #[payable]
+    pub fn complete_swap_split(
+        &mut self,
+        source_chain: String,
+        source_address: String,
+        destination: AccountId,
+        amount: U128,
+        preimage: String,
+        relayer_shares: Vec<(AccountId, u16)>,
+    ) -> Promise {
+        require!(!relayer_shares.is_empty(), "No relayer shares given");
+        let total_bps = relayer_shares
+            .iter()
+            .try_fold(0u16, |total, (_, share_bps)| total.checked_add(*share_bps));
+        require!(
+            total_bps == Some(self.relayer_fee_bps),
+            "Relayer shares must sum to the relayer fee"
+        );
+        let caller = env::predecessor_account_id();
+        require!(
+            relayer_shares.iter().any(|(relayer, _)| relayer == &caller),
+            "Caller is not among the listed relayers"
+        );
+        self.internal_complete_swap(
+            source_chain,
+            source_address,
+            destination,
+            amount,
+            preimage,
+            relayer_shares,
+        )
+    }
+
+    // Helper to mint a completed swap to its destination, minus the fees owed to `relayer_shares`
+    fn internal_complete_swap(
+        &mut self,
+        source_chain: String,
+        source_address: String,
+        destination: AccountId,
+        amount: U128,
+        preimage: String,
+        relayer_shares: Vec<(AccountId, u16)>,
+    ) -> Promise {
+        // Verify the caller is a relayer for the source chain
+        let source_chain_id = ChainId::from_str(&source_chain).unwrap_or_else(|e| env::panic_str(&e));
+        require!(
+            self.is_relayer_for(env::predecessor_account_id(), source_chain_id.clone()),
+            "Not an authorized relayer"
+        );
+        for (relayer, _) in &relayer_shares {
+            require!(
+                self.is_relayer_for(relayer.clone(), source_chain_id.clone()),
+                "Not an authorized relayer"
+            );
+        }
+        let source_chain = source_chain_id.to_string();
+        
+        // Generate a unique ID for this cross-chain completion
+        let lock_id = env::sha256(
+            &[
+                source_chain.as_bytes(),
+                source_address.as_bytes(),
+                destination.as_bytes(),
+                &amount.0.to_le_bytes(),
+                preimage.as_bytes(),
+            ].concat()
+        );
+        let lock_id: CryptoHash = lock_id.try_into().expect("Invalid hash length");
+        
+        // Make sure the same completion can't be minted twice
+        require!(self.completed_swaps.get(&lock_id).is_none(), "Swap already completed");
+        self.completed_swaps.insert(
+            &lock_id,
+            &CompletedSwap {
+                source_chain: source_chain.clone(),
+                source_address: source_address.clone(),
+                destination: destination.clone(),
+                amount: amount.0,
+                preimage: preimage.clone(),
+                completed_at: env::block_timestamp(),
+                relayer: env::predecessor_account_id(),
+            },
+        );
+        
+        // Hold the relayer's safety deposit until the swap is settled
+        let deposit = env::attached_deposit().as_yoctonear();
+        require!(deposit >= self.safety_deposit_amount, "Insufficient safety deposit");
+        if deposit > 0 {
+            self.safety_deposits.insert(
+                &lock_id,
+                &SafetyDeposit { relayer: env::predecessor_account_id(), amount: deposit },
+            );
+        }
+        
+        // Each relayer's cut of the amount, paid once the destination got the rest
+        let relayer_fees: Vec<(AccountId, U128)> = relayer_shares
+            .into_iter()
+            .map(|(relayer, share_bps)| (relayer, U128(split_relayer_fee(amount.0, share_bps).0)))
+            .collect();
+        let fees: Balance = relayer_fees.iter().map(|(_, fee)| fee.0).sum();
+        let callback_gas = Gas::from_gas(
+            GAS_FOR_MINT_CALLBACK.as_gas()
+                + GAS_FOR_RELAYER_FEE_MINT.as_gas() * relayer_fees.len() as u64,
+        );
+        
+        // Mint the tokens to the destination, the HTLC has to be a minter of the token
+        let mint = ext_fungible_token::ext(self.token.clone())
+            .with_attached_deposit(ONE_YOCTO)
+            .with_static_gas(remaining_gas(callback_gas, GAS_FOR_FT_MINT))
+            .ft_mint(
+                destination.clone(),
+                U128(amount.0 - fees),
+                Some(format!("Cross-chain swap {}", hex::encode(lock_id))),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(callback_gas)
+                    .on_complete_swap_mint(lock_id, destination.clone(), amount, relayer_fees),
+            );
+        
+        log!(
+            "Cross-chain swap completed with ID: {}, from {}, source_address: {}, to: {}, amount: {}, preimage: {}",
+            hex::encode(lock_id),
+            source_chain,
+            source_address,
+            destination,
+            amount.0,
+            preimage
+        );
+        HtlcEvent::SwapCompleted(&[SwapCompleted {
+            completed_id: hex::encode(lock_id),
+            source_chain: &source_chain,
+            source_address: &source_address,
+            destination: &destination,
+            amount,
+            preimage: &preimage,
+            relayer: &env::predecessor_account_id(),
+        }])
+        .emit();
+        
+        mint
+    }
+
+    /// Callback of `complete_swap`'s mint. Once the destination got its tokens, the
+    /// `relayer_fees` are minted to their relayers. If the mint failed, the completion is
+    /// forgotten and the safety deposit returned, so the relayer can retry
+    #[private]
+    pub fn on_complete_swap_mint(
+        &mut self,
+        completed_id: CryptoHash,
+        destination: AccountId,
+        amount: U128,
+        relayer_fees: Vec<(AccountId, U128)>,
+    ) -> bool {
+        if is_promise_success(0) {
+            let memo = format!("Relayer fee for cross-chain swap {}", hex::encode(completed_id));
+            for (relayer, fee) in relayer_fees {
+                if fee.0 > 0 {
+                    ext_fungible_token::ext(self.token.clone())
+                        .with_attached_deposit(ONE_YOCTO)
+                        .with_static_gas(GAS_FOR_RELAYER_FEE_MINT)
+                        .ft_mint(relayer, fee, Some(memo.clone()));
+                }
+            }
+            return true;
+        }
+        
+        self.completed_swaps.remove(&completed_id);
+        if let Some(deposit) = self.safety_deposits.remove(&completed_id) {
+            Promise::new(deposit.relayer).transfer(NearToken::from_yoctonear(deposit.amount));
+        }
+        
+        log!(
+            "Cross-chain swap completion failed with ID: {}, to: {}, amount: {}",
+            hex::encode(completed_id),
+            destination,
+            amount.0
+        );
+        HtlcEvent::SwapCompletionFailed(&[SwapCompletionFailed {
+            completed_id: hex::encode(completed_id),
+            destination: &destination,
+            amount,
+        }])
+        .emit();
+        
+        false
+    }
+    
+    /// Get the MPC signer contract used by `execute_on_evm`
+    pub fn get_mpc_signer(&self) -> AccountId {
+        self.mpc_signer.clone()
+    }
+
+    /// Set the MPC signer contract used by `execute_on_evm`
+    pub fn set_mpc_signer(&mut self, mpc_signer: AccountId) {
+        self.assert_owner();
+        self.mpc_signer = mpc_signer;
+        log!("MPC signer set to {}", self.mpc_signer);
+    }
+
+    /// Get the key derivation path `account_id` signs EVM transactions with
+    pub fn get_derivation_path(&self, account_id: AccountId) -> String {
+        self.derivation_paths
+            .get(&account_id)
+            .unwrap_or_else(|| DEFAULT_DERIVATION_PATH.to_string())
+    }
+
+    /// Set the key derivation path a relayer signs EVM transactions with
+    pub fn set_derivation_path(&mut self, account_id: AccountId, path: String) {
+        self.assert_owner();
+        require!(!path.is_empty(), "Derivation path cannot be empty");
+        self.derivation_paths.insert(&account_id, &path);
+        log!("Derivation path of {} set to {}", account_id, path);
+    }
+
+    /// 1inch Fusion: Execute an EVM transaction from NEAR using chain signatures.
+    /// Builds an EIP-1559 transaction calling `contract_address` with `calldata`, has the
+    /// MPC signer sign its hash with the caller's derivation path, and emits the signed
+    /// raw transaction in an `evm_transaction_signed` event for a relayer to broadcast.
+    /// The attached deposit pays the MPC signer
+    #[payable]
+    pub fn execute_on_evm(
+        &mut self,
+        evm_chain_id: String,
+        contract_address: String,
+        calldata: String,
+        gas_limit: U128,
+        nonce: u64,
+        max_fee_per_gas: U128,
+        max_priority_fee_per_gas: U128,
+    ) -> Promise {
+        // Only relayers or owner can call this function
+        let caller = env::predecessor_account_id();
+        require!(
+            self.is_relayer(&caller) || caller == self.owner_id,
+            "Only relayers or owner can execute cross-chain operations"
+        );
+        
+        // Parse the EVM chain ID (e.g. `mainnet:1`) to ensure it's valid
+        let evm_chain_id = match ChainId::from_str(&evm_chain_id) {
+            Ok(id) => id,
+            Err(_) => env::panic_str("Invalid EVM chain ID format")
+        };
+        require!(
+            caller == self.owner_id || self.is_relayer_for(caller.clone(), evm_chain_id.clone()),
+            "Not an authorized relayer for this chain"
+        );
+        let chain_id = evm_chain_id.chain_id;
+        
+        // Validate the contract address format (should be a hex address for EVM)
+        if !contract_address.starts_with("0x") || contract_address.len() != 42 {
+            env::panic_str("Invalid EVM contract address format");
+        }
+        let to = decode_hex(&contract_address, "Invalid EVM contract address format");
+        
+        // 1inch Fusion requires calldata to be properly formatted for their resolver contracts
+        if calldata.is_empty() {
+            env::panic_str("Calldata cannot be empty");
+        }
+        let data = decode_hex(&calldata, "Calldata must be hex encoded");
+        
+        require!(!env::attached_deposit().is_zero(), "Attach a deposit for the MPC signer");
+        
+        let unsigned_fields = [
+            rlp_encode_uint(chain_id.into()),
+            rlp_encode_uint(nonce.into()),
+            rlp_encode_uint(max_priority_fee_per_gas.0),
+            rlp_encode_uint(max_fee_per_gas.0),
+            rlp_encode_uint(gas_limit.0),
+            rlp_encode_bytes(&to),
+            rlp_encode_uint(0),  // value
+            rlp_encode_bytes(&data),
+            rlp_encode_list(&[]),  // access list
+        ]
+        .concat();
+        let payload = eip1559_payload(&unsigned_fields);
+        let payload: CryptoHash = env::keccak256(&payload).try_into().expect("Invalid hash length");
+        
+        log!(
+            "1inch Fusion: Signing transaction for EVM chain {}, contract: {}, gas: {}, nonce: {}",
+            chain_id,
+            contract_address,
+            gas_limit.0,
+            nonce
+        );
+        
+        // Fail early rather than in the callback if the signer can't get its gas
+        remaining_gas(GAS_FOR_EVM_SIGNATURE_CALLBACK, GAS_FOR_MPC_SIGN);
+        ext_mpc_signer::ext(self.mpc_signer.clone())
+            .with_attached_deposit(env::attached_deposit())
+            .with_static_gas(GAS_FOR_MPC_SIGN)
+            .sign(SignRequest {
+                payload,
+                path: self.get_derivation_path(caller.clone()),
+                key_version: 0,
+            })
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_EVM_SIGNATURE_CALLBACK)
+                    .on_evm_signature(chain_id, hex::encode(unsigned_fields), caller),
+            )
+    }
+
+    /// Callback assembling the signed raw transaction once the MPC signer responded
+    #[private]
+    pub fn on_evm_signature(
+        &mut self,
+        chain_id: u64,
+        unsigned_fields: String,
+        caller: AccountId,
+    ) -> String {
+        let signature: SignatureResponse = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice(&value)
+                .unwrap_or_else(|_| env::panic_str("Invalid MPC signature response")),
+            _ => env::panic_str("MPC signing failed"),
+        };
+        
+        // `big_r` is a compressed point, its x coordinate is the signature's `r`
+        let big_r = decode_hex(&signature.big_r.affine_point, "Invalid MPC signature response");
+        let s = decode_hex(&signature.s.scalar, "Invalid MPC signature response");
+        require!(big_r.len() == 33 && s.len() == 32, "Invalid MPC signature response");
+        
+        let unsigned_fields = hex::decode(unsigned_fields).expect("Invalid transaction fields");
+        let signed_fields = [
+            unsigned_fields,
+            rlp_encode_uint(signature.recovery_id.into()),
+            rlp_encode_uint_bytes(&big_r[1..]),
+            rlp_encode_uint_bytes(&s),
+        ]
+        .concat();
+        let signed_transaction = eip1559_payload(&signed_fields);
+        let transaction_hash = env::keccak256(&signed_transaction);
+        let signed_transaction = format!("0x{}", hex::encode(signed_transaction));
+        
+        HtlcEvent::EvmTransactionSigned(&[EvmTransactionSigned {
+            chain_id,
+            signed_transaction: &signed_transaction,
+            transaction_hash: format!("0x{}", hex::encode(transaction_hash)),
+            caller: &caller,
+        }])
+        .emit();
+        
+        signed_transaction
+    }
+
+    /// Get the status of a lock contract, with the seconds left until expiry while pending
+    pub fn swap_status(&self, lock_contract_id: CryptoHash) -> Option<SwapStatus> {
+        self.lock_contracts.get(&lock_contract_id).map(|lock_contract| {
+            let now = env::block_timestamp();
+            if lock_contract.withdrawn {
+                SwapStatus::Withdrawn
+            } else if lock_contract.refunded {
+                SwapStatus::Refunded
+            } else if now >= lock_contract.endtime {
+                SwapStatus::Expired
+            } else {
+                SwapStatus::Pending { remaining_seconds: (lock_contract.endtime - now) / 1_000_000_000 }
+            }
+        })
+    }
+
+    /// Get the current stage of a lock contract
+    pub fn swap_stage(&self, lock_contract_id: CryptoHash) -> SwapStage {
+        self.lock_contracts
+            .get(&lock_contract_id)
+            .expect("Lock contract does not exist")
+            .stage(env::block_timestamp())
+    }
+
+    /// Check if a lock contract exists
+    pub fn has_lock_contract(&self, lock_contract_id: CryptoHash) -> bool {
+        self.lock_contracts.get(&lock_contract_id).is_some()
+    }
+
+    /// Get the escrow of the lock keyed by `order_hash`, `<hash prefix>.escrow.<this contract>`.
+    /// It is a namespace rather than a deployed account: token payouts of the lock name it
+    /// in their memo, so each swap's funds can be followed and audited on their own
+    pub fn escrow_account_for(&self, order_hash: CryptoHash) -> AccountId {
+        escrow_account_id(&order_hash)
+    }
+
+    /// Get details of a lock contract
+    pub fn get_lock_contract(&self, lock_contract_id: CryptoHash) -> Option<LockContractView> {
+        self.lock_contracts
+            .get(&lock_contract_id)
+            .map(|lock_contract| lock_contract_view(&lock_contract_id, lock_contract))
+    }
+
+    /// Get the share of a lock the recipient would get by withdrawing now, scaled by
+    /// `RATE_PRECISION`. Locks without an auction always pay out in full
+    pub fn current_rate(&self, lock_contract_id: CryptoHash) -> U128 {
+        let lock_contract = self
+            .lock_contracts
+            .get(&lock_contract_id)
+            .unwrap_or_else(|| env::panic_str("Lock contract does not exist"));
+        let rate = lock_contract
+            .auction
+            .map_or(RATE_PRECISION, |auction| auction.rate_at(env::block_timestamp()));
+        U128(rate)
+    }
+
+    /// Compute the order hash a lock with `params` is keyed by, so resolvers can derive the
+    /// same ID before submitting on either chain
+    pub fn compute_order_hash(&self, params: OrderHashParams) -> CryptoHash {
+        order_hash(&params)
+    }
+
+    /// Get the secret revealed by withdrawing a lock contract, `None` until it's withdrawn
+    pub fn get_preimage(&self, lock_contract_id: CryptoHash) -> Option<String> {
+        self.lock_contracts
+            .get(&lock_contract_id)
+            .filter(|lock_contract| lock_contract.withdrawn)
+            .map(|lock_contract| lock_contract.preimage)
+    }
+
+    /// Get the cross-chain swaps minted so far, in no particular order
+    pub fn get_completed_swaps(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<CompletedSwapView> {
+        let keys = self.completed_swaps.keys_as_vector();
+        let values = self.completed_swaps.values_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let end = keys.len().min(from_index.saturating_add(limit.unwrap_or(50)));
+        (from_index..end)
+            .map(|index| completed_swap_view(&keys.get(index).unwrap(), values.get(index).unwrap()))
+            .collect()
+    }
+
+    /// Get the number of cross-chain swaps minted so far
+    pub fn completed_swap_count(&self) -> u64 {
+        self.completed_swaps.len()
+    }
+
+    /// Get the pending lock contracts created by `account_id`
+    pub fn get_lock_contracts_by_sender(
+        &self,
+        account_id: AccountId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<LockContractView> {
+        self.lock_contracts_in_index(self.by_sender.get(&account_id), from_index, limit)
+    }
+
+    /// Get the pending lock contracts `account_id` can withdraw from
+    pub fn get_lock_contracts_by_recipient(
+        &self,
+        account_id: AccountId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<LockContractView> {
+        self.lock_contracts_in_index(self.by_recipient.get(&account_id), from_index, limit)
+    }
+
+    fn lock_contracts_in_index(
+        &self,
+        ids: Option<Vector<CryptoHash>>,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<LockContractView> {
+        let ids = match ids {
+            Some(ids) => ids,
+            None => return vec![],
+        };
+        ids.iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(50) as usize)
+            .filter_map(|id| {
+                self.lock_contracts
+                    .get(&id)
+                    .map(|lock_contract| lock_contract_view(&id, lock_contract))
+            })
+            .collect()
+    }
+
+    /// Delete a withdrawn or refunded lock contract (only its sender or the owner). Nobody
+    /// paid the contract for the lock's storage, so the freed storage stake stays with it.
+    /// Returns the freed storage stake
+    pub fn purge_settled(&mut self, lock_contract_id: CryptoHash) -> U128 {
+        self.purge_settled_batch(vec![lock_contract_id])
+    }
+
+    /// Delete up to `MAX_PURGE_BATCH` withdrawn or refunded lock contracts at once
+    pub fn purge_settled_batch(&mut self, lock_contract_ids: Vec<CryptoHash>) -> U128 {
+        require!(!lock_contract_ids.is_empty(), "Nothing to purge");
+        require!(lock_contract_ids.len() <= MAX_PURGE_BATCH, "Too many lock contracts in one batch");
+        
+        let caller = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        for lock_contract_id in lock_contract_ids {
+            let lock_contract = self
+                .lock_contracts
+                .get(&lock_contract_id)
+                .unwrap_or_else(|| env::panic_str("Lock contract does not exist"));
+            require!(
+                caller == lock_contract.sender || caller == self.owner_id,
+                "Only the sender or owner can purge a lock contract"
+            );
+            require!(lock_contract.withdrawn || lock_contract.refunded, "Lock contract is not settled");
+            self.assert_not_in_flight(&lock_contract_id);
+            require!(
+                !self.failed_payouts.contains_key(&lock_contract_id),
+                "Lock contract has failed payouts to retry"
+            );
+            
+            // Keep the revealed secret discoverable after the entry is gone
+            HtlcEvent::SwapPurged(&[SwapPurged {
+                lock_contract_id: hex::encode(lock_contract_id),
+                preimage: &lock_contract.preimage,
+            }])
+            .emit();
+            self.lock_contracts.remove(&lock_contract_id);
+        }
+        
+        let freed_bytes = initial_storage_usage.saturating_sub(env::storage_usage());
+        U128(Balance::from(freed_bytes) * env::storage_byte_cost().as_yoctonear())
+    }
+
+    /// Get the amount of `token` held by unsettled locks, the default token if not given
+    pub fn total_locked(&self, token: Option<AccountId>) -> U128 {
+        let token = token.unwrap_or_else(|| self.token.clone());
+        U128(self.total_locked.get(&token).unwrap_or(0))
+    }
+
+    /// Get the native NEAR held by unsettled locks
+    pub fn total_locked_near(&self) -> U128 {
+        U128(self.total_locked_near)
+    }
+
+    /// Get the number of locks neither withdrawn nor refunded
+    pub fn active_swap_count(&self) -> u64 {
+        self.active_swap_count
+    }
+
+    // Helper to count a new lock in the running totals
+    fn internal_add_locked(&mut self, lock_contract: &LockContract) {
+        self.active_swap_count += 1;
+        match &lock_contract.token {
+            Some(token) => {
+                let total = self.total_locked.get(token).unwrap_or(0) + lock_contract.amount;
+                self.total_locked.insert(token, &total);
+            }
+            None => self.total_locked_near += lock_contract.amount,
+        }
+    }
+
+    // Helper to take `amount` paid out of a lock off the running totals, and the lock
+    // itself off the active count once it's settled
+    fn internal_release_locked(&mut self, lock_contract: &LockContract, amount: Balance) {
+        if lock_contract.withdrawn || lock_contract.refunded {
+            self.active_swap_count -= 1;
+        }
+        match &lock_contract.token {
+            Some(token) => {
+                let total = self.total_locked.get(token).unwrap_or(0) - amount;
+                self.total_locked.insert(token, &total);
+            }
+            None => self.total_locked_near -= amount,
+        }
+    }
+
+    // Helper to index a new lock contract under its sender and recipient
+    fn add_to_indexes(&mut self, lock_contract_id: &CryptoHash, lock_contract: &LockContract) {
+        index_insert(&mut self.by_sender, b'S', &lock_contract.sender, lock_contract_id);
+        index_insert(&mut self.by_recipient, b'P', &lock_contract.recipient, lock_contract_id);
+    }
+
+    // Helper to drop a settled lock contract from the sender and recipient indexes
+    fn remove_from_indexes(&mut self, lock_contract_id: &CryptoHash, lock_contract: &LockContract) {
+        index_remove(&mut self.by_sender, &lock_contract.sender, lock_contract_id);
+        index_remove(&mut self.by_recipient, &lock_contract.recipient, lock_contract_id);
+    }
+
+    // Helper to assert the caller is the owner
+    fn assert_owner(&self) {
+        require!(env::predecessor_account_id() == self.owner_id, "Not the owner");
+    }
+
+    fn assert_arbiter(&self) {
+        require!(
+            self.arbiter.as_ref() == Some(&env::predecessor_account_id()),
+            "Not the arbiter"
+        );
+    }
+}
+
+/// Escrow namespace of a lock, derived from the order hash the lock is keyed by
+fn escrow_account_id(lock_contract_id: &CryptoHash) -> AccountId {
+    let prefix = &hex::encode(lock_contract_id)[..ESCROW_PREFIX_LENGTH];
+    format!("{}.{}.{}", prefix, ESCROW_NAMESPACE, env::current_account_id())
+        .parse()
+        .unwrap_or_else(|_| env::panic_str("Escrow account id is too long"))
+}
+
+fn lock_contract_view(lock_contract_id: &CryptoHash, lock_contract: LockContract) -> LockContractView {
+    let stage = lock_contract.stage(env::block_timestamp());
+    LockContractView {
+        lock_contract_id: hex::encode(lock_contract_id),
+        secret_hash: hex::encode(lock_contract.secret_hash),
+        recipient: lock_contract.recipient,
+        sender: lock_contract.sender,
+        amount: U128(lock_contract.amount),
+        endtime: lock_contract.endtime,
+        withdrawn: lock_contract.withdrawn,
+        refunded: lock_contract.refunded,
+        preimage: lock_contract.preimage,
+        target_chain: lock_contract.target_chain,
+        target_address: lock_contract.target_address,
+        hash_algorithm: lock_contract.hash_algorithm,
+        cancellation_approved: lock_contract.cancellation_approved,
+        relayer: lock_contract.relayer,
+        relayer_fee_bps: lock_contract.relayer_fee_bps,
+        token_type: lock_contract.token_type,
+        token: lock_contract.token,
+        created_at: lock_contract.created_at,
+        timelocks: lock_contract.timelocks,
+        parts_count: lock_contract.parts_count,
+        claimed_parts: lock_contract.claimed_parts,
+        claimed_amount: U128(lock_contract.claimed_amount),
+        auction: lock_contract.auction,
+        extension: lock_contract.extension,
+        stage,
+        escrow_account: escrow_account_id(lock_contract_id),
+    }
+}
+
+fn completed_swap_view(completed_id: &CryptoHash, completed_swap: CompletedSwap) -> CompletedSwapView {
+    CompletedSwapView {
+        completed_id: hex::encode(completed_id),
+        source_chain: completed_swap.source_chain,
+        source_address: completed_swap.source_address,
+        destination: completed_swap.destination,
+        amount: U128(completed_swap.amount),
+        preimage: completed_swap.preimage,
+        completed_at: completed_swap.completed_at,
+        relayer: completed_swap.relayer,
+    }
+}
+
+/// Only the recipient can withdraw in the exclusive window, anyone in the public one
+fn assert_withdrawal_allowed(lock_contract: &LockContract) {
+    match lock_contract.stage(env::block_timestamp()) {
+        SwapStage::ExclusiveWithdrawal => require!(
+            env::predecessor_account_id() == lock_contract.recipient,
+            "Not the recipient"
+        ),
+        SwapStage::PublicWithdrawal => {}
+        _ => env::panic_str("Withdrawal not allowed in the current stage"),
+    }
+}
+
+/// Returns true if `preimage` hashes to `expected` with `algo`
+pub fn verify_preimage(preimage: &str, expected: &CryptoHash, algo: HashAlgorithm) -> bool {
+    let hash = match algo {
+        HashAlgorithm::Sha256 => env::sha256(preimage.as_bytes()),
+        HashAlgorithm::Keccak256 => env::keccak256(preimage.as_bytes()),
+    };
+    hash.as_slice() == expected.as_slice()
+}
+
+/// Leaf of the secrets Merkle tree: hash(part_index || hash(preimage))
+pub fn merkle_leaf(algorithm: HashAlgorithm, part_index: u32, preimage: &[u8]) -> CryptoHash {
+    algorithm.hash(&[&part_index.to_le_bytes()[..], &algorithm.hash(preimage)[..]].concat())
+}
+
+/// Checks `proof` links `leaf` to `root`, hashing each pair in sorted order
+pub fn verify_merkle_proof(
+    algorithm: HashAlgorithm,
+    leaf: CryptoHash,
+    proof: &[CryptoHash],
+    root: CryptoHash,
+) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| hash_pair(algorithm, node, *sibling));
+    computed == root
+}
+
+fn hash_pair(algorithm: HashAlgorithm, a: CryptoHash, b: CryptoHash) -> CryptoHash {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    algorithm.hash(&[left, right].concat())
+}
+
+// Order hash over fixed-width fields, hashed with the order's algorithm. Solidity computes the
+// same as `hash(abi.encode(secretHash, hash(maker), hash(taker), amount, hash(targetChain), salt))`
+fn order_hash(params: &OrderHashParams) -> CryptoHash {
+    let algorithm = params.hash_algorithm.unwrap_or_default();
+    let uint256 = |value: u128| [[0u8; 16], value.to_be_bytes()].concat();
+    algorithm.hash(
+        &[
+            &params.secret_hash[..],
+            &algorithm.hash(params.maker.as_bytes()),
+            &algorithm.hash(params.taker.as_bytes()),
+            &uint256(params.amount.0)[..],
+            &algorithm.hash(params.target_chain.as_bytes()),
+            &uint256(params.salt.into())[..],
+        ]
+        .concat(),
+    )
+}
+
+// Share of `amount` at `rate`, a fraction scaled by `RATE_PRECISION`
+fn apply_rate(amount: Balance, rate: Balance) -> Balance {
+    // Same as `amount * rate / RATE_PRECISION` without overflowing, as `rate <= RATE_PRECISION`
+    amount / RATE_PRECISION * rate + amount % RATE_PRECISION * rate / RATE_PRECISION
+}
+
+/// Splits `amount` into the relayer fee and what's left for the recipient
+fn split_relayer_fee(amount: Balance, fee_bps: u16) -> (Balance, Balance) {
+    let fee_bps = Balance::from(fee_bps);
+    let max_fee_bps = Balance::from(MAX_FEE_BPS);
+    // Same as `amount * fee_bps / MAX_FEE_BPS` without overflowing
+    let fee = amount / max_fee_bps * fee_bps + amount % max_fee_bps * fee_bps / max_fee_bps;
+    (fee, amount - fee)
+}
+
+fn index_insert(
+    index: &mut LookupMap<AccountId, Vector<CryptoHash>>,
+    prefix: u8,
+    account_id: &AccountId,
+    lock_contract_id: &CryptoHash,
+) {
+    let mut ids = index.get(account_id).unwrap_or_else(|| {
+        let mut storage_prefix = vec![prefix];
+        storage_prefix.extend(env::sha256(account_id.as_bytes()));
+        Vector::new(storage_prefix)
+    });
+    ids.push(lock_contract_id);
+    index.insert(account_id, &ids);
+}
+
+fn index_remove(
+    index: &mut LookupMap<AccountId, Vector<CryptoHash>>,
+    account_id: &AccountId,
+    lock_contract_id: &CryptoHash,
+) {
+    if let Some(mut ids) = index.get(account_id) {
+        if let Some(position) = ids.iter().position(|id| &id == lock_contract_id) {
+            ids.swap_remove(position as u64);
+            index.insert(account_id, &ids);
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LockContractView {
+    pub lock_contract_id: String,
+    pub secret_hash: String,
+    pub recipient: AccountId,
+    pub sender: AccountId,
+    pub amount: U128,
+    pub endtime: u64,
+    pub withdrawn: bool,
+    pub refunded: bool,
+    pub preimage: String,
+    pub target_chain: String,
+    pub target_address: String,
+    pub hash_algorithm: HashAlgorithm,
+    pub cancellation_approved: bool,
+    pub relayer: Option<AccountId>,
+    pub relayer_fee_bps: u16,
+    pub token_type: TokenType,
+    pub token: Option<AccountId>,
+    pub created_at: u64,
+    pub timelocks: Timelocks,
+    pub parts_count: u32,
+    pub claimed_parts: Vec<u32>,
+    pub claimed_amount: U128,
+    pub auction: Option<AuctionDetails>,
+    // Nanoseconds `extend_timelock` added to `endtime`
+    pub extension: u64,
+    // Stage at the time of the call
+    pub stage: SwapStage,
+    // See `escrow_account_for`
+    pub escrow_account: AccountId,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CompletedSwapView {
+    pub completed_id: String,
+    pub source_chain: String,
+    pub source_address: String,
+    pub destination: AccountId,
+    pub amount: U128,
+    pub preimage: String,
+    pub completed_at: u64,
+    pub relayer: AccountId,
+}
+
+// NEP-297 events, logged as `EVENT_JSON:{...}` for relayers and indexers
+
+const HTLC_STANDARD: &str = "unreal_htlc";
+const HTLC_VERSION: &str = "1.0.0";
+
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a, T: serde::Serialize> {
+    standard: &'a str,
+    version: &'a str,
+    #[serde(flatten)]
+    event: T,
+}
+
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum HtlcEvent<'a> {
+    SwapInitiated(&'a [SwapInitiated<'a>]),
+    SwapWithdrawn(&'a [SwapWithdrawn<'a>]),
+    WithdrawFailed(&'a [WithdrawFailed<'a>]),
+    SwapRefunded(&'a [SwapRefunded<'a>]),
+    SwapCompleted(&'a [SwapCompleted<'a>]),
+    SwapCompletionFailed(&'a [SwapCompletionFailed<'a>]),
+    TimelockExtended(&'a [TimelockExtended]),
+    EvmTransactionSigned(&'a [EvmTransactionSigned<'a>]),
+    SwapPurged(&'a [SwapPurged<'a>]),
+    EmergencyRecovered(&'a [EmergencyRecovered<'a>]),
+    RelayerSlashed(&'a [RelayerSlashed<'a>]),
+    RelayerVindicated(&'a [RelayerVindicated<'a>]),
+}
+
+impl HtlcEvent<'_> {
+    pub fn emit(self) {
+        let event = EventLog { standard: HTLC_STANDARD, version: HTLC_VERSION, event: self };
+        log!(
+            "EVENT_JSON:{}",
+            near_sdk::serde_json::to_string(&event).expect("Failed to serialize event")
+        );
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapInitiated<'a> {
+    pub lock_contract_id: String,
+    pub sender: &'a AccountId,
+    pub recipient: &'a AccountId,
+    pub amount: U128,
+    // Absent for native NEAR locks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<&'a AccountId>,
+    pub secret_hash: String,
+    pub hash_algorithm: HashAlgorithm,
+    pub endtime: u64,
+    pub target_chain: &'a str,
+    pub target_address: &'a str,
+}
+
+// Reveals the preimage, so the counterparty can claim on the other chain
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapWithdrawn<'a> {
+    pub lock_contract_id: String,
+    pub recipient: &'a AccountId,
+    pub amount: U128,
+    pub preimage: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part_index: Option<u32>,
+    // Differs from the recipient for public withdrawals
+    pub caller: &'a AccountId,
+}
+
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WithdrawFailed<'a> {
+    pub lock_contract_id: String,
+    pub recipient: &'a AccountId,
+    // Whether the lock was reopened for the recipient to withdraw again
+    pub reverted: bool,
+    // Recorded for `retry_failed_payouts` unless reverted
+    pub failed_payouts: &'a [Payout],
+}
+
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapRefunded<'a> {
+    pub lock_contract_id: String,
+    pub sender: &'a AccountId,
+    pub amount: U128,
+    // Set when the sender redirected the refund with `refund_to`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refunded_to: Option<&'a AccountId>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapCompleted<'a> {
+    pub completed_id: String,
+    pub source_chain: &'a str,
+    pub source_address: &'a str,
+    pub destination: &'a AccountId,
+    pub amount: U128,
+    pub preimage: &'a str,
+    pub relayer: &'a AccountId,
+}
+
+// The mint of a completed swap failed, undoing its `swap_completed`
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapCompletionFailed<'a> {
+    pub completed_id: String,
+    pub destination: &'a AccountId,
+    pub amount: U128,
+}
+
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TimelockExtended {
+    pub lock_contract_id: String,
+    pub additional_hours: u64,
+    pub endtime: u64,
+}
+
+// A settled lock contract was deleted, repeating its preimage
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapPurged<'a> {
+    pub lock_contract_id: String,
+    pub preimage: &'a str,
+}
+
+// The owner moved a stranded lock's funds to the recovery address
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EmergencyRecovered<'a> {
+    pub lock_contract_id: String,
+    pub sender: &'a AccountId,
+    pub recovery_address: &'a AccountId,
+    pub amount: U128,
+}
+
+// The arbiter forfeited a relayer's safety deposit to the recovery address
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RelayerSlashed<'a> {
+    pub completed_id: String,
+    pub relayer: &'a AccountId,
+    pub amount: U128,
+    pub reason: &'a str,
+}
+
+// The arbiter returned a relayer's safety deposit
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RelayerVindicated<'a> {
+    pub completed_id: String,
+    pub relayer: &'a AccountId,
+    pub amount: U128,
+}
+
+// Ready to broadcast with `eth_sendRawTransaction`
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EvmTransactionSigned<'a> {
+    pub chain_id: u64,
+    pub signed_transaction: &'a str,
+    pub transaction_hash: String,
+    pub caller: &'a AccountId,
+}
+
+// Chain signatures (NEAR MPC) request and response, as defined by the signer contract
+
+#[derive(serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SignRequest {
+    pub payload: CryptoHash,
+    pub path: String,
+    pub key_version: u32,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SignatureResponse {
+    pub big_r: AffinePoint,
+    pub s: Scalar,
+    pub recovery_id: u8,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AffinePoint {
+    pub affine_point: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Scalar {
+    pub scalar: String,
+}
+
+// What UnrealToken's `transfer_from` returns. Only the credited amount is read, which falls
+// short of the amount pulled if the token charges a transfer fee
+#[derive(serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransferResult {
+    pub amount: U128,
+}
+
+// EIP-1559 transaction encoding
+
+/// `0x02 || rlp([fields])`, the typed transaction envelope
+fn eip1559_payload(encoded_fields: &[u8]) -> Vec<u8> {
+    [&[0x02][..], &rlp_encode_list(encoded_fields)].concat()
+}
+
+fn rlp_encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        bytes.to_vec()
+    } else {
+        [rlp_encode_length(bytes.len(), 0x80), bytes.to_vec()].concat()
+    }
+}
+
+/// Encodes `items`, already RLP encoded and concatenated, as a list
+fn rlp_encode_list(items: &[u8]) -> Vec<u8> {
+    [rlp_encode_length(items.len(), 0xc0), items.to_vec()].concat()
+}
+
+fn rlp_encode_uint(value: u128) -> Vec<u8> {
+    rlp_encode_uint_bytes(&value.to_be_bytes())
+}
+
+/// Big-endian integers are encoded without leading zeros
+fn rlp_encode_uint_bytes(bytes: &[u8]) -> Vec<u8> {
+    let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len());
+    rlp_encode_bytes(&bytes[start..])
+}
+
+fn rlp_encode_length(length: usize, offset: u8) -> Vec<u8> {
+    if length < 56 {
+        vec![offset + length as u8]
+    } else {
+        let length_bytes = length.to_be_bytes();
+        let start = length_bytes.iter().position(|byte| *byte != 0).unwrap();
+        [&[offset + 55 + (length_bytes.len() - start) as u8][..], &length_bytes[start..]].concat()
+    }
+}
+
+// Gas left for an outgoing call once `reserved` is set aside for its callback and the rest
+// of the current call. Panics with a clear message, instead of underflowing, unless at least
+// `required` is left
+fn remaining_gas(reserved: Gas, required: Gas) -> Gas {
+    env::prepaid_gas()
+        .checked_sub(env::used_gas())
+        .and_then(|gas| gas.checked_sub(reserved))
+        .and_then(|gas| gas.checked_sub(GAS_RESERVED_FOR_CURRENT_CALL))
+        .filter(|gas| *gas >= required)
+        .unwrap_or_else(|| env::panic_str("Not enough gas attached"))
+}
+
+fn is_promise_success(index: u64) -> bool {
+    matches!(env::promise_result(index), PromiseResult::Successful(_))
+}
+
+// Amount the HTLC was credited by the `transfer_from` of the first promise, if it went through
+fn transferred_amount() -> Option<Balance> {
+    match env::promise_result(0) {
+        PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<TransferResult>(&value)
+            .ok()
+            .map(|result| result.amount.0),
+        _ => None,
+    }
+}
+
+fn assert_ed25519(public_key: &PublicKey) {
+    require!(public_key.curve_type() == CurveType::ED25519, "Only ed25519 keys are supported");
+}
+
+fn decode_hex(value: &str, error: &str) -> Vec<u8> {
+    hex::decode(value.strip_prefix("0x").unwrap_or(value)).unwrap_or_else(|_| env::panic_str(error))
+}
+
+// Deposit required by NEP-141 transfers
+const ONE_YOCTO: NearToken = NearToken::from_yoctonear(1);
+
+// Cap on the cumulative time `extend_timelock` can add to a lock
+const MAX_TIMELOCK_EXTENSION_HOURS: u64 = 72;
+
+// Default bounds on a lock's timeout
+const MIN_TIMEOUT_HOURS: u64 = 1;
+const MAX_TIMEOUT_HOURS: u64 = 30 * 24;
+
+// Time after a lock's expiry before the owner can recover its funds
+const EMERGENCY_RECOVERY_GRACE_DAYS: u64 = 30;
+
+// Gas kept for the rest of the current call after scheduling outgoing calls
+const GAS_RESERVED_FOR_CURRENT_CALL: Gas = Gas::from_tgas(20);
+
+// Gas for token calls made by the HTLC
+const GAS_FOR_FT_TRANSFER: Gas = Gas::from_tgas(5);
+const GAS_FOR_FT_MINT: Gas = Gas::from_tgas(10);
+
+// Gas for the MPC signer's `sign` and for assembling the signed transaction
+const GAS_FOR_MPC_SIGN: Gas = Gas::from_tgas(250);
+const GAS_FOR_EVM_SIGNATURE_CALLBACK: Gas = Gas::from_tgas(10);
+
+// Gas for releasing a lock's reentrancy guard after a payout
+const GAS_FOR_PAYOUT_CALLBACK: Gas = Gas::from_tgas(5);
+
+// Gas for checking the protocol fee of an `ft_on_transfer` deposit reached the treasury
+const GAS_FOR_PROTOCOL_FEE_CALLBACK: Gas = Gas::from_tgas(5);
+
+// Gas for checking the mint of a completed swap, and for minting each relayer's fee share
+const GAS_FOR_MINT_CALLBACK: Gas = Gas::from_tgas(10);
+const GAS_FOR_RELAYER_FEE_MINT: Gas = Gas::from_tgas(10);
+
+// Gas for pulling a signed order's tokens from the maker, and for checking it went through,
+// returning a short funding
+const GAS_FOR_TRANSFER_FROM: Gas = Gas::from_tgas(20);
+const GAS_FOR_ORDER_CALLBACK: Gas = Gas::from_tgas(15);
+
+// Gas for checking a swap batch was funded, per lock of the batch, on top of returning a
+// short funding
+const GAS_FOR_BATCH_CALLBACK: Gas = Gas::from_tgas(5);
+
+// Chain signatures contract on mainnet
+const DEFAULT_MPC_SIGNER: &str = "v1.signer";
+const DEFAULT_DERIVATION_PATH: &str = "unreal-htlc";
+
+// Cap on the lock contracts `purge_settled_batch` deletes in one call
+const MAX_PURGE_BATCH: usize = 50;
+
+// Cap on the locks `initiate_swap_batch` creates in one call
+const MAX_SWAP_BATCH: usize = 10;
+
+// Basis points making up 100%
+const MAX_FEE_BPS: u16 = 10_000;
+
+// Ceiling on the protocol fee, 1%
+const MAX_PROTOCOL_FEE_BPS: u16 = 100;
+
+// Escrow ids are `<first ESCROW_PREFIX_LENGTH hex digits of the order hash>.escrow.<contract>`
+const ESCROW_NAMESPACE: &str = "escrow";
+const ESCROW_PREFIX_LENGTH: usize = 16;
+
+// Auction rate paying out the whole locked amount
+const RATE_PRECISION: Balance = 1_000_000_000_000_000_000;
+
+// External contract interfaces
+
+#[ext_contract(ext_fungible_token)]
+pub trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+    fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> Promise;
+    fn ft_mint(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+// Tokens with ERC-20 style allowances, like UnrealToken
+#[ext_contract(ext_allowance_token)]
+pub trait AllowanceToken {
+    fn transfer_from(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> TransferResult;
+}
+
+#[ext_contract(ext_mpc_signer)]
+pub trait MpcSigner {
+    fn sign(&mut self, request: SignRequest) -> Promise;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    /// Deploys the HTLC as `accounts(0)` owned by `accounts(1)`, with `accounts(2)` as a relayer
+    fn setup() -> (VMContextBuilder, UnrealHTLC) {
+        let mut context = VMContextBuilder::new();
+        context
+            .current_account_id(accounts(0))
+            .predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        let mut contract = UnrealHTLC::new();
+        contract.add_relayer_for_all_chains(accounts(2));
+        contract.add_supported_token(accounts(5));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        (context, contract)
+    }
+
+    /// Locks 100 tokens of `accounts(5)` from `accounts(3)` to `accounts(4)` behind sha256(`secret`)
+    fn lock(context: &mut VMContextBuilder, contract: &mut UnrealHTLC, secret: &str) -> CryptoHash {
+        let secret_hash = HashAlgorithm::Sha256.hash(secret.as_bytes());
+        lock_with_secret_hash(context, contract, secret_hash);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let lock_contract = contract
+            .get_lock_contracts_by_sender(accounts(3), None, None)
+            .into_iter()
+            .find(|lock_contract| lock_contract.secret_hash == hex::encode(secret_hash))
+            .unwrap();
+        hex::decode(lock_contract.lock_contract_id).unwrap().try_into().unwrap()
+    }
+
+    /// Runs the callback of a lock's payout as if it went through, leaving the contract as
+    /// predecessor
+    fn resolve_payout(context: &mut VMContextBuilder, contract: &mut UnrealHTLC, id: CryptoHash) {
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        let lock_contract = contract.get_lock_contract(id).unwrap();
+        let payout = Payout { receiver_id: lock_contract.sender, amount: lock_contract.amount };
+        contract.on_lock_payout(id, payout);
+    }
+
+    #[test]
+    #[should_panic(expected = "Swap already completed")]
+    fn test_complete_swap_twice() {
+        let (_, mut contract) = setup();
+        for _ in 0..2 {
+            contract.complete_swap(
+                "mainnet:1".to_string(),
+                "0x0000000000000000000000000000000000000001".to_string(),
+                accounts(3),
+                U128(100),
+                "secret".to_string(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_withdraw_with_keccak256_secret() {
+        let (mut context, mut contract) = setup();
+        // keccak256("hello"), as computed by `ethers.utils.id("hello")`
+        let secret_hash: CryptoHash =
+            hex::decode("1c8aff950685c2ed4bc3174f3472287b56d9517b9c948127319a09a7a36deac8")
+                .unwrap()
+                .try_into()
+                .unwrap();
+
+        let msg = near_sdk::serde_json::json!({
+            "secret_hash": secret_hash,
+            "recipient": accounts(4),
+            "timeout_hours": 24,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+            "hash_algorithm": HashAlgorithm::Keccak256,
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(100), msg.to_string());
+        let lock_contract = contract.get_lock_contracts_by_sender(accounts(3), None, None).remove(0);
+        let lock_contract_id: CryptoHash =
+            hex::decode(lock_contract.lock_contract_id).unwrap().try_into().unwrap();
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        assert!(contract.withdraw(lock_contract_id, "hello".to_string()));
+        let lock_contract = contract.get_lock_contract(lock_contract_id).unwrap();
+        assert!(lock_contract.withdrawn);
+        assert_eq!(lock_contract.hash_algorithm, HashAlgorithm::Keccak256);
+    }
+
+    #[test]
+    fn test_lock_contracts_by_sender_and_recipient() {
+        let (mut context, mut contract) = setup();
+        let first = lock(&mut context, &mut contract, "first");
+        lock(&mut context, &mut contract, "second");
+        assert_eq!(contract.get_lock_contracts_by_sender(accounts(3), None, None).len(), 2);
+        assert_eq!(contract.get_lock_contracts_by_recipient(accounts(4), None, None).len(), 2);
+        assert_eq!(contract.get_lock_contracts_by_sender(accounts(3), Some(1), Some(10)).len(), 1);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(first, "first".to_string());
+        let pending = contract.get_lock_contracts_by_recipient(accounts(4), None, None);
+        assert_eq!(pending.len(), 1);
+        assert_ne!(pending[0].lock_contract_id, hex::encode(first));
+        assert_eq!(contract.get_lock_contracts_by_sender(accounts(3), None, None).len(), 1);
+    }
+
+    #[test]
+    fn test_extend_timelock() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        let endtime = contract.get_lock_contract(lock_contract_id).unwrap().endtime;
+
+        contract.extend_timelock(lock_contract_id, 48);
+        contract.extend_timelock(lock_contract_id, 24);
+        let lock_contract = contract.get_lock_contract(lock_contract_id).unwrap();
+        assert_eq!(lock_contract.endtime, endtime + 72 * 3600 * 1_000_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Total extension exceeds the maximum")]
+    fn test_extend_timelock_over_cap() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        contract.extend_timelock(lock_contract_id, 48);
+        contract.extend_timelock(lock_contract_id, 25);
+    }
+
+    #[test]
+    #[should_panic(expected = "Timelock already expired")]
+    fn test_extend_expired_timelock() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        let endtime = contract.get_lock_contract(lock_contract_id).unwrap().endtime;
+
+        testing_env!(context.block_timestamp(endtime).build());
+        contract.extend_timelock(lock_contract_id, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not the sender")]
+    fn test_extend_timelock_not_sender() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.extend_timelock(lock_contract_id, 1);
+    }
+
+    #[test]
+    fn test_cancel_swap_after_approval() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.approve_cancellation(lock_contract_id);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert!(contract.cancel_swap(lock_contract_id));
+        assert!(contract.get_lock_contract(lock_contract_id).unwrap().refunded);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cancellation not approved by the recipient")]
+    fn test_cancel_swap_without_approval() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        contract.cancel_swap(lock_contract_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient safety deposit")]
+    fn test_complete_swap_underfunded_safety_deposit() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_safety_deposit_amount(U128(1_000));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(NearToken::from_yoctonear(999)).build());
+        contract.complete_swap(
+            "mainnet:1".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            accounts(3),
+            U128(100),
+            "secret".to_string(),
+        );
+    }
+
+    #[test]
+    fn test_split_relayer_fee() {
+        assert_eq!(split_relayer_fee(1_000, 0), (0, 1_000));
+        assert_eq!(split_relayer_fee(1_000, 30), (3, 997));
+        assert_eq!(split_relayer_fee(u128::MAX, MAX_FEE_BPS), (u128::MAX, 0));
+    }
+
+    #[test]
+    fn test_withdraw_pays_relayer_fee() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_relayer_fee_bps(30);
+
+        let msg = near_sdk::serde_json::json!({
+            "secret_hash": HashAlgorithm::Sha256.hash(b"secret"),
+            "recipient": accounts(4),
+            "timeout_hours": 24,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+            "relayer": accounts(2),
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(1_000), msg.to_string());
+        let lock_contract = contract.get_lock_contracts_by_sender(accounts(3), None, None).remove(0);
+        let lock_contract_id: CryptoHash =
+            hex::decode(lock_contract.lock_contract_id).unwrap().try_into().unwrap();
+        assert_eq!(contract.get_lock_contract(lock_contract_id).unwrap().relayer_fee_bps, 30);
+
+        // Raising the fee afterwards doesn't affect the existing swap
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_relayer_fee_bps(500);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+        // The fee and the remainder, plus the callback releasing the reentrancy guard
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 3);
+        assert_eq!(contract.get_lock_contract(lock_contract_id).unwrap().relayer_fee_bps, 30);
+    }
+
+    #[test]
+    fn test_native_near_swap() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(NearToken::from_yoctonear(1_000)).build());
+        let lock_contract_id = contract.initiate_swap_near(
+            HashAlgorithm::Sha256.hash(b"secret"),
+            accounts(4),
+            24,
+            "mainnet:1".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            None,
+            None,
+        );
+        let lock_contract = contract.get_lock_contract(lock_contract_id).unwrap();
+        assert_eq!(lock_contract.token_type, TokenType::Near);
+        assert_eq!(lock_contract.amount, U128(1_000));
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(NearToken::from_yoctonear(0)).build());
+        assert!(contract.withdraw(lock_contract_id, "secret".to_string()));
+        assert!(contract.get_lock_contract(lock_contract_id).unwrap().withdrawn);
+    }
+
+    #[test]
+    #[should_panic(expected = "initiate_swap is deprecated")]
+    fn test_initiate_swap_deprecated() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.initiate_swap(
+            HashAlgorithm::Sha256.hash(b"secret"),
+            accounts(4),
+            U128(100),
+            24,
+            "mainnet:1".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            None,
+            None,
+            Some(accounts(5)),
+        );
+    }
+
+    #[test]
+    fn test_ft_on_transfer_creates_lock() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        let lock_contract = contract.get_lock_contract(lock_contract_id).unwrap();
+        assert_eq!(lock_contract.sender, accounts(3));
+        assert_eq!(lock_contract.recipient, accounts(4));
+        assert_eq!(lock_contract.amount, U128(100));
+        assert_eq!(lock_contract.token, Some(accounts(5)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid swap message")]
+    fn test_ft_on_transfer_invalid_msg() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(100), "not a swap".to_string());
+    }
+
+    /// Locks 100 tokens from `accounts(3)` to `accounts(4)` with 1h finality,
+    /// public withdrawal after 2h and cancellation after 3h
+    fn lock_staged(context: &mut VMContextBuilder, contract: &mut UnrealHTLC) -> CryptoHash {
+        let msg = near_sdk::serde_json::json!({
+            "secret_hash": HashAlgorithm::Sha256.hash(b"secret"),
+            "recipient": accounts(4),
+            "timeout_hours": 0,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+            "timelocks": { "withdrawal": 3600, "public_withdrawal": 7200, "cancellation": 10800 },
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(100), msg.to_string());
+        let lock_contract = contract.get_lock_contracts_by_sender(accounts(3), None, None).remove(0);
+        hex::decode(lock_contract.lock_contract_id).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_swap_stages() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock_staged(&mut context, &mut contract);
+        let hour = 3600 * 1_000_000_000;
+        for (timestamp, stage) in [
+            (0, SwapStage::FinalityLock),
+            (hour, SwapStage::ExclusiveWithdrawal),
+            (2 * hour, SwapStage::PublicWithdrawal),
+            (3 * hour, SwapStage::Cancellation),
+        ] {
+            testing_env!(context.block_timestamp(timestamp).build());
+            assert_eq!(contract.swap_stage(lock_contract_id), stage);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Withdrawal not allowed in the current stage")]
+    fn test_withdraw_during_finality_lock() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock_staged(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Not the recipient")]
+    fn test_withdraw_exclusive_window_not_recipient() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock_staged(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(3600 * 1_000_000_000)
+            .build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+    }
+
+    #[test]
+    fn test_public_withdrawal_by_anyone() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock_staged(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(2 * 3600 * 1_000_000_000)
+            .build());
+        assert!(contract.withdraw(lock_contract_id, "secret".to_string()));
+    }
+
+    #[test]
+    fn test_public_withdraw_pays_recipient() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock_staged(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(2 * 3600 * 1_000_000_000)
+            .build());
+        assert!(contract.public_withdraw(lock_contract_id, "secret".to_string()));
+        let lock_contract = contract.get_lock_contract(lock_contract_id).unwrap();
+        assert!(lock_contract.withdrawn);
+        assert_eq!(lock_contract.recipient, accounts(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Public withdrawal closed")]
+    fn test_public_withdraw_once_cancellation_started() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock_staged(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(3 * 3600 * 1_000_000_000)
+            .build());
+        contract.public_withdraw(lock_contract_id, "secret".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Public withdrawal not open yet")]
+    fn test_public_withdraw_before_public_stage() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock_staged(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(3600 * 1_000_000_000)
+            .build());
+        contract.public_withdraw(lock_contract_id, "secret".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Public withdrawal closed")]
+    fn test_public_withdraw_after_refund() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock_staged(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(3 * 3600 * 1_000_000_000)
+            .build());
+        contract.refund(lock_contract_id);
+        resolve_payout(&mut context, &mut contract, lock_contract_id);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.public_withdraw(lock_contract_id, "secret".to_string());
+    }
+
+    /// Leaves of a 4-part tree with secrets "s0".."s3", its inner nodes and root
+    fn merkle_tree() -> ([CryptoHash; 4], [CryptoHash; 2], CryptoHash) {
+        let leaves = [0, 1, 2, 3]
+            .map(|index| merkle_leaf(HashAlgorithm::Sha256, index, format!("s{}", index).as_bytes()));
+        let nodes = [
+            hash_pair(HashAlgorithm::Sha256, leaves[0], leaves[1]),
+            hash_pair(HashAlgorithm::Sha256, leaves[2], leaves[3]),
+        ];
+        let root = hash_pair(HashAlgorithm::Sha256, nodes[0], nodes[1]);
+        (leaves, nodes, root)
+    }
+
+    #[test]
+    fn test_partial_fills() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.add_resolver(accounts(4));
+        let (leaves, nodes, root) = merkle_tree();
+        let msg = near_sdk::serde_json::json!({
+            "secret_merkle_root": root,
+            "parts_count": 4,
+            "recipient": accounts(4),
+            "timeout_hours": 24,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(100), msg.to_string());
+        let lock_contract = contract.get_lock_contracts_by_sender(accounts(3), None, None).remove(0);
+        let lock_contract_id: CryptoHash =
+            hex::decode(lock_contract.lock_contract_id).unwrap().try_into().unwrap();
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw_part(lock_contract_id, 0, "s0".to_string(), vec![leaves[1], nodes[1]]);
+        resolve_payout(&mut context, &mut contract, lock_contract_id);
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw_part(lock_contract_id, 2, "s2".to_string(), vec![leaves[3], nodes[0]]);
+        resolve_payout(&mut context, &mut contract, lock_contract_id);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        // A proof for another part doesn't verify
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.withdraw_part(lock_contract_id, 1, "s1".to_string(), vec![leaves[1], nodes[1]]);
+        }));
+        assert!(result.is_err());
+
+        let lock_contract = contract.get_lock_contract(lock_contract_id).unwrap();
+        assert_eq!(lock_contract.claimed_parts, vec![0, 2]);
+        assert_eq!(lock_contract.claimed_amount, U128(50));
+        assert!(!lock_contract.withdrawn);
+
+        // The unclaimed half goes back to the sender after expiry
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(24 * 3600 * 1_000_000_000)
+            .build());
+        assert!(contract.refund(lock_contract_id));
+    }
+
+    #[test]
+    #[should_panic(expected = "Part already claimed")]
+    fn test_partial_fill_claimed_twice() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.add_resolver(accounts(4));
+        let (leaves, nodes, root) = merkle_tree();
+        let msg = near_sdk::serde_json::json!({
+            "secret_merkle_root": root,
+            "parts_count": 4,
+            "recipient": accounts(4),
+            "timeout_hours": 24,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(100), msg.to_string());
+        let lock_contract = contract.get_lock_contracts_by_sender(accounts(3), None, None).remove(0);
+        let lock_contract_id: CryptoHash =
+            hex::decode(lock_contract.lock_contract_id).unwrap().try_into().unwrap();
+
+        for _ in 0..2 {
+            testing_env!(context.predecessor_account_id(accounts(4)).build());
+            contract.withdraw_part(lock_contract_id, 0, "s0".to_string(), vec![leaves[1], nodes[1]]);
+            resolve_payout(&mut context, &mut contract, lock_contract_id);
+        }
+    }
+
+    #[test]
+    fn test_lock_contract_view_json() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+
+        let view = contract.get_lock_contract(lock_contract_id).unwrap();
+        let json = near_sdk::serde_json::to_value(&view).unwrap();
+        // Fields the frontend already reads keep their names and encoding
+        assert_eq!(json["lock_contract_id"], hex::encode(lock_contract_id));
+        assert_eq!(json["secret_hash"], hex::encode(HashAlgorithm::Sha256.hash(b"secret")));
+        assert_eq!(json["recipient"], accounts(4).to_string());
+        assert_eq!(json["sender"], accounts(3).to_string());
+        assert_eq!(json["amount"], "100");
+        assert_eq!(json["endtime"], 24 * 3600 * 1_000_000_000u64);
+        assert_eq!(json["withdrawn"], false);
+        assert_eq!(json["refunded"], false);
+        assert_eq!(json["preimage"], "");
+        assert_eq!(json["target_chain"], "mainnet:1");
+        assert_eq!(json["target_address"], "0x0000000000000000000000000000000000000001");
+        // Fields added since
+        assert_eq!(json["hash_algorithm"], "Sha256");
+        assert_eq!(json["token_type"], "Ft");
+        assert_eq!(json["token"], accounts(5).to_string());
+        assert_eq!(json["relayer"], near_sdk::serde_json::Value::Null);
+        assert_eq!(json["timelocks"]["cancellation"], 24 * 3600);
+        assert_eq!(json["parts_count"], 0);
+        assert_eq!(json["claimed_amount"], "0");
+        assert_eq!(json["auction"], near_sdk::serde_json::Value::Null);
+        assert_eq!(json["extension"], 0);
+        assert_eq!(json["stage"], "ExclusiveWithdrawal");
+        let escrow_account = contract.escrow_account_for(lock_contract_id);
+        assert_eq!(json["escrow_account"], escrow_account.to_string());
+        assert_eq!(json.as_object().unwrap().len(), 26);
+    }
+
+    #[test]
+    fn test_failed_withdraw_transfer_is_reverted() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(0));
+
+        // The token rejects the transfer, e.g. because the HTLC's balance is short
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        let payouts = vec![Payout { receiver_id: accounts(4), amount: U128(100) }];
+        assert!(!contract.on_withdraw_transfer(lock_contract_id, payouts));
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| {
+            log.contains("\"event\":\"withdraw_failed\"") && log.contains("\"reverted\":true")
+        }));
+
+        let lock_contract = contract.get_lock_contract(lock_contract_id).unwrap();
+        assert!(!lock_contract.withdrawn);
+        assert_eq!(lock_contract.preimage, "");
+        assert_eq!(contract.get_lock_contracts_by_recipient(accounts(4), None, None).len(), 1);
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(100));
+        assert_eq!(contract.active_swap_count(), 1);
+
+        // The recipient can retry
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        assert!(contract.withdraw(lock_contract_id, "secret".to_string()));
+    }
+
+    #[test]
+    fn test_partly_failed_withdraw_transfer_stays_withdrawn() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+
+        // The recipient got paid, the relayer's fee transfer failed
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(Vec::new()), PromiseResult::Failed],
+        );
+        let relayer_fee = Payout { receiver_id: accounts(2), amount: U128(10) };
+        let payouts = vec![
+            Payout { receiver_id: accounts(4), amount: U128(90) },
+            relayer_fee.clone(),
+        ];
+        assert!(!contract.on_withdraw_transfer(lock_contract_id, payouts));
+        assert!(contract.get_lock_contract(lock_contract_id).unwrap().withdrawn);
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(0));
+        assert_eq!(contract.get_failed_payouts(lock_contract_id), vec![relayer_fee.clone()]);
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| {
+            log.contains("\"failed_payouts\":[{\"receiver_id\":\"charlie\",\"amount\":\"10\"}]")
+        }));
+
+        // A settled lock keeps its failed payouts until they went through
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.purge_settled(lock_contract_id);
+        }));
+        assert!(result.is_err());
+
+        // Anyone can retry, which only pays the failed transfer again
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert!(contract.retry_failed_payouts(lock_contract_id));
+        assert!(contract.get_failed_payouts(lock_contract_id).is_empty());
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        match &receipts[0].actions[0] {
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. } => {
+                assert_eq!(method_name, b"ft_transfer");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["receiver_id"], accounts(2).to_string());
+                assert_eq!(args["amount"], "10");
+            }
+            _ => panic!("Expected the retried transfer"),
+        }
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(Vec::new())],
+        );
+        assert!(contract.on_payout_retry(lock_contract_id, vec![relayer_fee]));
+        assert!(contract.get_failed_payouts(lock_contract_id).is_empty());
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert!(contract.purge_settled(lock_contract_id).0 > 0);
+    }
+
+    #[test]
+    fn test_failed_refund_transfer_is_recorded() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(24 * 3600 * 1_000_000_000)
+            .build());
+        contract.refund(lock_contract_id);
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        let refund = Payout { receiver_id: accounts(3), amount: U128(100) };
+        assert!(!contract.on_lock_payout(lock_contract_id, refund.clone()));
+        assert!(contract.get_lock_contract(lock_contract_id).unwrap().refunded);
+        assert_eq!(contract.get_failed_payouts(lock_contract_id), vec![refund]);
+
+        // The refund is retried instead of being left to emergency recovery
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert!(contract.retry_failed_payouts(lock_contract_id));
+        assert!(contract.get_failed_payouts(lock_contract_id).is_empty());
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        match &receipts[0].actions[0] {
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. } => {
+                assert_eq!(method_name, b"ft_transfer");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["receiver_id"], accounts(3).to_string());
+                assert_eq!(args["amount"], "100");
+            }
+            _ => panic!("Expected the retried refund"),
+        }
+    }
+
+    #[test]
+    fn test_failed_part_transfer_is_recorded() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.add_resolver(accounts(4));
+        let (leaves, nodes, root) = merkle_tree();
+        let msg = near_sdk::serde_json::json!({
+            "secret_merkle_root": root,
+            "parts_count": 4,
+            "recipient": accounts(4),
+            "timeout_hours": 24,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(100), msg.to_string());
+        let lock_contract = contract.get_lock_contracts_by_sender(accounts(3), None, None).remove(0);
+        let lock_contract_id: CryptoHash =
+            hex::decode(lock_contract.lock_contract_id).unwrap().try_into().unwrap();
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw_part(lock_contract_id, 0, "s0".to_string(), vec![leaves[1], nodes[1]]);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        let payout = Payout { receiver_id: accounts(4), amount: U128(25) };
+        assert!(!contract.on_withdraw_transfer(lock_contract_id, vec![payout.clone()]));
+
+        // The part stays claimed, its payout is left for a retry
+        let lock_contract = contract.get_lock_contract(lock_contract_id).unwrap();
+        assert_eq!(lock_contract.claimed_parts, vec![0]);
+        assert_eq!(contract.get_failed_payouts(lock_contract_id), vec![payout]);
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw_part(lock_contract_id, 2, "s2".to_string(), vec![leaves[3], nodes[0]]);
+    }
+
+    /// Deposits 100 tokens from `accounts(3)` to `accounts(4)` behind `secret_hash`
+    fn lock_with_secret_hash(
+        context: &mut VMContextBuilder,
+        contract: &mut UnrealHTLC,
+        secret_hash: CryptoHash,
+    ) {
+        let msg = near_sdk::serde_json::json!({
+            "secret_hash": secret_hash,
+            "recipient": accounts(4),
+            "timeout_hours": 24,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(100), msg.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Secret hash is blocked")]
+    fn test_lock_with_zero_secret_hash() {
+        let (mut context, mut contract) = setup();
+        lock_with_secret_hash(&mut context, &mut contract, [0; 32]);
+    }
+
+    #[test]
+    fn test_lock_with_blocked_secret_hash() {
+        let (mut context, mut contract) = setup();
+        let secret_hash = HashAlgorithm::Sha256.hash(b"password");
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.block_secret_hash(secret_hash);
+        assert!(contract.is_secret_hash_blocked(secret_hash));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            lock_with_secret_hash(&mut context, &mut contract, secret_hash);
+        }));
+        assert!(result.is_err());
+
+        // A normal hash is accepted, and so is the blocked one once unblocked
+        lock_with_secret_hash(&mut context, &mut contract, HashAlgorithm::Sha256.hash(b"secret"));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.unblock_secret_hash(secret_hash);
+        lock_with_secret_hash(&mut context, &mut contract, secret_hash);
+        assert_eq!(contract.get_lock_contracts_by_sender(accounts(3), None, None).len(), 2);
+    }
+
+    #[test]
+    fn test_escrow_account_for() {
+        let (_, contract) = setup();
+        let order_hash = HashAlgorithm::Sha256.hash(b"order");
+        let escrow = contract.escrow_account_for(order_hash);
+        assert_eq!(escrow, contract.escrow_account_for(order_hash));
+        assert_eq!(
+            escrow.as_str(),
+            format!("{}.escrow.{}", &hex::encode(order_hash)[..16], accounts(0))
+        );
+        let other_hash = HashAlgorithm::Sha256.hash(b"other order");
+        assert_ne!(contract.escrow_account_for(other_hash), escrow);
+    }
+
+    #[test]
+    fn test_payout_memo_names_escrow() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        match &receipts[0].actions[0] {
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. } => {
+                assert_eq!(method_name, b"ft_transfer");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                let escrow = contract.escrow_account_for(lock_contract_id);
+                assert_eq!(args["memo"], format!("Released from escrow {}", escrow));
+            }
+            _ => panic!("Expected the payout transfer"),
+        }
+    }
+
+    #[test]
+    fn test_withdraw_event_reveals_preimage() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+        let event = near_sdk::test_utils::get_logs()
+            .into_iter()
+            .find_map(|log| log.strip_prefix("EVENT_JSON:").map(str::to_string))
+            .unwrap();
+        let event: near_sdk::serde_json::Value = near_sdk::serde_json::from_str(&event).unwrap();
+        assert_eq!(event["standard"], "unreal_htlc");
+        assert_eq!(event["event"], "swap_withdrawn");
+        assert_eq!(event["data"][0]["preimage"], "secret");
+        assert_eq!(event["data"][0]["lock_contract_id"], hex::encode(lock_contract_id));
+    }
+
+    #[test]
+    fn test_swap_status() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        assert_eq!(
+            contract.swap_status(lock_contract_id),
+            Some(SwapStatus::Pending { remaining_seconds: 24 * 3600 })
+        );
+        assert_eq!(contract.swap_status([0; 32]), None);
+
+        testing_env!(context.block_timestamp(24 * 3600 * 1_000_000_000).build());
+        assert_eq!(contract.swap_status(lock_contract_id), Some(SwapStatus::Expired));
+
+        contract.refund(lock_contract_id);
+        assert_eq!(contract.swap_status(lock_contract_id), Some(SwapStatus::Refunded));
+    }
+
+    #[test]
+    fn test_rlp_encoding() {
+        assert_eq!(rlp_encode_bytes(b"dog"), hex::decode("83646f67").unwrap());
+        assert_eq!(
+            rlp_encode_list(&[rlp_encode_bytes(b"cat"), rlp_encode_bytes(b"dog")].concat()),
+            hex::decode("c88363617483646f67").unwrap()
+        );
+        assert_eq!(rlp_encode_uint(0), vec![0x80]);
+        assert_eq!(rlp_encode_uint(15), vec![0x0f]);
+        assert_eq!(rlp_encode_uint(1024), hex::decode("820400").unwrap());
+        assert_eq!(rlp_encode_bytes(&[0; 56])[..2], [0xb8, 56]);
+    }
+
+    #[test]
+    fn test_execute_on_evm_requests_signature() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(1)).build());
+        contract.execute_on_evm(
+            "mainnet:1".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            "0xdeadbeef".to_string(),
+            U128(100_000),
+            0,
+            U128(30_000_000_000),
+            U128(1_000_000_000),
+        );
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 2);
+    }
+
+    #[test]
+    fn test_per_chain_relayers() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.add_relayer(accounts(3), ChainId::ethereum_mainnet());
+        assert!(contract.is_relayer_for(accounts(3), ChainId::ethereum_mainnet()));
+        assert!(!contract.is_relayer_for(accounts(3), ChainId::ethereum_sepolia()));
+        assert!(contract.is_relayer_for(accounts(2), ChainId::ethereum_sepolia()));
+
+        contract.remove_relayer(accounts(3), ChainId::ethereum_mainnet());
+        assert!(!contract.is_relayer(&accounts(3)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Not an authorized relayer")]
+    fn test_complete_swap_relayer_for_other_chain() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.add_relayer(accounts(3), ChainId::ethereum_sepolia());
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.complete_swap(
+            ChainId::ethereum_mainnet().to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            accounts(4),
+            U128(100),
+            "secret".to_string(),
+        );
+    }
+
+    #[test]
+    fn test_total_locked() {
+        let (mut context, mut contract) = setup();
+        let first = lock(&mut context, &mut contract, "first");
+        lock(&mut context, &mut contract, "second");
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(200));
+        assert_eq!(contract.active_swap_count(), 2);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(first, "first".to_string());
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(100));
+        assert_eq!(contract.active_swap_count(), 1);
+        assert_eq!(contract.total_locked(None), U128(0));
+    }
+
+    #[test]
+    fn test_purge_settled() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+        resolve_payout(&mut context, &mut contract, lock_contract_id);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .storage_usage(env::storage_usage())
+            .build());
+        assert!(contract.purge_settled(lock_contract_id).0 > 0);
+        assert!(!contract.has_lock_contract(lock_contract_id));
+        // The freed storage stake stays with the contract
+        assert!(near_sdk::test_utils::get_created_receipts().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Lock contract is not settled")]
+    fn test_purge_pending_lock() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        contract.purge_settled(lock_contract_id);
+    }
+
+    #[test]
+    fn test_verify_preimage() {
+        let (_, _) = setup();
+        let expected = HashAlgorithm::Sha256.hash(b"secret");
+        assert!(verify_preimage("secret", &expected, HashAlgorithm::Sha256));
+        assert!(!verify_preimage("wrong", &expected, HashAlgorithm::Sha256));
+        assert!(!verify_preimage("", &expected, HashAlgorithm::Sha256));
+        assert!(!verify_preimage("secret", &expected, HashAlgorithm::Keccak256));
+    }
+
+    #[test]
+    #[should_panic(expected = "Secret hash does not match")]
+    fn test_withdraw_empty_preimage() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, String::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "Reentrant call")]
+    fn test_withdraw_reentered_by_token() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+
+        // A malicious token calling back into the HTLC before the payout resolved
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Already withdrawn")]
+    fn test_payout_callback_releases_guard() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+
+        resolve_payout(&mut context, &mut contract, lock_contract_id);
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Reentrant call")]
+    fn test_withdraw_before_deposit_resolved() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        // As left by an `initiate_swap` deposit still in flight
+        contract.in_flight.insert(&lock_contract_id, &true);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+    }
+
+    #[test]
+    fn test_failed_deposit_drops_lock() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        // As left by an `initiate_swap` deposit still in flight
+        contract.in_flight.insert(&lock_contract_id, &true);
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        assert!(!contract.on_ft_transfer_call(lock_contract_id, accounts(3), accounts(4), U128(100)));
+        assert!(!contract.has_lock_contract(lock_contract_id));
+        assert!(contract.in_flight.get(&lock_contract_id).is_none());
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(0));
+    }
+
+    #[test]
+    fn test_failed_mint_allows_retry() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_safety_deposit_amount(U128(1_000));
+        let complete = |contract: &mut UnrealHTLC| {
+            contract.complete_swap(
+                "mainnet:1".to_string(),
+                "0x0000000000000000000000000000000000000001".to_string(),
+                accounts(3),
+                U128(100),
+                "secret".to_string(),
+            );
+        };
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(NearToken::from_yoctonear(1_000)).build());
+        complete(&mut contract);
+        let completed_id: CryptoHash = env::sha256(
+            &[
+                "mainnet:1".as_bytes(),
+                "0x0000000000000000000000000000000000000001".as_bytes(),
+                accounts(3).as_bytes(),
+                &100u128.to_le_bytes(),
+                "secret".as_bytes(),
+            ]
+            .concat(),
+        )
+        .try_into()
+        .unwrap();
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(NearToken::from_yoctonear(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        assert!(!contract.on_complete_swap_mint(completed_id, accounts(3), U128(100), Vec::new()));
+        // The safety deposit goes back to the relayer
+        assert_eq!(near_sdk::test_utils::get_created_receipts().len(), 1);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(NearToken::from_yoctonear(1_000)).build());
+        complete(&mut contract);
+    }
+
+    #[test]
+    fn test_complete_swap_mints_on_token() {
+        let (_, mut contract) = setup();
+        contract.complete_swap(
+            "mainnet:1".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            accounts(3),
+            U128(100),
+            "secret".to_string(),
+        );
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, contract.get_default_token());
+        assert!(matches!(
+            &receipts[0].actions[0],
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, .. } if method_name == b"ft_mint"
+        ));
+    }
+
+    #[test]
+    fn test_emergency_recover_after_grace_period() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .block_timestamp((24 + 30 * 24) * 3600 * 1_000_000_000)
+            .build());
+        contract.set_recovery_address(accounts(2));
+        assert!(contract.emergency_recover(lock_contract_id));
+        assert!(contract.get_lock_contract(lock_contract_id).unwrap().refunded);
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Emergency recovery grace period not over")]
+    fn test_emergency_recover_before_grace_period() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .block_timestamp((24 + 30 * 24) * 3600 * 1_000_000_000 - 1)
+            .build());
+        contract.emergency_recover(lock_contract_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not the owner")]
+    fn test_emergency_recover_not_owner() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context.block_timestamp((24 + 30 * 24) * 3600 * 1_000_000_000).build());
+        contract.emergency_recover(lock_contract_id);
+    }
+
+    #[test]
+    fn test_target_chain_stored_in_canonical_form() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(NearToken::from_yoctonear(1_000)).build());
+        let lock_contract_id = contract.initiate_swap_near(
+            HashAlgorithm::Sha256.hash(b"secret"),
+            accounts(4),
+            24,
+            "testnet:011155111".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            None,
+            None,
+        );
+        let lock_contract = contract.get_lock_contract(lock_contract_id).unwrap();
+        assert_eq!(lock_contract.target_chain, ChainId::ethereum_sepolia().to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported target chain")]
+    fn test_initiate_swap_to_unsupported_chain() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.remove_supported_chain(ChainId::ethereum_sepolia());
+        assert_eq!(contract.supported_chains(), vec![ChainId::ethereum_mainnet()]);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(NearToken::from_yoctonear(1_000)).build());
+        contract.initiate_swap_near(
+            HashAlgorithm::Sha256.hash(b"secret"),
+            accounts(4),
+            24,
+            ChainId::ethereum_sepolia().to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected <network>:<chain_id>")]
+    fn test_initiate_swap_to_malformed_chain() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(NearToken::from_yoctonear(1_000)).build());
+        contract.initiate_swap_near(
+            HashAlgorithm::Sha256.hash(b"secret"),
+            accounts(4),
+            24,
+            "ethereum".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            None,
+            None,
+        );
+    }
+
+    /// Locks 1000 yoctoNEAR for `timeout_hours` as `accounts(3)`
+    fn lock_near_for(
+        context: &mut VMContextBuilder,
+        contract: &mut UnrealHTLC,
+        timeout_hours: u64,
+    ) -> CryptoHash {
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(NearToken::from_yoctonear(1_000)).build());
+        contract.initiate_swap_near(
+            HashAlgorithm::Sha256.hash(b"secret"),
+            accounts(4),
+            timeout_hours,
+            "mainnet:1".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    #[should_panic(expected = "Timeout below the minimum")]
+    fn test_zero_timeout() {
+        let (mut context, mut contract) = setup();
+        lock_near_for(&mut context, &mut contract, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Timeout below the minimum")]
+    fn test_timeout_below_minimum() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_timeout_bounds(2, 48);
+        lock_near_for(&mut context, &mut contract, 1);
+    }
+
+    #[test]
+    fn test_timeout_in_range() {
+        let (mut context, mut contract) = setup();
+        assert_eq!(contract.get_timeout_bounds(), (MIN_TIMEOUT_HOURS, MAX_TIMEOUT_HOURS));
+        let lock_contract_id = lock_near_for(&mut context, &mut contract, MIN_TIMEOUT_HOURS);
+        assert!(contract.has_lock_contract(lock_contract_id));
+        // A later salt keeps the second lock's id apart from the first
+        context.block_timestamp(1);
+        lock_near_for(&mut context, &mut contract, MAX_TIMEOUT_HOURS);
+    }
+
+    #[test]
+    #[should_panic(expected = "Timeout above the maximum")]
+    fn test_timeout_above_maximum() {
+        let (mut context, mut contract) = setup();
+        lock_near_for(&mut context, &mut contract, MAX_TIMEOUT_HOURS + 1);
+    }
+
+    #[test]
+    fn test_get_preimage_once_withdrawn() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        assert_eq!(contract.get_preimage(lock_contract_id), None);
+        assert_eq!(contract.get_preimage([0; 32]), None);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+        assert_eq!(contract.get_preimage(lock_contract_id), Some("secret".to_string()));
+    }
+
+    #[test]
+    fn test_complete_swap_split_between_two_relayers() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_relayer_fee_bps(30);
+        contract.add_relayer_for_all_chains(accounts(3));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.complete_swap_split(
+            "mainnet:1".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            accounts(4),
+            U128(1_000),
+            "secret".to_string(),
+            vec![(accounts(2), 20), (accounts(3), 10)],
+        );
+        // The destination gets the amount minus both shares
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        match &receipts[0].actions[0] {
+            near_sdk::mock::MockAction::FunctionCallWeight { args, .. } => {
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["amount"], "997");
+            }
+            _ => panic!("Expected the destination mint"),
+        }
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        assert!(contract.on_complete_swap_mint(
+            [0; 32],
+            accounts(4),
+            U128(1_000),
+            vec![(accounts(2), U128(2)), (accounts(3), U128(1))],
+        ));
+        // One fee mint per relayer
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, contract.get_default_token());
+    }
+
+    #[test]
+    #[should_panic(expected = "Relayer shares must sum to the relayer fee")]
+    fn test_complete_swap_split_shares_dont_sum() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_relayer_fee_bps(30);
+        contract.add_relayer_for_all_chains(accounts(3));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.complete_swap_split(
+            "mainnet:1".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            accounts(4),
+            U128(1_000),
+            "secret".to_string(),
+            vec![(accounts(2), 20), (accounts(3), 5)],
+        );
+    }
+
+    /// Locks 1000 tokens from `accounts(3)` to `accounts(4)` with an hour long auction
+    /// from the full amount down to half of it, leaving `accounts(3)` as predecessor
+    fn lock_with_auction(
+        context: &mut VMContextBuilder,
+        contract: &mut UnrealHTLC,
+        end_rate: u128,
+    ) -> CryptoHash {
+        let msg = near_sdk::serde_json::json!({
+            "secret_hash": HashAlgorithm::Sha256.hash(b"secret"),
+            "recipient": accounts(4),
+            "timeout_hours": 24,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+            "auction": {
+                "start_time": 0,
+                "duration": 3600,
+                "start_rate": RATE_PRECISION.to_string(),
+                "end_rate": end_rate.to_string(),
+            },
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(1_000), msg.to_string());
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let lock_contract = contract.get_lock_contracts_by_sender(accounts(3), None, None).remove(0);
+        hex::decode(lock_contract.lock_contract_id).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_auction_rate_decays_linearly() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock_with_auction(&mut context, &mut contract, RATE_PRECISION / 2);
+        assert_eq!(contract.current_rate(lock_contract_id).0, RATE_PRECISION);
+
+        testing_env!(context.block_timestamp(1_800 * 1_000_000_000).build());
+        assert_eq!(contract.current_rate(lock_contract_id).0, RATE_PRECISION / 4 * 3);
+
+        testing_env!(context.block_timestamp(7_200 * 1_000_000_000).build());
+        assert_eq!(contract.current_rate(lock_contract_id).0, RATE_PRECISION / 2);
+    }
+
+    #[test]
+    fn test_withdraw_pays_auction_rate() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock_with_auction(&mut context, &mut contract, RATE_PRECISION / 2);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(1_800 * 1_000_000_000)
+            .build());
+        contract.withdraw(lock_contract_id, "secret".to_string());
+        // The recipient gets 75% and the sender the rest back
+        let amounts: Vec<(AccountId, String)> = near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .filter_map(|receipt| match &receipt.actions[0] {
+                near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. }
+                    if method_name == b"ft_transfer" =>
+                {
+                    let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                    Some((
+                        args["receiver_id"].as_str().unwrap().parse().unwrap(),
+                        args["amount"].as_str().unwrap().to_string(),
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            amounts,
+            vec![(accounts(4), "750".to_string()), (accounts(3), "250".to_string())]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Auction rate out of range")]
+    fn test_auction_rate_above_full_amount() {
+        let (mut context, mut contract) = setup();
+        lock_with_auction(&mut context, &mut contract, RATE_PRECISION + 1);
+    }
+
+    /// Implicit account of the ed25519 key with a secret of `[7; 32]`
+    const ORDER_MAKER: &str = "ea4a6c63e29c520abef5507b132ec5f9954776aebebe7b92421eea691446d22c";
+    const ORDER_KEY: &str = "ed25519:GmaDrppBC7P5ARKV8g3djiwP89vz1jLK23V2GBjuAEGB";
+    /// `ORDER_KEY` signing `signed_order()` for the HTLC at `accounts(0)`
+    const ORDER_SIGNATURE: &str = "2763cae3eab83f2e7edc0e74d12d4c09b039ff8164f0885902ed0b1bee7a2da5\
+                                   98419a3a06bc72cfbf9edbad33abaec7ee3907de0be1d24abad9b7715033c10a";
+
+    fn signed_order() -> SignedOrder {
+        SignedOrder {
+            maker: ORDER_MAKER.parse().unwrap(),
+            token: accounts(5),
+            amount: U128(1_000),
+            secret_hash: HashAlgorithm::Sha256.hash(b"secret"),
+            target_chain: "mainnet:1".to_string(),
+            target_address: "0x0000000000000000000000000000000000000001".to_string(),
+            timeout_hours: 24,
+            deadline: 1_000,
+            salt: 1,
+        }
+    }
+
+    /// `setup` with the relayer `accounts(2)` also whitelisted as a resolver
+    fn setup_with_resolver() -> (VMContextBuilder, UnrealHTLC) {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.add_resolver(accounts(2));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        (context, contract)
+    }
+
+    #[test]
+    fn test_fill_signed_order() {
+        let (_, mut contract) = setup_with_resolver();
+        let lock_contract_id = contract.fill_signed_order(
+            signed_order(),
+            ORDER_SIGNATURE.to_string(),
+            ORDER_KEY.to_string(),
+        );
+        let lock_contract = contract.get_lock_contract(lock_contract_id).unwrap();
+        assert_eq!(lock_contract.sender.as_str(), ORDER_MAKER);
+        assert_eq!(lock_contract.recipient, accounts(2));
+        assert_eq!(lock_contract.amount, U128(1_000));
+        assert!(contract.is_order_filled(ORDER_MAKER.parse().unwrap(), 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid signature")]
+    fn test_fill_tampered_signed_order() {
+        let (_, mut contract) = setup_with_resolver();
+        let order = SignedOrder { amount: U128(1_000_000), ..signed_order() };
+        contract.fill_signed_order(order, ORDER_SIGNATURE.to_string(), ORDER_KEY.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Order already filled")]
+    fn test_fill_signed_order_twice() {
+        let (_, mut contract) = setup_with_resolver();
+        for _ in 0..2 {
+            contract.fill_signed_order(
+                signed_order(),
+                ORDER_SIGNATURE.to_string(),
+                ORDER_KEY.to_string(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_failed_order_funding_frees_salt() {
+        let (mut context, mut contract) = setup_with_resolver();
+        let lock_contract_id = contract.fill_signed_order(
+            signed_order(),
+            ORDER_SIGNATURE.to_string(),
+            ORDER_KEY.to_string(),
+        );
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        let maker: AccountId = ORDER_MAKER.parse().unwrap();
+        assert!(!contract.on_signed_order_funded(lock_contract_id, maker.clone(), 1));
+        assert!(contract.get_lock_contract(lock_contract_id).is_none());
+        assert!(!contract.is_order_filled(maker, 1));
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(0));
+    }
+
+    #[test]
+    fn test_order_funding() {
+        let (mut context, mut contract) = setup_with_resolver();
+        let lock_contract_id = contract.fill_signed_order(
+            signed_order(),
+            ORDER_SIGNATURE.to_string(),
+            ORDER_KEY.to_string(),
+        );
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![transfer_result(1_000)],
+        );
+        assert!(contract.on_signed_order_funded(lock_contract_id, ORDER_MAKER.parse().unwrap(), 1));
+        assert!(contract.has_lock_contract(lock_contract_id));
+    }
+
+    #[test]
+    fn test_short_order_funding_drops_lock() {
+        let (mut context, mut contract) = setup_with_resolver();
+        let lock_contract_id = contract.fill_signed_order(
+            signed_order(),
+            ORDER_SIGNATURE.to_string(),
+            ORDER_KEY.to_string(),
+        );
+
+        // The token kept a transfer fee out of the 1000 pulled
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![transfer_result(990)],
+        );
+        let maker: AccountId = ORDER_MAKER.parse().unwrap();
+        assert!(!contract.on_signed_order_funded(lock_contract_id, maker.clone(), 1));
+        assert!(contract.get_lock_contract(lock_contract_id).is_none());
+        assert!(!contract.is_order_filled(maker.clone(), 1));
+        // What arrived goes back to the maker
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        match &receipts[0].actions[0] {
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. } => {
+                assert_eq!(method_name, b"ft_transfer");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["receiver_id"], maker.to_string());
+                assert_eq!(args["amount"], "990");
+            }
+            _ => panic!("Expected the funding to be returned"),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Not a whitelisted resolver")]
+    fn test_fill_signed_order_by_non_resolver() {
+        // `accounts(2)` is a relayer, which doesn't make it a resolver
+        let (_, mut contract) = setup();
+        contract.fill_signed_order(
+            signed_order(),
+            ORDER_SIGNATURE.to_string(),
+            ORDER_KEY.to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Not a whitelisted resolver")]
+    fn test_partial_fill_by_non_resolver() {
+        let (mut context, mut contract) = setup();
+        let (leaves, nodes, root) = merkle_tree();
+        let msg = near_sdk::serde_json::json!({
+            "secret_merkle_root": root,
+            "parts_count": 4,
+            "recipient": accounts(4),
+            "timeout_hours": 24,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(100), msg.to_string());
+        let lock_contract = contract.get_lock_contracts_by_sender(accounts(3), None, None).remove(0);
+        let lock_contract_id: CryptoHash =
+            hex::decode(lock_contract.lock_contract_id).unwrap().try_into().unwrap();
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw_part(lock_contract_id, 0, "s0".to_string(), vec![leaves[1], nodes[1]]);
+    }
+
+    #[test]
+    fn test_remove_resolver() {
+        let (mut context, mut contract) = setup_with_resolver();
+        assert!(contract.is_resolver(accounts(2)));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.remove_resolver(accounts(2));
+        assert!(!contract.is_resolver(accounts(2)));
+        assert!(contract.is_relayer(&accounts(2)));
+    }
+
+    #[test]
+    fn test_remaining_gas() {
+        let (mut context, _) = setup();
+        testing_env!(context.prepaid_gas(Gas::from_tgas(100)).build());
+        let remaining = remaining_gas(GAS_RESERVED_FOR_CURRENT_CALL, GAS_FOR_TRANSFER_FROM);
+        assert!(remaining <= Gas::from_tgas(80) && remaining >= GAS_FOR_TRANSFER_FROM);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough gas attached")]
+    fn test_complete_swap_with_low_gas() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.prepaid_gas(Gas::from_tgas(20)).build());
+        contract.complete_swap(
+            "mainnet:1".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            accounts(3),
+            U128(100),
+            "secret".to_string(),
+        );
+    }
+
+    fn order_hash_params(hash_algorithm: HashAlgorithm) -> OrderHashParams {
+        OrderHashParams {
+            secret_hash: HashAlgorithm::Keccak256.hash(b"hello"),
+            maker: "maker.near".to_string(),
+            taker: "taker.near".to_string(),
+            amount: U128(1_000),
+            target_chain: "mainnet:1".to_string(),
+            salt: 42,
+            hash_algorithm: Some(hash_algorithm),
+        }
+    }
+
+    #[test]
+    fn test_compute_order_hash_vectors() {
+        let (_, contract) = setup();
+        // keccak256(abi.encode(keccak256("hello"), keccak256("maker.near"),
+        // keccak256("taker.near"), 1000, keccak256("mainnet:1"), 42))
+        assert_eq!(
+            hex::encode(contract.compute_order_hash(order_hash_params(HashAlgorithm::Keccak256))),
+            "87e4f435f35ed5db9cc38fe02076662304052bff82e67eee80ece4fea219d341"
+        );
+        assert_eq!(
+            hex::encode(contract.compute_order_hash(order_hash_params(HashAlgorithm::Sha256))),
+            "383194351ca402c1f037565b51d2a2aa7ac5be71a5530c9eb1d9813143ed3ed7"
+        );
+    }
+
+    #[test]
+    fn test_lock_is_keyed_by_order_hash() {
+        let (mut context, mut contract) = setup();
+        let msg = near_sdk::serde_json::json!({
+            "secret_hash": HashAlgorithm::Keccak256.hash(b"hello"),
+            "recipient": accounts(4),
+            "timeout_hours": 24,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+            "hash_algorithm": "Keccak256",
+            "salt": 42,
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(1_000), msg.to_string());
+
+        let params = OrderHashParams {
+            maker: accounts(3).to_string(),
+            taker: accounts(4).to_string(),
+            ..order_hash_params(HashAlgorithm::Keccak256)
+        };
+        let lock_contract_id = contract.compute_order_hash(params);
+        assert_eq!(contract.get_lock_contract(lock_contract_id).unwrap().sender, accounts(3));
+    }
+
+    /// Promise result of a `transfer_from` crediting `amount` to the HTLC
+    fn transfer_result(amount: u128) -> PromiseResult {
+        PromiseResult::Successful(
+            serde_json::to_vec(&serde_json::json!({
+                "from": accounts(3),
+                "to": accounts(0),
+                "amount": U128(amount),
+                "new_sender_balance": U128(0),
+                "new_receiver_balance": U128(amount),
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn batch_swap(recipient: AccountId, amount: u128) -> InitiateSwapArgs {
+        InitiateSwapArgs {
+            secret_hash: HashAlgorithm::Sha256.hash(b"secret"),
+            recipient,
+            amount: U128(amount),
+            timeout_hours: 24,
+            target_chain: "mainnet:1".to_string(),
+            target_address: "0x0000000000000000000000000000000000000001".to_string(),
+            hash_algorithm: None,
+            relayer: None,
+            salt: None,
+        }
+    }
+
+    #[test]
+    fn test_initiate_swap_batch() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let swaps = vec![
+            batch_swap(accounts(4), 100),
+            batch_swap(accounts(2), 200),
+            InitiateSwapArgs {
+                target_chain: "testnet:11155111".to_string(),
+                ..batch_swap(accounts(4), 300)
+            },
+        ];
+        let lock_contract_ids = contract.initiate_swap_batch(swaps, Some(accounts(5)));
+        assert_eq!(lock_contract_ids.len(), 3);
+        for (lock_contract_id, amount) in lock_contract_ids.iter().zip([100, 200, 300]) {
+            let lock_contract = contract.get_lock_contract(*lock_contract_id).unwrap();
+            assert_eq!(lock_contract.sender, accounts(3));
+            assert_eq!(lock_contract.amount, U128(amount));
+        }
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(600));
+
+        // The total is pulled once
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        match &receipts[0].actions[0] {
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. } => {
+                assert_eq!(method_name, b"transfer_from");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["amount"], "600");
+            }
+            _ => panic!("Expected the batch pull"),
+        }
+
+        // A pull crediting the total funds the batch
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![transfer_result(600)],
+        );
+        assert!(contract.on_swap_batch_funded(lock_contract_ids.clone()));
+        assert!(lock_contract_ids.iter().all(|id| contract.has_lock_contract(*id)));
+
+        // A failed pull drops the whole batch
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        assert!(!contract.on_swap_batch_funded(lock_contract_ids.clone()));
+        assert!(lock_contract_ids.iter().all(|id| !contract.has_lock_contract(*id)));
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(0));
+    }
+
+    #[test]
+    fn test_initiate_swap_batch_short_funding() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let swaps = vec![batch_swap(accounts(4), 100), batch_swap(accounts(2), 200)];
+        let lock_contract_ids = contract.initiate_swap_batch(swaps, Some(accounts(5)));
+
+        // The token kept a transfer fee out of the 300 pulled
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![transfer_result(297)],
+        );
+        assert!(!contract.on_swap_batch_funded(lock_contract_ids.clone()));
+        assert!(lock_contract_ids.iter().all(|id| !contract.has_lock_contract(*id)));
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(0));
+        // What arrived goes back to the sender
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, accounts(5));
+        match &receipts[0].actions[0] {
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. } => {
+                assert_eq!(method_name, b"ft_transfer");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["receiver_id"], accounts(3).to_string());
+                assert_eq!(args["amount"], "297");
+            }
+            _ => panic!("Expected the funding to be returned"),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported target chain")]
+    fn test_initiate_swap_batch_with_invalid_entry() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let swaps = vec![
+            batch_swap(accounts(4), 100),
+            InitiateSwapArgs {
+                target_chain: "mainnet:56".to_string(),
+                ..batch_swap(accounts(4), 200)
+            },
+        ];
+        contract.initiate_swap_batch(swaps, Some(accounts(5)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate swap in batch")]
+    fn test_initiate_swap_batch_with_duplicate() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let swaps = vec![batch_swap(accounts(4), 100), batch_swap(accounts(4), 100)];
+        contract.initiate_swap_batch(swaps, Some(accounts(5)));
+    }
+
+    /// Completes a swap as `accounts(2)` with a 1000 yoctoNEAR safety deposit, with
+    /// `arbiter.near` as arbiter. Returns the completed swap ID
+    fn complete_with_safety_deposit(
+        context: &mut VMContextBuilder,
+        contract: &mut UnrealHTLC,
+    ) -> CryptoHash {
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_safety_deposit_amount(U128(1_000));
+        contract.set_arbiter(Some("arbiter.near".parse().unwrap()));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(NearToken::from_yoctonear(1_000)).build());
+        contract.complete_swap(
+            "mainnet:1".to_string(),
+            "0x0000000000000000000000000000000000000001".to_string(),
+            accounts(3),
+            U128(100),
+            "secret".to_string(),
+        );
+        env::sha256(
+            &[
+                "mainnet:1".as_bytes(),
+                "0x0000000000000000000000000000000000000001".as_bytes(),
+                accounts(3).as_bytes(),
+                &100u128.to_le_bytes(),
+                "secret".as_bytes(),
+            ]
+            .concat(),
+        )
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_arbiter_slashes_relayer() {
+        let (mut context, mut contract) = setup();
+        let completed_id = complete_with_safety_deposit(&mut context, &mut contract);
+
+        testing_env!(context
+            .predecessor_account_id("arbiter.near".parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(0))
+            .build());
+        contract.slash_relayer(completed_id, "Secret never revealed on the source chain".into());
+        assert_eq!(
+            contract.get_adjudication(completed_id),
+            Some(Adjudication::Slashed {
+                reason: "Secret never revealed on the source chain".to_string()
+            })
+        );
+        // The deposit goes to the recovery address, the owner by default
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts.last().unwrap().receiver_id, accounts(1));
+    }
+
+    #[test]
+    fn test_arbiter_vindicates_relayer() {
+        let (mut context, mut contract) = setup();
+        let completed_id = complete_with_safety_deposit(&mut context, &mut contract);
+
+        testing_env!(context
+            .predecessor_account_id("arbiter.near".parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(0))
+            .build());
+        contract.vindicate_relayer(completed_id);
+        assert_eq!(contract.get_adjudication(completed_id), Some(Adjudication::Vindicated));
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts.last().unwrap().receiver_id, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Not the arbiter")]
+    fn test_owner_cant_slash_relayer() {
+        let (mut context, mut contract) = setup();
+        let completed_id = complete_with_safety_deposit(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(NearToken::from_yoctonear(0)).build());
+        contract.slash_relayer(completed_id, "Invalid completion".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "The arbiter can't be the owner")]
+    fn test_owner_cant_be_arbiter() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_arbiter(Some(accounts(1)));
+    }
+
+    #[test]
+    fn test_get_relayers() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.add_relayer(accounts(3), ChainId::ethereum_mainnet());
+        contract.add_relayer(accounts(3), ChainId::ethereum_sepolia());
+        contract.add_relayer(accounts(4), ChainId::ethereum_mainnet());
+        assert_eq!(contract.relayer_count(), 3);
+        assert_eq!(contract.get_relayers(None, None), vec![accounts(2), accounts(3), accounts(4)]);
+        assert_eq!(contract.get_relayers(Some(1), Some(1)), vec![accounts(3)]);
+
+        // Listed until the last chain is removed
+        contract.remove_relayer(accounts(3), ChainId::ethereum_mainnet());
+        assert_eq!(contract.relayer_count(), 3);
+        contract.remove_relayer(accounts(3), ChainId::ethereum_sepolia());
+        contract.remove_relayer_for_all_chains(accounts(2));
+        assert_eq!(contract.get_relayers(None, None), vec![accounts(4)]);
+    }
+
+    #[test]
+    fn test_refund_to_other_account() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(25 * 3600 * 1_000_000_000)
+            .build());
+        assert!(contract.refund_to(lock_contract_id, accounts(2)));
+
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        match &receipts[0].actions[0] {
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. } => {
+                assert_eq!(method_name, b"ft_transfer");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["receiver_id"], accounts(2).to_string());
+                assert_eq!(args["amount"], "100");
+            }
+            _ => panic!("Expected the refund transfer"),
+        }
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains(&format!("\"refunded_to\":\"{}\"", accounts(2)))));
+    }
+
+    #[test]
+    #[should_panic(expected = "Not the sender")]
+    fn test_refund_to_not_sender() {
+        let (mut context, mut contract) = setup();
+        let lock_contract_id = lock(&mut context, &mut contract, "secret");
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(25 * 3600 * 1_000_000_000)
+            .build());
+        contract.refund_to(lock_contract_id, accounts(4));
+    }
+
+    #[test]
+    fn test_protocol_fee_on_deposit() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_protocol_fee(50, accounts(2));
+
+        let msg = near_sdk::serde_json::json!({
+            "secret_hash": HashAlgorithm::Sha256.hash(b"secret"),
+            "recipient": accounts(4),
+            "timeout_hours": 24,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        assert!(matches!(
+            contract.ft_on_transfer(accounts(3), U128(10_000), msg.to_string()),
+            PromiseOrValue::Promise(_)
+        ));
+
+        // 0.5% goes to the treasury and the rest is locked
+        let lock_contract = contract.get_lock_contracts_by_sender(accounts(3), None, None).remove(0);
+        assert_eq!(lock_contract.amount, U128(9_950));
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(9_950));
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, accounts(5));
+        match &receipts[0].actions[0] {
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. } => {
+                assert_eq!(method_name, b"ft_transfer");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["receiver_id"], accounts(2).to_string());
+                assert_eq!(args["amount"], "50");
+            }
+            _ => panic!("Expected the protocol fee transfer"),
+        }
+        let lock_contract_id: CryptoHash =
+            hex::decode(lock_contract.lock_contract_id).unwrap().try_into().unwrap();
+        match &receipts[1].actions[0] {
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, .. } => {
+                assert_eq!(method_name, b"on_protocol_fee_transfer");
+            }
+            _ => panic!("Expected the protocol fee callback"),
+        }
+
+        // A failed fee transfer hands the fee back to the token to refund
+        let results = [(PromiseResult::Successful(Vec::new()), 0), (PromiseResult::Failed, 50)];
+        for (result, unused) in results {
+            testing_env!(
+                context.predecessor_account_id(accounts(0)).build(),
+                near_sdk::test_vm_config(),
+                near_sdk::RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![result],
+            );
+            let refund = contract.on_protocol_fee_transfer(lock_contract_id, U128(50));
+            assert_eq!(refund, U128(unused));
+        }
+        assert_eq!(contract.total_locked(Some(accounts(5))), U128(9_950));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported token")]
+    fn test_lock_unsupported_token() {
+        let (mut context, mut contract) = setup();
+        assert!(contract.is_supported_token(contract.get_default_token()));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.remove_supported_token(accounts(5));
+        assert!(contract.supported_tokens().is_empty());
+        lock(&mut context, &mut contract, "secret");
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount below the minimum swap amount")]
+    fn test_lock_below_min_swap_amount() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_min_swap_amount(U128(101));
+        assert_eq!(contract.min_swap_amount(), U128(101));
+        lock(&mut context, &mut contract, "secret");
+    }
+
+    #[test]
+    fn test_partial_fill_dust_swept_to_treasury() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_min_swap_amount(U128(30));
+        contract.add_resolver(accounts(4));
+        let (leaves, nodes, root) = merkle_tree();
+        let msg = near_sdk::serde_json::json!({
+            "secret_merkle_root": root,
+            "parts_count": 4,
+            "recipient": accounts(4),
+            "timeout_hours": 24,
+            "target_chain": "mainnet:1",
+            "target_address": "0x0000000000000000000000000000000000000001",
+        });
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.ft_on_transfer(accounts(3), U128(100), msg.to_string());
+        let lock_contract = contract.get_lock_contracts_by_sender(accounts(3), None, None).remove(0);
+        let lock_contract_id: CryptoHash =
+            hex::decode(lock_contract.lock_contract_id).unwrap().try_into().unwrap();
+
+        let proofs = [
+            vec![leaves[1], nodes[1]],
+            vec![leaves[0], nodes[1]],
+            vec![leaves[3], nodes[0]],
+        ];
+        for (part_index, proof) in (0..).zip(proofs) {
+            testing_env!(context.predecessor_account_id(accounts(4)).build());
+            let preimage = format!("s{}", part_index);
+            contract.withdraw_part(lock_contract_id, part_index, preimage, proof);
+            resolve_payout(&mut context, &mut contract, lock_contract_id);
+        }
+
+        // The last 25 tokens are below the minimum, so they go to the treasury
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(24 * 3600 * 1_000_000_000)
+            .build());
+        assert!(contract.refund(lock_contract_id));
+        let receipts = near_sdk::test_utils::get_created_receipts();
+        match &receipts[0].actions[0] {
+            near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. } => {
+                assert_eq!(method_name, b"ft_transfer");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["receiver_id"], accounts(1).to_string());
+                assert_eq!(args["amount"], "25");
+            }
+            _ => panic!("Expected the dust transfer"),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Protocol fee cannot exceed 1%")]
+    fn test_protocol_fee_above_ceiling() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.set_protocol_fee(101, accounts(2));
+    }
+
+    #[test]
+    fn test_get_completed_swaps() {
+        let (context, mut contract) = setup();
+        for (amount, preimage) in [(100, "first"), (200, "second")] {
+            testing_env!(context.build());
+            contract.complete_swap(
+                "mainnet:1".to_string(),
+                "0x0000000000000000000000000000000000000001".to_string(),
+                accounts(3),
+                U128(amount),
+                preimage.to_string(),
+            );
+        }
+        assert_eq!(contract.completed_swap_count(), 2);
+
+        let completed_swaps = contract.get_completed_swaps(None, None);
+        assert_eq!(completed_swaps.len(), 2);
+        assert_eq!(completed_swaps[1].amount, U128(200));
+        assert_eq!(completed_swaps[1].preimage, "second");
+        assert_eq!(completed_swaps[1].relayer, accounts(2));
+        assert_eq!(completed_swaps[1].source_chain, "mainnet:1");
+        let page = contract.get_completed_swaps(Some(1), Some(10));
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].completed_id, completed_swaps[1].completed_id);
+    }
+}
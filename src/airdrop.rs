@@ -0,0 +1,149 @@
+//! Claim-based airdrop against a Merkle root of `(account, amount)` allocations.
+//!
+//! The owner funds the pool from its own balance into the contract's account and
+//! publishes the root; each eligible account then claims its allocation with a
+//! proof. A leaf is `sha256("{account_id}:{amount}")` and every pair of nodes is
+//! hashed in sorted order, so proofs don't need to say which side a sibling is on.
+
+use near_sdk::json_types::U128;
+use near_sdk::{env, log, near_bindgen, AccountId, CryptoHash};
+
+use crate::events::FtTransfer;
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the Merkle root of the active airdrop
+    pub fn airdrop_root(&self) -> Option<CryptoHash> {
+        self.airdrop_root
+    }
+
+    /// Returns the amount of the active airdrop that hasn't been claimed yet
+    pub fn airdrop_remaining(&self) -> U128 {
+        U128(self.airdrop_remaining)
+    }
+
+    /// Returns the timestamp (in nanoseconds) after which the active airdrop can't be claimed
+    pub fn airdrop_deadline(&self) -> u64 {
+        self.airdrop_deadline
+    }
+
+    /// Returns true if `account_id` already claimed from the active airdrop
+    pub fn is_airdrop_claimed(&self, account_id: AccountId) -> bool {
+        self.airdrop_root.is_some_and(|root| {
+            self.airdrop_claimed
+                .get(&(root, account_id))
+                .unwrap_or(false)
+        })
+    }
+
+    /// Start an airdrop of `total_allocated` tokens claimable until `deadline` (in nanoseconds)
+    /// against `merkle_root` - only callable by owner.
+    /// The tokens are moved from the owner to the contract account right away
+    pub fn set_airdrop_root(
+        &mut self,
+        merkle_root: CryptoHash,
+        total_allocated: U128,
+        deadline: u64,
+    ) {
+        self.assert_owner();
+        assert!(self.airdrop_root.is_none(), "An airdrop is already active");
+        let amount: Balance = total_allocated.into();
+        assert!(amount > 0, "The amount should be a positive number");
+        assert!(deadline > env::block_timestamp(), "Deadline must be in the future");
+
+        let owner_id = self.owner_id.clone();
+        let treasury_id = env::current_account_id();
+        if !self.internal_is_registered(&treasury_id) {
+            self.internal_set_balance(&treasury_id, 0);
+        }
+        self.internal_withdraw(&owner_id, amount);
+        self.internal_deposit(&treasury_id, amount);
+        FtTransfer {
+            old_owner_id: &owner_id,
+            new_owner_id: &treasury_id,
+            amount: U128(amount),
+            memo: Some("airdrop"),
+        }
+        .emit();
+
+        self.airdrop_root = Some(merkle_root);
+        self.airdrop_remaining = amount;
+        self.airdrop_deadline = deadline;
+        log!("Started airdrop {} of {} tokens", hex::encode(merkle_root), amount);
+    }
+
+    /// Claim the caller's `amount` from the active airdrop, proving it with `proof`.
+    /// Subject to the transfer pause and the blacklist like any other transfer
+    pub fn claim_airdrop(&mut self, amount: U128, proof: Vec<CryptoHash>) -> U128 {
+        self.assert_transfers_not_paused();
+        let root = self.airdrop_root.unwrap_or_else(|| env::panic_str("No active airdrop"));
+        assert!(env::block_timestamp() <= self.airdrop_deadline, "Airdrop has ended");
+        let account_id = env::predecessor_account_id();
+        self.assert_not_blacklisted(&account_id);
+        let claim_key = (root, account_id.clone());
+        assert!(self.airdrop_claimed.get(&claim_key).is_none(), "Airdrop already claimed");
+        let leaf = airdrop_leaf(&account_id, amount.0);
+        assert!(verify_proof(leaf, &proof, root), "Invalid Merkle proof");
+        assert!(amount.0 <= self.airdrop_remaining, "Airdrop pool exhausted");
+
+        self.airdrop_claimed.insert(&claim_key, &true);
+        self.airdrop_remaining -= amount.0;
+        let treasury_id = env::current_account_id();
+        self.internal_withdraw(&treasury_id, amount.0);
+        self.internal_deposit(&account_id, amount.0);
+        FtTransfer {
+            old_owner_id: &treasury_id,
+            new_owner_id: &account_id,
+            amount,
+            memo: Some("airdrop claim"),
+        }
+        .emit();
+        log!("{} claimed {} airdropped tokens", account_id, amount.0);
+
+        amount
+    }
+
+    /// Return what's left of the airdrop to the owner once the deadline has passed,
+    /// ending the airdrop - only callable by owner. Returns the reclaimed amount
+    pub fn reclaim_unclaimed_airdrop(&mut self) -> U128 {
+        self.assert_owner();
+        assert!(self.airdrop_root.is_some(), "No active airdrop");
+        assert!(env::block_timestamp() > self.airdrop_deadline, "Airdrop is still open");
+
+        let amount = self.airdrop_remaining;
+        self.airdrop_root = None;
+        self.airdrop_remaining = 0;
+        if amount > 0 {
+            let owner_id = self.owner_id.clone();
+            let treasury_id = env::current_account_id();
+            self.internal_withdraw(&treasury_id, amount);
+            self.internal_deposit(&owner_id, amount);
+            FtTransfer {
+                old_owner_id: &treasury_id,
+                new_owner_id: &owner_id,
+                amount: U128(amount),
+                memo: Some("airdrop reclaim"),
+            }
+            .emit();
+        }
+        log!("Reclaimed {} unclaimed airdrop tokens", amount);
+
+        U128(amount)
+    }
+}
+
+/// Leaf committing to `account_id`'s allocation of `amount`
+pub(crate) fn airdrop_leaf(account_id: &AccountId, amount: Balance) -> CryptoHash {
+    env::sha256_array(format!("{}:{}", account_id, amount).as_bytes())
+}
+
+/// Hashes two nodes in sorted order
+pub(crate) fn hash_pair(a: CryptoHash, b: CryptoHash) -> CryptoHash {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    env::sha256_array(&[left, right].concat())
+}
+
+fn verify_proof(leaf: CryptoHash, proof: &[CryptoHash], root: CryptoHash) -> bool {
+    proof.iter().fold(leaf, |node, sibling| hash_pair(node, *sibling)) == root
+}
@@ -0,0 +1,242 @@
+//! Historical balance checkpoints used for governance voting power.
+//!
+//! Every balance and total supply change records a `(block_timestamp, value)`
+//! checkpoint, overwriting the one left by an earlier change within the same
+//! block. Values at a past timestamp are looked up with a binary search, and
+//! only timestamps before the current block can be looked up, so they can't be
+//! inflated by tokens borrowed afterwards. Balances are recorded in shares
+//! alongside the scaling factor, see `rebase`.
+//!
+//! The balance and vote checkpoints of an account are each kept in a ring of
+//! `MAX_ACCOUNT_CHECKPOINTS`, which registration pays for. Once the ring is full, a new checkpoint overwrites
+//! the oldest one, and the account's history no longer reaches back before the
+//! oldest one left. Unregistering drops the account's checkpoints along with the
+//! storage deposit paid for them.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::collections::Vector;
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::rebase::{is_rebased, SCALING_PRECISION};
+use crate::rewards::mul_div;
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+/// Most checkpoints kept for each account's balance and votes, the oldest being
+/// overwritten first
+pub(crate) const MAX_ACCOUNT_CHECKPOINTS: u64 = 16;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Checkpoint {
+    pub timestamp: u64,
+    pub value: Balance,
+}
+
+/// Checkpoints of a single account, in a ring of at most `MAX_ACCOUNT_CHECKPOINTS`
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountCheckpoints {
+    entries: Vector<Checkpoint>,
+    /// Index in `entries` of the oldest checkpoint, only moving once the ring is full
+    start: u64,
+    /// Whether an older checkpoint was overwritten, losing the history before `start`
+    truncated: bool,
+}
+
+impl AccountCheckpoints {
+    pub(crate) fn new(prefix: &[u8], account_id: &AccountId) -> Self {
+        let prefix = [prefix, &env::sha256_array(account_id.as_bytes())].concat();
+        Self { entries: Vector::new(prefix), start: 0, truncated: false }
+    }
+
+    /// Checkpoint `index` counting from the oldest one
+    fn get(&self, index: u64) -> Checkpoint {
+        self.entries.get((self.start + index) % self.entries.len()).unwrap()
+    }
+
+    /// Value of the latest checkpoint, zero if there is none
+    pub(crate) fn latest(&self) -> Balance {
+        match self.entries.len() {
+            0 => 0,
+            len => self.get(len - 1).value,
+        }
+    }
+
+    /// Records `value` at `timestamp`, which can't be before the latest checkpoint.
+    /// Several changes within the same block overwrite a single checkpoint, and
+    /// unchanged values aren't recorded at all
+    pub(crate) fn write(&mut self, timestamp: u64, value: Balance) {
+        let len = self.entries.len();
+        if len > 0 {
+            let last = self.get(len - 1);
+            if last.timestamp == timestamp {
+                let last_index = (self.start + len - 1) % len;
+                self.entries.replace(last_index, &Checkpoint { timestamp, value });
+                return;
+            }
+            if last.value == value {
+                return;
+            }
+        }
+        if len < MAX_ACCOUNT_CHECKPOINTS {
+            self.entries.push(&Checkpoint { timestamp, value });
+        } else {
+            self.entries.replace(self.start, &Checkpoint { timestamp, value });
+            self.start = (self.start + 1) % len;
+            self.truncated = true;
+        }
+    }
+
+    /// Value of the latest checkpoint at or before `timestamp`, zero if there is none.
+    /// `None` if the checkpoints that would tell were overwritten
+    pub(crate) fn value_at(&self, timestamp: u64) -> Option<Balance> {
+        match checkpoints_until(self.entries.len(), timestamp, |index| self.get(index).timestamp) {
+            0 if self.truncated => None,
+            0 => Some(0),
+            count => Some(self.get(count - 1).value),
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.start = 0;
+        self.truncated = false;
+    }
+}
+
+/// Number of the `len` checkpoints recorded at or before `timestamp`, given the timestamp
+/// of each in order
+fn checkpoints_until(len: u64, timestamp: u64, timestamp_of: impl Fn(u64) -> u64) -> u64 {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if timestamp_of(mid) <= timestamp {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// Records `value` in the contract-wide `checkpoints` at the current block, overwriting
+/// a checkpoint of the same block. Unchanged values aren't recorded at all
+pub(crate) fn write_checkpoint(checkpoints: &mut Vector<Checkpoint>, value: Balance) {
+    let timestamp = env::block_timestamp();
+    match checkpoints.len().checked_sub(1) {
+        Some(last_index) => {
+            let last = checkpoints.get(last_index).unwrap();
+            if last.timestamp == timestamp {
+                checkpoints.replace(last_index, &Checkpoint { timestamp, value });
+            } else if last.value != value {
+                checkpoints.push(&Checkpoint { timestamp, value });
+            }
+        }
+        None => checkpoints.push(&Checkpoint { timestamp, value }),
+    }
+}
+
+/// Value of the latest of the contract-wide `checkpoints` at or before `timestamp`,
+/// zero if there is none
+pub(crate) fn checkpoint_value_at(checkpoints: &Vector<Checkpoint>, timestamp: u64) -> Balance {
+    match checkpoints_until(checkpoints.len(), timestamp, |index| {
+        checkpoints.get(index).unwrap().timestamp
+    }) {
+        0 => 0,
+        count => checkpoints.get(count - 1).unwrap().value,
+    }
+}
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the balance `account_id` had at `timestamp` (in nanoseconds), which must be
+    /// before the current block
+    pub fn balance_of_at(&self, account_id: AccountId, timestamp: u64) -> U128 {
+        self.assert_past_timestamp(timestamp);
+        match self.internal_balance_at(&account_id, timestamp) {
+            Some(balance) => U128(balance),
+            None => env::panic_str(&format!(
+                "The checkpoints of {} don't reach back to {}",
+                account_id, timestamp
+            )),
+        }
+    }
+
+    /// Returns the total supply at `timestamp` (in nanoseconds), e.g. to compute a quorum.
+    /// The timestamp must be before the current block
+    pub fn total_supply_at(&self, timestamp: u64) -> U128 {
+        self.assert_past_timestamp(timestamp);
+        U128(checkpoint_value_at(&self.total_supply_checkpoints, timestamp))
+    }
+
+    /// Asserts `timestamp` is before the current block, so the values at it can't change anymore
+    pub(crate) fn assert_past_timestamp(&self, timestamp: u64) {
+        assert!(
+            timestamp < env::block_timestamp(),
+            "Timestamp {} is not in the past",
+            timestamp
+        );
+    }
+
+    /// Balance of `account_id` at a past timestamp, `None` if its checkpoints don't reach
+    /// back that far. An account without checkpoints hasn't changed since before they
+    /// were introduced, so its current shares are also its shares at the timestamp
+    pub(crate) fn internal_balance_at(
+        &self,
+        account_id: &AccountId,
+        timestamp: u64,
+    ) -> Option<Balance> {
+        let shares = match self.balance_checkpoints.get(account_id) {
+            Some(checkpoints) => checkpoints.value_at(timestamp)?,
+            None => self.internal_shares_of(account_id),
+        };
+        if is_rebased(account_id) {
+            Some(mul_div(shares, self.internal_scaling_factor_at(timestamp), SCALING_PRECISION))
+        } else {
+            Some(shares)
+        }
+    }
+
+    /// Scaling factor at a past timestamp, the initial one if nothing was rebased by then
+    pub(crate) fn internal_scaling_factor_at(&self, timestamp: u64) -> Balance {
+        match checkpoint_value_at(&self.scaling_factor_checkpoints, timestamp) {
+            0 => SCALING_PRECISION,
+            scaling_factor => scaling_factor,
+        }
+    }
+
+    /// Records the new shares of `account_id`, which held `old_shares` before this change
+    pub(crate) fn internal_checkpoint_balance(
+        &mut self,
+        account_id: &AccountId,
+        old_shares: Balance,
+        shares: Balance,
+    ) {
+        let mut checkpoints = self.balance_checkpoints.get(account_id).unwrap_or_else(|| {
+            let mut checkpoints = AccountCheckpoints::new(b"K", account_id);
+            // A first change of older shares keeps them for every earlier timestamp
+            if old_shares > 0 {
+                checkpoints.write(0, old_shares);
+            }
+            checkpoints
+        });
+        checkpoints.write(env::block_timestamp(), shares);
+        self.balance_checkpoints.insert(account_id, &checkpoints);
+    }
+
+    /// Drops the balance checkpoints of an account being unregistered
+    pub(crate) fn internal_clear_balance_checkpoints(&mut self, account_id: &AccountId) {
+        if let Some(mut checkpoints) = self.balance_checkpoints.remove(account_id) {
+            checkpoints.clear();
+        }
+    }
+
+    /// Records the current total supply
+    pub(crate) fn internal_checkpoint_total_supply(&mut self) {
+        write_checkpoint(&mut self.total_supply_checkpoints, self.total_supply);
+    }
+
+    /// Records the current scaling factor
+    pub(crate) fn internal_checkpoint_scaling_factor(&mut self) {
+        write_checkpoint(&mut self.scaling_factor_checkpoints, self.scaling_factor);
+    }
+}
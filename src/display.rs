@@ -0,0 +1,122 @@
+//! Conversion between raw amounts and their human-readable decimal form.
+//!
+//! `1500000000000000000` with 18 decimals displays as `1.5`. Both directions
+//! work on integers only, so every `u128` amount converts exactly.
+
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen};
+
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Formats a raw amount with the token's decimals, e.g. `"1.5"`
+    pub fn to_display_amount(&self, raw: U128) -> String {
+        format_amount(raw.0, self.decimals)
+    }
+
+    /// Parses a decimal amount like `"1.5"` into a raw amount. Panics on malformed input,
+    /// on more fractional digits than the token's decimals and on overflow
+    pub fn from_display_amount(&self, display: String) -> U128 {
+        U128(parse_amount(&display, self.decimals))
+    }
+}
+
+/// `raw` with `decimals` fractional digits, trailing zeros trimmed
+fn format_amount(raw: Balance, decimals: u8) -> String {
+    let decimals = usize::from(decimals);
+    let digits = format!("{:0>width$}", raw, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// Inverse of `format_amount`, which also accepts trailing zeros
+fn parse_amount(display: &str, decimals: u8) -> Balance {
+    let (whole, fraction) = match display.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (display, None),
+    };
+    let is_number = |part: &str| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit());
+    assert!(is_number(whole) && fraction.into_iter().all(is_number), "Invalid amount");
+    let fraction = fraction.unwrap_or_default();
+    assert!(
+        fraction.len() <= usize::from(decimals),
+        "Too many fractional digits, at most {} allowed",
+        decimals
+    );
+
+    let overflow = || env::panic_str("Amount overflow");
+    let scale = |part: &str, exponent: usize| -> Balance {
+        let value: Balance = if part.is_empty() {
+            0
+        } else {
+            part.parse().unwrap_or_else(|_| overflow())
+        };
+        10u128
+            .checked_pow(exponent as u32)
+            .and_then(|factor| value.checked_mul(factor))
+            .unwrap_or_else(overflow)
+    };
+    scale(whole, usize::from(decimals))
+        .checked_add(scale(fraction, usize::from(decimals) - fraction.len()))
+        .unwrap_or_else(overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_decimals() {
+        assert_eq!(format_amount(0, 0), "0");
+        assert_eq!(format_amount(1_500, 0), "1500");
+        assert_eq!(parse_amount("1500", 0), 1_500);
+        assert_eq!(format_amount(u128::MAX, 0), u128::MAX.to_string());
+        assert_eq!(parse_amount(&u128::MAX.to_string(), 0), u128::MAX);
+    }
+
+    #[test]
+    fn test_24_decimals() {
+        let one_near = 10u128.pow(24);
+        assert_eq!(format_amount(one_near, 24), "1");
+        assert_eq!(format_amount(one_near / 2 * 3, 24), "1.5");
+        assert_eq!(format_amount(1, 24), "0.000000000000000000000001");
+        assert_eq!(parse_amount("1.5", 24), one_near / 2 * 3);
+        assert_eq!(parse_amount("0.000000000000000000000001", 24), 1);
+        assert_eq!(parse_amount("1.500", 24), one_near / 2 * 3);
+        let max = format_amount(u128::MAX, 24);
+        assert_eq!(max, "340282366920938.463463374607431768211455");
+        assert_eq!(parse_amount(&max, 24), u128::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many fractional digits, at most 2 allowed")]
+    fn test_parse_rejects_rounding() {
+        parse_amount("1.005", 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many fractional digits, at most 0 allowed")]
+    fn test_parse_rejects_fraction_without_decimals() {
+        parse_amount("1.5", 0);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_input() {
+        for display in ["", ".5", "1.", "1.2.3", "-1", "+1", "1e18", " 1", "1,5"] {
+            let result = std::panic::catch_unwind(|| parse_amount(display, 18));
+            assert!(result.is_err(), "{:?} should be rejected", display);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount overflow")]
+    fn test_parse_rejects_overflow() {
+        parse_amount("340282366920938.463463374607431768211456", 24);
+    }
+}
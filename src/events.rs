@@ -0,0 +1,255 @@
+//! NEP-297 structured events.
+//!
+//! Every event is logged as a single `EVENT_JSON:{...}` line so that indexers and
+//! explorers can parse it without relying on the human-readable logs.
+
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::{log, serde_json, AccountId};
+
+use crate::timelock::TimelockedAction;
+
+/// Standard name and version for the NEP-141 events
+const NEP141_STANDARD: &str = "nep141";
+const NEP141_VERSION: &str = "1.0.0";
+/// Standard name and version for the events specific to this token
+const UNREAL_STANDARD: &str = "unreal_token";
+const UNREAL_VERSION: &str = "1.0.0";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a, T: Serialize> {
+    standard: &'a str,
+    version: &'a str,
+    #[serde(flatten)]
+    event: T,
+}
+
+/// Logs `event` wrapped in the NEP-297 envelope
+fn emit_event<T: Serialize>(standard: &str, version: &str, event: T) {
+    let event = EventLog { standard, version, event };
+    log!("EVENT_JSON:{}", serde_json::to_string(&event).expect("Failed to serialize event"));
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+// The variant names become the NEP-297 event names, which the standard prefixes with `ft_`
+#[allow(clippy::enum_variant_names)]
+enum Nep141Event<'a> {
+    FtMint(&'a [FtMint<'a>]),
+    FtBurn(&'a [FtBurn<'a>]),
+    FtTransfer(&'a [FtTransfer<'a>]),
+}
+
+impl Nep141Event<'_> {
+    fn emit(self) {
+        emit_event(NEP141_STANDARD, NEP141_VERSION, self);
+    }
+}
+
+/// Tokens were minted to `owner_id`
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtMint<'a> {
+    pub owner_id: &'a AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+impl FtMint<'_> {
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    pub fn emit_many(data: &[FtMint<'_>]) {
+        Nep141Event::FtMint(data).emit()
+    }
+}
+
+/// Tokens were burned from `owner_id`
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtBurn<'a> {
+    pub owner_id: &'a AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+impl FtBurn<'_> {
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    pub fn emit_many(data: &[FtBurn<'_>]) {
+        Nep141Event::FtBurn(data).emit()
+    }
+}
+
+/// Tokens moved from `old_owner_id` to `new_owner_id`
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtTransfer<'a> {
+    pub old_owner_id: &'a AccountId,
+    pub new_owner_id: &'a AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+impl FtTransfer<'_> {
+    pub fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    pub fn emit_many(data: &[FtTransfer<'_>]) {
+        Nep141Event::FtTransfer(data).emit()
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+enum UnrealEvent<'a> {
+    BlacklistUpdate(&'a [BlacklistUpdate<'a>]),
+    MetadataUpdate(&'a [MetadataUpdate<'a>]),
+    TimelockQueued(&'a [TimelockQueued<'a>]),
+    TimelockExecuted(&'a [TimelockExecuted<'a>]),
+    TimelockCancelled(&'a [TimelockCancelled<'a>]),
+    Rebase(&'a [Rebase]),
+    Pause(&'a [Pause<'a>]),
+    Unpause(&'a [Unpause<'a>]),
+}
+
+impl UnrealEvent<'_> {
+    fn emit(self) {
+        emit_event(UNREAL_STANDARD, UNREAL_VERSION, self);
+    }
+}
+
+/// An account was added to or removed from the blacklist
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BlacklistUpdate<'a> {
+    pub account_id: &'a AccountId,
+    pub blacklisted: bool,
+}
+
+impl BlacklistUpdate<'_> {
+    pub fn emit(self) {
+        UnrealEvent::BlacklistUpdate(&[self]).emit()
+    }
+}
+
+/// Token metadata was changed by the owner, only the updated fields are set
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MetadataUpdate<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<&'a str>,
+    /// Base64 SHA-256 of the JSON behind `reference`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_hash: Option<&'a str>,
+}
+
+impl MetadataUpdate<'_> {
+    pub fn emit(self) {
+        UnrealEvent::MetadataUpdate(&[self]).emit()
+    }
+}
+
+/// An owner action was queued behind the timelock
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TimelockQueued<'a> {
+    pub action_id: &'a str,
+    pub action: &'a TimelockedAction,
+    /// Earliest timestamp (in nanoseconds) the action can be executed at
+    pub eta: u64,
+}
+
+impl TimelockQueued<'_> {
+    pub fn emit(self) {
+        UnrealEvent::TimelockQueued(&[self]).emit()
+    }
+}
+
+/// A queued owner action was executed
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TimelockExecuted<'a> {
+    pub action_id: &'a str,
+}
+
+impl TimelockExecuted<'_> {
+    pub fn emit(self) {
+        UnrealEvent::TimelockExecuted(&[self]).emit()
+    }
+}
+
+/// A queued owner action was cancelled
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TimelockCancelled<'a> {
+    pub action_id: &'a str,
+}
+
+impl TimelockCancelled<'_> {
+    pub fn emit(self) {
+        UnrealEvent::TimelockCancelled(&[self]).emit()
+    }
+}
+
+/// Every balance was scaled so the total supply moved to `new_total_supply`
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Rebase {
+    pub old_total_supply: U128,
+    pub new_total_supply: U128,
+    /// Tokens per share, scaled by 10^18
+    pub scaling_factor: U128,
+}
+
+impl Rebase {
+    pub fn emit(self) {
+        UnrealEvent::Rebase(&[self]).emit()
+    }
+}
+
+/// Every operation was paused by `account_id`
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Pause<'a> {
+    pub account_id: &'a AccountId,
+    /// Block timestamp in nanoseconds
+    pub timestamp: u64,
+}
+
+impl Pause<'_> {
+    pub fn emit(self) {
+        UnrealEvent::Pause(&[self]).emit()
+    }
+}
+
+/// Every operation was unpaused by `account_id`
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Unpause<'a> {
+    pub account_id: &'a AccountId,
+    /// Block timestamp in nanoseconds
+    pub timestamp: u64,
+}
+
+impl Unpause<'_> {
+    pub fn emit(self) {
+        UnrealEvent::Unpause(&[self]).emit()
+    }
+}
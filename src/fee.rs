@@ -0,0 +1,95 @@
+//! Percentage fee taken on every transfer and routed to a fee collector.
+
+use near_sdk::{log, near_bindgen, AccountId};
+
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+/// Basis points in 100%
+const MAX_FEE_BASIS_POINTS: u16 = 10_000;
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the transfer fee in basis points (1/100 of a percent)
+    pub fn transfer_fee(&self) -> u16 {
+        self.fee_basis_points
+    }
+
+    /// Returns the account that receives transfer fees
+    pub fn fee_collector(&self) -> AccountId {
+        self.fee_collector.clone()
+    }
+
+    /// Returns true if transfers from or to the account pay no fee
+    pub fn is_fee_exempt(&self, account_id: AccountId) -> bool {
+        self.internal_is_fee_exempt(&account_id)
+    }
+
+    /// Set the transfer fee and its collector - only callable by owner.
+    /// The collector has to be registered so it can receive fees. While a timelock
+    /// delay is set, the change has to be queued instead
+    pub fn set_transfer_fee(&mut self, fee_basis_points: u16, fee_collector: AccountId) {
+        self.assert_owner();
+        self.assert_not_timelocked();
+        self.internal_set_transfer_fee(fee_basis_points, fee_collector);
+    }
+
+    pub(crate) fn internal_set_transfer_fee(
+        &mut self,
+        fee_basis_points: u16,
+        fee_collector: AccountId,
+    ) {
+        assert!(
+            fee_basis_points <= MAX_FEE_BASIS_POINTS,
+            "Fee cannot exceed {} basis points",
+            MAX_FEE_BASIS_POINTS
+        );
+        self.internal_unwrap_balance_of(&fee_collector);
+        self.fee_basis_points = fee_basis_points;
+        self.fee_collector = fee_collector;
+        log!(
+            "Transfer fee set to {} basis points, collected by {}",
+            fee_basis_points,
+            self.fee_collector
+        );
+    }
+
+    /// Exempt an account from transfer fees - only callable by owner
+    pub fn add_fee_exempt(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.fee_exempt.insert(&account_id, &true);
+        log!("Added fee exemption: {}", account_id);
+    }
+
+    /// Remove an account's fee exemption - only callable by owner
+    pub fn remove_fee_exempt(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.fee_exempt.remove(&account_id);
+        log!("Removed fee exemption: {}", account_id);
+    }
+
+    /// The owner and the fee collector are always exempt
+    fn internal_is_fee_exempt(&self, account_id: &AccountId) -> bool {
+        account_id == &self.owner_id
+            || account_id == &self.fee_collector
+            || self.fee_exempt.get(account_id).unwrap_or(false)
+    }
+
+    /// Fee due on a transfer of `amount`, rounded down.
+    /// Splitting `amount` around 10_000 keeps the multiplication from overflowing
+    pub(crate) fn internal_transfer_fee(
+        &self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: Balance,
+    ) -> Balance {
+        if self.fee_basis_points == 0
+            || self.internal_is_fee_exempt(sender_id)
+            || self.internal_is_fee_exempt(receiver_id)
+        {
+            return 0;
+        }
+        let bps = Balance::from(self.fee_basis_points);
+        let denominator = Balance::from(MAX_FEE_BASIS_POINTS);
+        amount / denominator * bps + amount % denominator * bps / denominator
+    }
+}
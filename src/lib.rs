@@ -1,17 +1,66 @@
 use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, LazyOption};
-use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault, Gas, log};
-use near_sdk::json_types::U128;
-use std::collections::HashMap;
+use near_sdk::collections::{LookupMap, LazyOption, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::{
+    assert_one_yocto, env, ext_contract, log, near_bindgen, AccountId, CryptoHash, Gas,
+    PanicOnDefault, Promise, PublicKey, PromiseOrValue, PromiseResult,
+};
+use near_sdk::json_types::{Base64VecU8, U128};
+use std::collections::{HashMap, HashSet};
+
+mod airdrop;
+mod checkpoints;
+mod display;
+mod events;
+mod fee;
+mod migrate;
+mod mint_cap;
+mod permit;
+mod rebase;
+mod recovery;
+mod restrictions;
+mod rewards;
+mod storage;
+mod supply;
+mod timelock;
+mod transfer_hook;
+mod vesting;
+mod votes;
+
+pub use crate::recovery::FungibleTokenCore;
+pub use crate::transfer_hook::TransferHook;
+
+use crate::events::{BlacklistUpdate, FtBurn, FtMint, FtTransfer, MetadataUpdate, Pause, Unpause};
+use crate::rebase::SCALING_PRECISION;
+use crate::rewards::RewardDistribution;
+use crate::checkpoints::{AccountCheckpoints, Checkpoint};
+use crate::timelock::PendingAction;
+use crate::vesting::VestingSchedule;
 
 type Balance = u128;
 
 /// Constants for gas and storage
-const TGAS: u64 = 1_000_000_000_000;
 const GAS_FOR_FT_TRANSFER: Gas = Gas::from_tgas(5);
 const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas::from_tgas(10);
+/// Gas `ft_transfer_call` keeps for its own execution, including the fees of the calls it schedules
+const GAS_FOR_FT_TRANSFER_CALL: Gas = Gas::from_tgas(35);
 /// Initial balance for the FT contract itself
 const CONTRACT_STORAGE_COST: Balance = 10_000_000_000_000_000_000_000; // 0.01 NEAR
+/// NEP-148 metadata spec version
+const FT_METADATA_SPEC: &str = "ft-1.0.0";
+/// Standards the contract implements, reported by `contract_version`
+const SUPPORTED_STANDARDS: &str = "nep141+nep145+nep148+nep297";
+/// Maximum number of recipients in a single `batch_transfer` to stay within gas limits
+const MAX_BATCH_TRANSFER_SIZE: usize = 100;
+/// Lower `batch_transfer` limit while a transfer hook is set, as every recipient costs a hook call
+const MAX_BATCH_TRANSFER_SIZE_WITH_HOOK: usize = 20;
+/// Maximum number of accounts in a single `burn_batch`
+const MAX_BATCH_BURN_SIZE: usize = 50;
+/// Most decimals a token can have, NEAR itself uses 24
+const MAX_DECIMALS: u8 = 24;
+/// Longest symbol exchanges list
+const MAX_SYMBOL_LENGTH: usize = 12;
+/// Most spenders an owner can have approved at once, registration pays for their allowances
+const MAX_SPENDERS_PER_OWNER: u64 = 8;
 
 /// The following is the NEP-141 standard for fungible tokens on NEAR
 /// It's equivalent to ERC-20 on Ethereum
@@ -29,28 +78,202 @@ pub struct UnrealToken {
     decimals: u8,
     /// Owner of the contract with admin rights
     owner_id: AccountId,
-    /// Contract pause state
-    paused: bool,
-    /// Balances of each account
-    balances: LookupMap<AccountId, Balance>,
-    /// Allowances between accounts (from, to) -> amount
-    allowances: LookupMap<AccountId, HashMap<AccountId, Balance>>,
+    /// Contract pause state per operation
+    pause_state: PauseState,
+    /// Shares of each registered account, see `scaling_factor`. Enumerable so holders
+    /// can be listed, at the cost of extra index writes when an account registers or
+    /// unregisters and a larger per-account storage deposit
+    balances: UnorderedMap<AccountId, Balance>,
+    /// Shares stored before `balances` became enumerable, moved over on write
+    /// or in bulk through `migrate_holders`
+    legacy_balances: LookupMap<AccountId, Balance>,
+    /// Allowances between accounts keyed by `allowance_key(owner, spender)` -> amount
+    allowances: LookupMap<CryptoHash, Balance>,
+    /// Allowances stored before the composite-key scheme (from, to) -> amount.
+    /// Entries are moved into `allowances` as they are written
+    legacy_allowances: LookupMap<AccountId, HashMap<AccountId, Balance>>,
     /// Metadata for the contract itself
     metadata: LazyOption<FungibleTokenMetadata>,
+    /// Storage used by a single `balances` entry, paid for on registration
+    account_storage_usage: u64,
+    /// Account proposed as the next owner, waiting to accept
+    pending_owner: Option<AccountId>,
+    /// Accounts allowed to mint besides the owner
+    minters: LookupMap<AccountId, bool>,
+    /// Hard cap on the total supply, `None` means uncapped
+    max_supply: Option<Balance>,
+    /// Accounts frozen from transferring, receiving and approving
+    blacklist: LookupMap<AccountId, bool>,
+    /// Fee taken on each transfer, in basis points
+    fee_basis_points: u16,
+    /// Account receiving transfer fees
+    fee_collector: AccountId,
+    /// Accounts whose transfers pay no fee
+    fee_exempt: LookupMap<AccountId, bool>,
+    /// Balance history of each account
+    balance_checkpoints: LookupMap<AccountId, AccountCheckpoints>,
+    /// Total supply history
+    total_supply_checkpoints: Vector<Checkpoint>,
+    /// Vote delegation, accounts missing here delegate to themselves
+    delegates: LookupMap<AccountId, AccountId>,
+    /// Voting power history of each delegatee
+    vote_checkpoints: LookupMap<AccountId, AccountCheckpoints>,
+    /// Vesting schedules keyed by (beneficiary, index)
+    vesting_schedules: LookupMap<(AccountId, u32), VestingSchedule>,
+    /// Number of vesting schedules created for each beneficiary
+    vesting_counts: LookupMap<AccountId, u32>,
+    /// Account allowed to pause (but not unpause) besides the owner
+    guardian: Option<AccountId>,
+    /// Minimum time between two transfers from the same account, zero disables it
+    transfer_cooldown_seconds: u64,
+    /// Timestamp of each account's last transfer, only written while a cooldown is set
+    last_transfer: LookupMap<AccountId, u64>,
+    /// Accounts not subject to the transfer cooldown
+    cooldown_exempt: LookupMap<AccountId, bool>,
+    /// Largest amount a single transfer can move
+    max_tx_amount: Option<Balance>,
+    /// Largest balance a receiving account can end up with
+    max_wallet: Option<Balance>,
+    /// Accounts not subject to `max_tx_amount` and `max_wallet`
+    limits_exempt: LookupMap<AccountId, bool>,
+    /// Until set, only transfers involving the owner or a whitelisted account go through
+    trading_enabled: bool,
+    /// Accounts allowed to transfer before trading is enabled
+    transfer_whitelist: LookupMap<AccountId, bool>,
+    /// Deadline (in nanoseconds) of each allowance approved with one, keyed like `allowances`
+    allowance_expiries: LookupMap<CryptoHash, u64>,
+    /// Nonce of the next permit each owner signs
+    permit_nonces: LookupMap<AccountId, u64>,
+    /// Keys owners registered for signing permits
+    permit_keys: LookupMap<AccountId, PublicKey>,
+    /// Length of a mint epoch, zero disables the mint rate limit
+    mint_epoch_length_seconds: u64,
+    /// Most that can be minted within a single epoch
+    mint_cap_per_epoch: Balance,
+    /// Epoch `minted_this_epoch` was counted in
+    current_mint_epoch: u64,
+    /// Amount minted so far in `current_mint_epoch`
+    minted_this_epoch: Balance,
+    /// Merkle root of the active airdrop
+    airdrop_root: Option<CryptoHash>,
+    /// Unclaimed part of the active airdrop, held on the contract's own account
+    airdrop_remaining: Balance,
+    /// Last timestamp the active airdrop can be claimed at
+    airdrop_deadline: u64,
+    /// Accounts that claimed, keyed by `(airdrop root, account)`
+    airdrop_claimed: LookupMap<(CryptoHash, AccountId), bool>,
+    /// Account holding the treasury, excluded from the circulating supply
+    treasury: AccountId,
+    /// Other accounts excluded from the circulating supply
+    circulating_excluded: UnorderedSet<AccountId>,
+    /// Reward distribution being worked through by `distribute_rewards_batch`
+    reward_distribution: Option<RewardDistribution>,
+    /// Delay between queueing a timelocked owner action and executing it, zero disables it
+    timelock_delay_seconds: u64,
+    /// Queued timelocked owner actions keyed by the hash of the action
+    pending_actions: LookupMap<CryptoHash, PendingAction>,
+    /// Tokens per share scaled by `SCALING_PRECISION`, moved by `rebase`
+    scaling_factor: Balance,
+    /// Scaling factor history, empty until the first rebase
+    scaling_factor_checkpoints: Vector<Checkpoint>,
+    /// Contract notified of every transfer, best-effort
+    transfer_hook: Option<AccountId>,
+    /// Whether NEP-141 transfers register unregistered receivers out of the attached deposit
+    auto_register_receivers: bool,
+    /// Timestamp (in nanoseconds) of the last `pause`, cleared by `unpause`
+    paused_at: Option<u64>,
+    /// Account that called the last `pause`, cleared by `unpause`
+    paused_by: Option<AccountId>,
+    /// Whether transfers to the contract's own account are allowed
+    allow_self_transfers: bool,
+    /// Spenders with a non-zero allowance, per owner, so allowances can be listed.
+    /// Allowances not yet moved out of `legacy_allowances` aren't included
+    spenders: LookupMap<AccountId, UnorderedSet<AccountId>>,
+    /// Everything ever minted, the initial supply included
+    total_minted: Balance,
+    /// Everything ever burned
+    total_burned: Balance,
+    /// Storage deposit paid by whoever registered each account, refunded on unregistering.
+    /// Accounts registered without paying (the owner, legacy holders) have none
+    storage_deposits: LookupMap<AccountId, Balance>,
 }
 
-#[derive(BorshDeserialize, BorshSerialize)]
+/// NEP-148 fungible token metadata
+#[derive(BorshDeserialize, BorshSerialize, serde::Serialize, serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
 pub struct FungibleTokenMetadata {
+    pub spec: String,
+    pub name: String,
+    pub symbol: String,
+    /// Data URL of the token icon
+    pub icon: Option<String>,
+    /// Link to off-chain JSON with extended metadata
+    pub reference: Option<String>,
+    /// SHA-256 hash of the JSON behind `reference`
+    pub reference_hash: Option<Base64VecU8>,
+    pub decimals: u8,
+}
+
+/// Which operations are currently paused
+#[derive(
+    BorshDeserialize, BorshSerialize, serde::Serialize, serde::Deserialize, Clone, Copy, Default,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PauseState {
+    /// Transfers and approvals
+    pub transfers: bool,
+    pub mints: bool,
+    pub burns: bool,
+}
+
+/// Outcome of `transfer` and `transfer_from`
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransferResult {
+    pub from: AccountId,
+    pub to: AccountId,
+    /// Amount credited to `to`, after the transfer fee
+    pub amount: U128,
+    pub new_sender_balance: U128,
+    pub new_receiver_balance: U128,
+}
+
+/// Token configuration returned by `get_config`, to render it with a single view call
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractConfig {
     pub name: String,
     pub symbol: String,
     pub decimals: u8,
+    pub total_supply: U128,
+    pub max_supply: Option<U128>,
+    pub owner_id: AccountId,
+    pub guardian: Option<AccountId>,
+    pub paused: bool,
+    pub pause_state: PauseState,
+    pub trading_enabled: bool,
+    /// Transfer fee in basis points
+    pub transfer_fee: u16,
+    pub fee_collector: AccountId,
+    /// Length of a mint epoch in seconds, zero when minting isn't rate limited
+    pub mint_epoch_length: u64,
+    pub mint_cap_per_epoch: U128,
+    /// Delay in seconds before queued owner actions can be executed
+    pub timelock_delay: u64,
+}
+
+impl PauseState {
+    fn all(paused: bool) -> Self {
+        Self { transfers: paused, mints: paused, burns: paused }
+    }
 }
 
 #[near_bindgen]
 impl UnrealToken {
-    /// Initializes the contract with hardcoded values
+    /// Initializes the contract with hardcoded values, an optional supply cap and
+    /// an optional treasury, which defaults to the owner
     #[init]
-    pub fn new() -> Self {
+    pub fn new(max_supply: Option<U128>, treasury: Option<AccountId>) -> Self {
         // Ensure contract is not initialized yet
         assert!(!env::state_exists(), "Contract is already initialized");
         
@@ -59,6 +282,11 @@ impl UnrealToken {
         let symbol = "UNREAL".to_string();
         let decimals = 18u8; // Standard for most tokens
         let initial_supply = 250_000_000_000_000_000_000_000_000u128; // 250M with 18 decimals
+        assert_valid_metadata(&name, &symbol, decimals);
+        let max_supply: Option<Balance> = max_supply.map(|max| max.into());
+        if let Some(max) = max_supply {
+            assert!(max >= initial_supply, "Max supply is below the initial supply");
+        }
         
         let owner_id = env::predecessor_account_id();
         let mut this = Self {
@@ -67,21 +295,86 @@ impl UnrealToken {
             total_supply: initial_supply,
             decimals,
             owner_id: owner_id.clone(),
-            paused: false,
-            balances: LookupMap::new(b"b"),
-            allowances: LookupMap::new(b"a"),
+            pause_state: PauseState::default(),
+            balances: UnorderedMap::new(b"h"),
+            legacy_balances: LookupMap::new(b"b"),
+            allowances: LookupMap::new(b"c"),
+            legacy_allowances: LookupMap::new(b"a"),
             metadata: LazyOption::new(
                 b"m", 
                 Some(&FungibleTokenMetadata {
+                    spec: FT_METADATA_SPEC.to_string(),
                     name: name.clone(),
                     symbol: symbol.clone(),
+                    icon: None,
+                    reference: None,
+                    reference_hash: None,
                     decimals,
                 }),
             ),
+            account_storage_usage: 0,
+            pending_owner: None,
+            minters: LookupMap::new(b"n"),
+            max_supply,
+            blacklist: LookupMap::new(b"l"),
+            fee_basis_points: 0,
+            fee_collector: owner_id.clone(),
+            fee_exempt: LookupMap::new(b"f"),
+            balance_checkpoints: LookupMap::new(b"k"),
+            total_supply_checkpoints: Vector::new(b"s"),
+            delegates: LookupMap::new(b"d"),
+            vote_checkpoints: LookupMap::new(b"v"),
+            vesting_schedules: LookupMap::new(b"g"),
+            vesting_counts: LookupMap::new(b"G"),
+            guardian: None,
+            transfer_cooldown_seconds: 0,
+            last_transfer: LookupMap::new(b"t"),
+            cooldown_exempt: LookupMap::new(b"e"),
+            max_tx_amount: None,
+            max_wallet: None,
+            limits_exempt: LookupMap::new(b"x"),
+            trading_enabled: false,
+            transfer_whitelist: LookupMap::new(b"w"),
+            allowance_expiries: LookupMap::new(b"y"),
+            permit_nonces: LookupMap::new(b"p"),
+            permit_keys: LookupMap::new(b"P"),
+            mint_epoch_length_seconds: 0,
+            mint_cap_per_epoch: 0,
+            current_mint_epoch: 0,
+            minted_this_epoch: 0,
+            airdrop_root: None,
+            airdrop_remaining: 0,
+            airdrop_deadline: 0,
+            airdrop_claimed: LookupMap::new(b"j"),
+            treasury: treasury.unwrap_or_else(|| owner_id.clone()),
+            circulating_excluded: UnorderedSet::new(b"q"),
+            reward_distribution: None,
+            timelock_delay_seconds: 0,
+            pending_actions: LookupMap::new(b"T"),
+            scaling_factor: SCALING_PRECISION,
+            scaling_factor_checkpoints: Vector::new(b"F"),
+            transfer_hook: None,
+            auto_register_receivers: false,
+            paused_at: None,
+            paused_by: None,
+            allow_self_transfers: false,
+            spenders: LookupMap::new(b"S"),
+            total_minted: initial_supply,
+            total_burned: 0,
+            storage_deposits: LookupMap::new(b"D"),
         };
+        this.measure_account_storage_usage();
         
-        // Mint the initial supply to the contract owner
+        // Register the owner and mint the initial supply to them
+        this.internal_set_balance(&owner_id, 0);
         this.internal_deposit(&owner_id, initial_supply);
+        this.internal_checkpoint_total_supply();
+        FtMint {
+            owner_id: &owner_id,
+            amount: U128(initial_supply),
+            memo: Some("Initial supply"),
+        }
+        .emit();
         log!("Initialized Unreal Token with 250,000,000 supply to {}", owner_id);
         
         this
@@ -106,14 +399,71 @@ impl UnrealToken {
         self.decimals
     }
     
+    /// Returns the NEP-148 metadata of the token
+    pub fn ft_metadata(&self) -> FungibleTokenMetadata {
+        self.metadata.get().expect("Metadata is not set")
+    }
+
+    /// Returns the crate version and the standards implemented, e.g.
+    /// `0.1.0 nep141+nep145+nep148+nep297`, so tooling can feature-detect the contract
+    pub fn contract_version(&self) -> String {
+        format!("{} {}", env!("CARGO_PKG_VERSION"), SUPPORTED_STANDARDS)
+    }
+
     /// Returns the total supply of the token
     pub fn total_supply(&self) -> U128 {
         U128(self.total_supply)
     }
 
+    /// Returns the supply cap, or `None` if the supply is uncapped
+    pub fn max_supply(&self) -> Option<U128> {
+        self.max_supply.map(U128)
+    }
+
+    /// Returns the total amount ever minted. Rebases change the supply without minting,
+    /// so afterwards this no longer adds up with `total_burned` to the total supply
+    pub fn total_minted(&self) -> U128 {
+        U128(self.total_minted)
+    }
+
+    /// Returns the total amount ever burned
+    pub fn total_burned(&self) -> U128 {
+        U128(self.total_burned)
+    }
+
     /// Returns the balance of the specified account
     pub fn balance_of(&self, account_id: AccountId) -> U128 {
-        U128(self.balances.get(&account_id).unwrap_or(0))
+        U128(self.internal_balance_of(&account_id).unwrap_or(0))
+    }
+
+    /// NEP-141 name of `total_supply`
+    pub fn ft_total_supply(&self) -> U128 {
+        self.total_supply()
+    }
+
+    /// NEP-141 name of `balance_of`
+    pub fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        self.balance_of(account_id)
+    }
+
+    /// Returns up to `limit` registered accounts and their balances, starting at `from_index`
+    pub fn get_holders(&self, from_index: u64, limit: u64) -> Vec<(AccountId, U128)> {
+        let keys = self.balances.keys_as_vector();
+        let values = self.balances.values_as_vector();
+        let end = std::cmp::min(from_index.saturating_add(limit), keys.len());
+        (from_index..end)
+            .map(|index| {
+                let account_id = keys.get(index).unwrap();
+                let balance = self.balance_from_shares(&account_id, values.get(index).unwrap());
+                (account_id, U128(balance))
+            })
+            .collect()
+    }
+
+    /// Returns the number of registered accounts, including those with a zero balance.
+    /// Accounts not yet moved out of the legacy balances map aren't counted
+    pub fn holder_count(&self) -> u64 {
+        self.balances.len()
     }
     
     /// Returns the allowance of the `spender` for the `owner`
@@ -121,45 +471,284 @@ impl UnrealToken {
         self.internal_get_allowance(&owner_id, &spender_id)
     }
 
+    /// Returns the spenders `owner_id` approved and their allowances, expired ones as zero
+    pub fn get_allowances(
+        &self,
+        owner_id: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<(AccountId, U128)> {
+        self.spenders.get(&owner_id).map_or_else(Vec::new, |spenders| {
+            let spenders = spenders.as_vector();
+            let end = std::cmp::min(from_index.saturating_add(limit), spenders.len());
+            (from_index..end)
+                .map(|index| {
+                    let spender_id = spenders.get(index).unwrap();
+                    let allowance = self.internal_get_allowance(&owner_id, &spender_id);
+                    (spender_id, allowance)
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the deadline (in nanoseconds) of the allowance, `None` if it never expires
+    pub fn allowance_expires_at(&self, owner_id: AccountId, spender_id: AccountId) -> Option<u64> {
+        self.allowance_expiries.get(&allowance_key(&owner_id, &spender_id))
+    }
+
     /// Transfer tokens to a specified account
-    pub fn transfer(&mut self, receiver_id: AccountId, amount: U128) -> bool {
-        self.assert_not_paused();
+    pub fn transfer(&mut self, receiver_id: AccountId, amount: U128) -> TransferResult {
+        self.assert_transfers_not_paused();
+        let sender_id = env::predecessor_account_id();
+        let received = self.internal_transfer(&sender_id, &receiver_id, amount.into(), None);
+        self.internal_transfer_result(sender_id, receiver_id, received)
+    }
+
+    /// NEP-141 transfer. Requires exactly 1 yoctoNEAR attached for security, plus the
+    /// receiver's storage deposit if it isn't registered and auto-registration is on.
+    /// Attaching more to a registered receiver panics rather than keeping the surplus
+    #[payable]
+    pub fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
+        self.internal_prepare_receiver(&receiver_id);
+        self.assert_transfers_not_paused();
         self.internal_transfer(
             &env::predecessor_account_id(),
             &receiver_id,
             amount.into(),
-            None,
+            memo,
+        );
+    }
+
+    /// NEP-141 transfer to a contract followed by a call to its `ft_on_transfer`.
+    /// Whatever the receiver reports as unused is refunded in `ft_resolve_transfer`
+    #[payable]
+    pub fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> Promise {
+        // The token doesn't implement `ft_on_transfer`, whether self transfers are allowed or not
+        assert!(receiver_id != env::current_account_id(), "Cannot transfer to the token contract");
+        self.internal_prepare_receiver(&receiver_id);
+        self.assert_transfers_not_paused();
+        let sender_id = env::predecessor_account_id();
+        // The receiver is told about what it actually got after fees
+        let amount = U128(self.internal_transfer(&sender_id, &receiver_id, amount.into(), memo));
+
+        // Everything not reserved for this call, the hook call and the resolve callback
+        // goes to the receiver
+        let reserved_gas = GAS_FOR_FT_TRANSFER_CALL
+            .saturating_add(GAS_FOR_RESOLVE_TRANSFER)
+            .saturating_add(self.internal_transfer_hook_gas());
+        let receiver_gas = env::prepaid_gas()
+            .checked_sub(reserved_gas)
+            .unwrap_or_else(|| env::panic_str("Not enough gas attached for ft_transfer_call"));
+
+        ext_ft_receiver::ext(receiver_id.clone())
+            .with_static_gas(receiver_gas)
+            .ft_on_transfer(sender_id.clone(), amount, msg)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_TRANSFER)
+                    .ft_resolve_transfer(sender_id, receiver_id, amount),
+            )
+    }
+
+    /// Callback for `ft_transfer_call` that refunds the unused amount to the sender.
+    /// Returns the amount that was actually used by the receiver
+    #[private]
+    pub fn ft_resolve_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128 {
+        let amount: Balance = amount.into();
+
+        // A failed or malformed `ft_on_transfer` counts as nothing used
+        let unused_amount = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                match near_sdk::serde_json::from_slice::<U128>(&value) {
+                    Ok(unused) => std::cmp::min(amount, unused.0),
+                    Err(_) => amount,
+                }
+            }
+            PromiseResult::Failed => amount,
+        };
+        if unused_amount == 0 {
+            return U128(amount);
+        }
+
+        let sender_registered = self.internal_is_registered(&sender_id);
+        let refund_amount = match self.internal_balance_of(&receiver_id) {
+            Some(receiver_balance) => {
+                // The receiver may already have spent part of the tokens
+                let refund_amount = std::cmp::min(receiver_balance, unused_amount);
+                if refund_amount > 0 {
+                    self.internal_withdraw(&receiver_id, refund_amount);
+                    if sender_registered {
+                        self.internal_deposit(&sender_id, refund_amount);
+                        FtTransfer {
+                            old_owner_id: &receiver_id,
+                            new_owner_id: &sender_id,
+                            amount: U128(refund_amount),
+                            memo: Some("refund"),
+                        }
+                        .emit();
+                    } else {
+                        // Nobody left to refund, so the tokens are burned
+                        self.total_supply = self
+                            .total_supply
+                            .checked_sub(refund_amount)
+                            .unwrap_or_else(|| env::panic_str("Total supply underflow"));
+                        self.total_burned = self.total_burned.saturating_add(refund_amount);
+                        self.internal_checkpoint_total_supply();
+                        FtBurn {
+                            owner_id: &receiver_id,
+                            amount: U128(refund_amount),
+                            memo: Some("refund"),
+                        }
+                        .emit();
+                    }
+                }
+                refund_amount
+            }
+            // The receiver unregistered in the meantime, which already burned its balance
+            // along with the unused amount, so there is nothing left to refund
+            None => 0,
+        };
+        if refund_amount > 0 {
+            log!("Refund {} from {} to {}", refund_amount, receiver_id, sender_id);
+        }
+
+        U128(amount - refund_amount)
+    }
+
+    /// Transfer tokens from the caller to many recipients in one call.
+    /// The whole batch is checked against the caller's balance up front, and one
+    /// `ft_transfer` event is emitted per recipient so indexers see ordinary transfers
+    pub fn batch_transfer(&mut self, transfers: Vec<(AccountId, U128)>) -> bool {
+        self.assert_transfers_not_paused();
+        assert!(!transfers.is_empty(), "Transfers cannot be empty");
+        let max_batch_size = if self.transfer_hook.is_some() {
+            MAX_BATCH_TRANSFER_SIZE_WITH_HOOK
+        } else {
+            MAX_BATCH_TRANSFER_SIZE
+        };
+        assert!(
+            transfers.len() <= max_batch_size,
+            "Cannot transfer to more than {} recipients at once",
+            max_batch_size
+        );
+
+        let sender_id = env::predecessor_account_id();
+        let total = transfers
+            .iter()
+            .try_fold(0u128, |total: Balance, (_, amount)| {
+                assert!(amount.0 > 0, "The amount should be a positive number");
+                total.checked_add(amount.0)
+            })
+            .unwrap_or_else(|| env::panic_str("Batch total overflow"));
+        assert!(
+            self.internal_balance_of(&sender_id).unwrap_or(0) >= total,
+            "Insufficient balance"
         );
+
+        for (receiver_id, amount) in transfers {
+            self.internal_transfer(&sender_id, &receiver_id, amount.into(), None);
+        }
         true
     }
 
     /// Transfer tokens from a specified account (if approved)
-    pub fn transfer_from(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) -> bool {
-        self.assert_not_paused();
+    pub fn transfer_from(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> TransferResult {
+        self.assert_transfers_not_paused();
         let caller_id = env::predecessor_account_id();
         let amount_u128: Balance = amount.into();
+        // Validate before consuming any allowance
+        assert_ne!(sender_id, receiver_id, "Cannot transfer to yourself");
+        assert!(amount_u128 > 0, "The amount should be a positive number");
         self.internal_decrease_allowance(&sender_id, &caller_id, amount_u128);
-        self.internal_transfer(&sender_id, &receiver_id, amount_u128, None);
-        true
+        let received = self.internal_transfer(&sender_id, &receiver_id, amount_u128, None);
+        self.internal_transfer_result(sender_id, receiver_id, received)
     }
 
-    /// Approve `spender` to transfer tokens on behalf of the caller
-    pub fn approve(&mut self, spender_id: AccountId, amount: U128) -> bool {
-        self.assert_not_paused();
-        self.internal_approve(
+    /// Approve `spender` to transfer tokens on behalf of the caller.
+    /// With `expires_at` (block timestamp in nanoseconds) the allowance can't be
+    /// used after that time, without it the approval never expires
+    pub fn approve(
+        &mut self,
+        spender_id: AccountId,
+        amount: U128,
+        expires_at: Option<u64>,
+    ) -> bool {
+        self.assert_transfers_not_paused();
+        self.internal_approve_until(
             &env::predecessor_account_id(),
             &spender_id,
             amount.into(),
+            expires_at,
         )
     }
 
+    /// Increase the allowance of `spender` by `added_value`, keeping its expiry while it
+    /// is in the future. Increasing an expired allowance starts from zero and clears the
+    /// expiry. Prefer this over `approve` to avoid the approval front-running race
+    pub fn increase_allowance(&mut self, spender_id: AccountId, added_value: U128) -> bool {
+        self.assert_transfers_not_paused();
+        let owner_id = env::predecessor_account_id();
+        let allowance = self.internal_get_allowance(&owner_id, &spender_id).0;
+        let key = allowance_key(&owner_id, &spender_id);
+        // A passed expiry would void the new allowance right away
+        if self.internal_is_allowance_expired(&key) {
+            self.allowance_expiries.remove(&key);
+        }
+        let new_allowance = allowance
+            .checked_add(added_value.0)
+            .unwrap_or_else(|| env::panic_str("Allowance overflow"));
+        self.internal_approve(&owner_id, &spender_id, new_allowance)
+    }
+
+    /// Decrease the allowance of `spender` by `subtracted_value`.
+    /// Saturates to zero rather than panicking, so revoking more than what is left
+    /// (e.g. after the spender used part of it) always succeeds
+    pub fn decrease_allowance(&mut self, spender_id: AccountId, subtracted_value: U128) -> bool {
+        self.assert_transfers_not_paused();
+        let owner_id = env::predecessor_account_id();
+        let allowance = self.internal_get_allowance(&owner_id, &spender_id).0;
+        self.internal_approve(&owner_id, &spender_id, allowance.saturating_sub(subtracted_value.0))
+    }
+
     /********************************
     * Owner Management & Pausable  *
     ********************************/
 
-    /// Returns true if the contract is currently paused
+    /// Returns true if any operation is currently paused
     pub fn is_paused(&self) -> bool {
-        self.paused
+        let state = self.pause_state;
+        state.transfers || state.mints || state.burns
+    }
+
+    /// Returns which operations are currently paused
+    pub fn pause_state(&self) -> PauseState {
+        self.pause_state
+    }
+
+    /// Returns when the contract was paused with `pause`, until it's unpaused
+    pub fn paused_at(&self) -> Option<u64> {
+        self.paused_at
+    }
+
+    /// Returns who paused the contract with `pause`, until it's unpaused
+    pub fn paused_by(&self) -> Option<AccountId> {
+        self.paused_by.clone()
     }
     
     /// Returns the account ID of the contract owner
@@ -167,49 +756,358 @@ impl UnrealToken {
         self.owner_id.clone()
     }
     
-    /// Pause the contract - only callable by owner
-    pub fn pause(&mut self) {
+    /// Returns the guardian account, if any
+    pub fn guardian(&self) -> Option<AccountId> {
+        self.guardian.clone()
+    }
+
+    /// Returns the whole token configuration at once
+    pub fn get_config(&self) -> ContractConfig {
+        ContractConfig {
+            name: self.name.clone(),
+            symbol: self.symbol.clone(),
+            decimals: self.decimals,
+            total_supply: U128(self.total_supply),
+            max_supply: self.max_supply.map(U128),
+            owner_id: self.owner_id.clone(),
+            guardian: self.guardian.clone(),
+            paused: self.is_paused(),
+            pause_state: self.pause_state,
+            trading_enabled: self.trading_enabled,
+            transfer_fee: self.fee_basis_points,
+            fee_collector: self.fee_collector.clone(),
+            mint_epoch_length: self.mint_epoch_length_seconds,
+            mint_cap_per_epoch: U128(self.mint_cap_per_epoch),
+            timelock_delay: self.timelock_delay_seconds,
+        }
+    }
+
+    /// Set or clear the guardian - only callable by owner
+    pub fn set_guardian(&mut self, guardian: Option<AccountId>) {
         self.assert_owner();
-        self.paused = true;
-        log!("Contract paused by owner");
+        match &guardian {
+            Some(account_id) => log!("Guardian set to {}", account_id),
+            None => log!("Guardian cleared"),
+        }
+        self.guardian = guardian;
+    }
+
+    /// Pause transfers, mints and burns - callable by owner or guardian.
+    /// The guardian can only pause, so a compromised guardian can't lift a pause
+    pub fn pause(&mut self) {
+        let caller_id = env::predecessor_account_id();
+        assert!(
+            caller_id == self.owner_id || self.guardian.as_ref() == Some(&caller_id),
+            "Only the owner or the guardian can pause"
+        );
+        self.pause_state = PauseState::all(true);
+        let timestamp = env::block_timestamp();
+        log!("Contract paused by {}", caller_id);
+        Pause { account_id: &caller_id, timestamp }.emit();
+        self.paused_at = Some(timestamp);
+        self.paused_by = Some(caller_id);
     }
     
-    /// Unpause the contract - only callable by owner
+    /// Unpause transfers, mints and burns - only callable by owner, never by the guardian
     pub fn unpause(&mut self) {
         self.assert_owner();
-        self.paused = false;
+        self.pause_state = PauseState::all(false);
+        self.paused_at = None;
+        self.paused_by = None;
         log!("Contract unpaused by owner");
+        Unpause { account_id: &self.owner_id, timestamp: env::block_timestamp() }.emit();
+    }
+
+    /// Pause or unpause transfers and approvals - only callable by owner
+    pub fn set_pause_transfers(&mut self, paused: bool) {
+        self.assert_owner();
+        self.pause_state.transfers = paused;
+        log!("Transfers paused: {}", paused);
+    }
+
+    /// Pause or unpause minting - only callable by owner
+    pub fn set_pause_mints(&mut self, paused: bool) {
+        self.assert_owner();
+        self.pause_state.mints = paused;
+        log!("Mints paused: {}", paused);
+    }
+
+    /// Pause or unpause burning - only callable by owner
+    pub fn set_pause_burns(&mut self, paused: bool) {
+        self.assert_owner();
+        self.pause_state.burns = paused;
+        log!("Burns paused: {}", paused);
     }
     
-    /// Transfer ownership to new account - only callable by owner
+    /// Returns the account proposed as the next owner, if any
+    pub fn pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner.clone()
+    }
+
+    /// Propose a new owner - only callable by owner.
+    /// Ownership only moves once `new_owner` calls `accept_ownership`
+    pub fn propose_owner(&mut self, new_owner: AccountId) {
+        self.assert_owner();
+        self.pending_owner = Some(new_owner.clone());
+        log!("Ownership transfer to {} proposed", new_owner);
+    }
+
+    /// Accept a pending ownership transfer - only callable by the pending owner
+    pub fn accept_ownership(&mut self) {
+        let caller_id = env::predecessor_account_id();
+        assert_eq!(
+            self.pending_owner.as_ref(),
+            Some(&caller_id),
+            "Only the pending owner can accept ownership"
+        );
+        self.owner_id = caller_id;
+        self.pending_owner = None;
+        log!("Ownership transferred to {}", self.owner_id);
+    }
+
+    /// Cancel a pending ownership transfer - only callable by owner
+    pub fn cancel_ownership_transfer(&mut self) {
+        self.assert_owner();
+        assert!(self.pending_owner.is_some(), "No pending ownership transfer");
+        self.pending_owner = None;
+        log!("Ownership transfer cancelled");
+    }
+
+    /// Kept for compatibility, delegates to `propose_owner`
     pub fn transfer_ownership(&mut self, new_owner: AccountId) {
+        self.propose_owner(new_owner);
+    }
+
+    /// Returns true if the account is blacklisted
+    pub fn is_blacklisted(&self, account_id: AccountId) -> bool {
+        self.blacklist.get(&account_id).unwrap_or(false)
+    }
+
+    /// Freeze an account - only callable by owner.
+    /// Its balance can still be burned by the owner
+    pub fn blacklist_account(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.blacklist.insert(&account_id, &true);
+        log!("Blacklisted account: {}", account_id);
+        BlacklistUpdate { account_id: &account_id, blacklisted: true }.emit();
+    }
+
+    /// Unfreeze an account - only callable by owner
+    pub fn unblacklist_account(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.blacklist.remove(&account_id);
+        log!("Unblacklisted account: {}", account_id);
+        BlacklistUpdate { account_id: &account_id, blacklisted: false }.emit();
+    }
+
+    /// Returns the base64 SHA-256 hash of the JSON behind the metadata `reference`
+    pub fn metadata_reference_hash(&self) -> Option<String> {
+        self.metadata
+            .get()
+            .and_then(|metadata| metadata.reference_hash)
+            .map(|hash| encode_base64(&hash))
+    }
+
+    /// Update the token metadata - only callable by owner.
+    /// Fields left as `None` are kept. Decimals are immutable, so passing a
+    /// different value for them is rejected. `reference_hash` is the SHA-256 of the
+    /// JSON behind `reference`; setting `reference` without it clears the old hash,
+    /// so it never vouches for content it wasn't computed from.
+    /// While a timelock delay is set, the update has to be queued instead
+    pub fn update_metadata(
+        &mut self,
+        name: Option<String>,
+        symbol: Option<String>,
+        icon: Option<String>,
+        reference: Option<String>,
+        reference_hash: Option<Base64VecU8>,
+        decimals: Option<u8>,
+    ) {
         self.assert_owner();
-        self.owner_id = new_owner.clone();
-        log!("Ownership transferred to {}", new_owner);
+        self.assert_not_timelocked();
+        self.internal_update_metadata(name, symbol, icon, reference, reference_hash, decimals);
+    }
+
+    fn internal_update_metadata(
+        &mut self,
+        name: Option<String>,
+        symbol: Option<String>,
+        icon: Option<String>,
+        reference: Option<String>,
+        reference_hash: Option<Base64VecU8>,
+        decimals: Option<u8>,
+    ) {
+        if let Some(decimals) = decimals {
+            assert_eq!(decimals, self.decimals, "Decimals cannot be changed");
+        }
+        if let Some(reference_hash) = &reference_hash {
+            assert_eq!(
+                reference_hash.0.len(),
+                32,
+                "Reference hash must be a 32 byte SHA-256 hash"
+            );
+        }
+
+        let mut metadata = self.metadata.get().expect("Metadata is not set");
+        assert_valid_metadata(
+            name.as_deref().unwrap_or(&metadata.name),
+            symbol.as_deref().unwrap_or(&metadata.symbol),
+            self.decimals,
+        );
+        if let Some(name) = &name {
+            self.name = name.clone();
+            metadata.name = name.clone();
+        }
+        if let Some(symbol) = &symbol {
+            self.symbol = symbol.clone();
+            metadata.symbol = symbol.clone();
+        }
+        if icon.is_some() {
+            metadata.icon = icon.clone();
+        }
+        if reference.is_some() {
+            metadata.reference = reference.clone();
+            metadata.reference_hash = reference_hash.clone();
+        } else if reference_hash.is_some() {
+            assert!(metadata.reference.is_some(), "Reference hash requires a reference");
+            metadata.reference_hash = reference_hash.clone();
+        }
+        self.metadata.set(&metadata);
+
+        log!("Metadata updated by owner");
+        MetadataUpdate {
+            name: name.as_deref(),
+            symbol: symbol.as_deref(),
+            icon: icon.as_deref(),
+            reference: reference.as_deref(),
+            reference_hash: reference_hash.as_ref().map(encode_base64).as_deref(),
+        }
+        .emit();
     }
 
     /***********************
     * Minting and Burning *
     ***********************/
 
-    /// Mint tokens to specified account - only callable by owner
-    pub fn mint(&mut self, to: AccountId, amount: U128) {
+    /// Returns true if the account is an authorized minter
+    pub fn is_minter(&self, account_id: AccountId) -> bool {
+        self.minters.get(&account_id).unwrap_or(false)
+    }
+
+    /// Authorize an account to mint - only callable by owner.
+    /// While a timelock delay is set, this has to be queued instead
+    pub fn add_minter(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.assert_not_timelocked();
+        self.internal_add_minter(account_id);
+    }
+
+    fn internal_add_minter(&mut self, account_id: AccountId) {
+        self.minters.insert(&account_id, &true);
+        log!("Added minter: {}", account_id);
+    }
+
+    /// Revoke an account's minting rights - only callable by owner
+    pub fn remove_minter(&mut self, account_id: AccountId) {
         self.assert_owner();
-        self.assert_not_paused();
+        self.minters.remove(&account_id);
+        log!("Removed minter: {}", account_id);
+    }
+
+    /// Mint tokens to specified account - only callable by owner or a minter.
+    /// While a timelock delay is set, the owner has to queue the mint instead
+    pub fn mint(&mut self, to: AccountId, amount: U128) {
+        self.assert_can_mint();
+        if env::predecessor_account_id() == self.owner_id {
+            self.assert_not_timelocked();
+        }
+        self.internal_mint(&to, amount, None);
+    }
+
+    /// Mint `amount` to `receiver_id` with an optional memo, for minters that are
+    /// contracts (e.g. the HTLC completing a cross-chain swap) - only callable by owner
+    /// or a minter. Requires exactly 1 yoctoNEAR like other NEP-141 calls
+    #[payable]
+    pub fn ft_mint(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
+        assert_one_yocto();
+        self.assert_can_mint();
+        if env::predecessor_account_id() == self.owner_id {
+            self.assert_not_timelocked();
+        }
+        self.internal_mint(&receiver_id, amount, memo.as_deref());
+    }
+
+    /// Mint `amount` to `to`, enforcing the supply caps
+    fn internal_mint(&mut self, to: &AccountId, amount: U128, memo: Option<&str>) {
+        self.assert_mints_not_paused();
         let amount_u128: Balance = amount.into();
-        self.internal_deposit(&to, amount_u128);
-        self.total_supply += amount_u128;
+        self.total_supply = self
+            .total_supply
+            .checked_add(amount_u128)
+            .unwrap_or_else(|| env::panic_str("Total supply overflow"));
+        if let Some(max_supply) = self.max_supply {
+            assert!(self.total_supply <= max_supply, "Max supply exceeded");
+        }
+        self.internal_consume_mint_budget(amount_u128);
+        self.total_minted = self.total_minted.saturating_add(amount_u128);
+        self.internal_deposit(to, amount_u128);
+        self.internal_checkpoint_total_supply();
         log!("Minted {} tokens to {}", amount.0, to);
+        FtMint { owner_id: to, amount, memo }.emit();
     }
 
     /// Burn tokens from specified account - only callable by owner
     pub fn burn(&mut self, from: AccountId, amount: U128) {
         self.assert_owner();
-        self.assert_not_paused();
-        let amount_u128: Balance = amount.into();
-        self.internal_withdraw(&from, amount_u128);
-        self.total_supply -= amount_u128;
-        log!("Burned {} tokens from {}", amount.0, from);
+        self.assert_burns_not_paused();
+        self.internal_burn(&from, amount.into());
+    }
+
+    /// Burn tokens from many accounts at once - only callable by owner.
+    /// Every balance is checked up front, so either all burns go through or none do.
+    /// Each account may appear once and gets its own `ft_burn` event
+    pub fn burn_batch(&mut self, burns: Vec<(AccountId, U128)>) {
+        self.assert_owner();
+        self.assert_burns_not_paused();
+        assert!(!burns.is_empty(), "Burns cannot be empty");
+        assert!(
+            burns.len() <= MAX_BATCH_BURN_SIZE,
+            "Cannot burn from more than {} accounts at once",
+            MAX_BATCH_BURN_SIZE
+        );
+
+        let mut seen = HashSet::new();
+        for (account_id, amount) in &burns {
+            assert!(seen.insert(account_id), "Duplicate account {} in batch", account_id);
+            assert!(amount.0 > 0, "The amount should be a positive number");
+            assert!(
+                self.internal_balance_of(account_id).unwrap_or(0) >= amount.0,
+                "Insufficient balance in {}",
+                account_id
+            );
+        }
+
+        for (account_id, amount) in burns {
+            self.internal_burn(&account_id, amount.into());
+        }
+    }
+
+    /// Burn tokens from the caller's own balance
+    pub fn burn_own(&mut self, amount: U128) {
+        self.assert_burns_not_paused();
+        self.internal_burn(&env::predecessor_account_id(), amount.into());
+    }
+
+    /// Burn tokens from `owner` using the caller's allowance, e.g. for redemptions
+    pub fn burn_from(&mut self, owner: AccountId, amount: U128) {
+        self.assert_burns_not_paused();
+        let caller_id = env::predecessor_account_id();
+        let amount: Balance = amount.into();
+        assert!(amount > 0, "The amount should be a positive number");
+        self.assert_not_blacklisted(&owner);
+        self.assert_not_blacklisted(&caller_id);
+        self.internal_decrease_allowance(&owner, &caller_id, amount);
+        self.internal_burn(&owner, amount);
     }
 
     /*************************
@@ -225,48 +1123,270 @@ impl UnrealToken {
         );
     }
 
-    /// Assert that the contract is not paused
-    fn assert_not_paused(&self) {
-        assert!(!self.paused, "Contract is paused");
+    /// Assert that the caller is the owner or an authorized minter
+    fn assert_can_mint(&self) {
+        let caller_id = env::predecessor_account_id();
+        assert!(
+            caller_id == self.owner_id || self.minters.get(&caller_id).unwrap_or(false),
+            "Only the owner or a minter can call this method"
+        );
+    }
+
+    /// Assert that the account is not blacklisted
+    fn assert_not_blacklisted(&self, account_id: &AccountId) {
+        assert!(
+            !self.blacklist.get(account_id).unwrap_or(false),
+            "Account {} is blacklisted",
+            account_id
+        );
+    }
+
+    /// Assert that transfers and approvals are not paused
+    fn assert_transfers_not_paused(&self) {
+        assert!(!self.pause_state.transfers, "Transfers are paused");
+    }
+
+    /// Assert that minting is not paused
+    fn assert_mints_not_paused(&self) {
+        assert!(!self.pause_state.mints, "Minting is paused");
+    }
+
+    /// Assert that burning is not paused
+    fn assert_burns_not_paused(&self) {
+        assert!(!self.pause_state.burns, "Burning is paused");
+    }
+
+    /// Measure the storage taken by one account with the longest possible account ID: its
+    /// `balances` and `storage_deposits` entries, full rings of balance and vote checkpoints
+    /// and the most allowances it can approve
+    fn measure_account_storage_usage(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id: AccountId = "a".repeat(64).parse().unwrap();
+        self.balances.insert(&tmp_account_id, &0);
+        let mut balance_checkpoints = AccountCheckpoints::new(b"K", &tmp_account_id);
+        let mut vote_checkpoints = AccountCheckpoints::new(b"V", &tmp_account_id);
+        for timestamp in 0..crate::checkpoints::MAX_ACCOUNT_CHECKPOINTS {
+            balance_checkpoints.write(timestamp, Balance::from(timestamp) + 1);
+            vote_checkpoints.write(timestamp, Balance::from(timestamp) + 1);
+        }
+        self.balance_checkpoints.insert(&tmp_account_id, &balance_checkpoints);
+        self.vote_checkpoints.insert(&tmp_account_id, &vote_checkpoints);
+        let mut spenders = UnorderedSet::new(spenders_prefix(&tmp_account_id));
+        for index in 0..MAX_SPENDERS_PER_OWNER {
+            let spender_id: AccountId = format!("{}{}", index, "a".repeat(63)).parse().unwrap();
+            let key = allowance_key(&tmp_account_id, &spender_id);
+            self.allowances.insert(&key, &Balance::MAX);
+            self.allowance_expiries.insert(&key, &u64::MAX);
+            spenders.insert(&spender_id);
+        }
+        self.spenders.insert(&tmp_account_id, &spenders);
+        self.storage_deposits.insert(&tmp_account_id, &Balance::MAX);
+        self.account_storage_usage = env::storage_usage() - initial_storage_usage;
+        self.balances.remove(&tmp_account_id);
+        self.storage_deposits.remove(&tmp_account_id);
+        self.internal_clear_balance_checkpoints(&tmp_account_id);
+        self.internal_clear_vote_checkpoints(&tmp_account_id);
+        self.internal_clear_allowances(&tmp_account_id);
+    }
+
+    /// Returns the balance of `account_id`, or `None` if it isn't registered
+    fn internal_balance_of(&self, account_id: &AccountId) -> Option<Balance> {
+        self.balances
+            .get(account_id)
+            .or_else(|| self.legacy_balances.get(account_id))
+            .map(|shares| self.balance_from_shares(account_id, shares))
+    }
+
+    /// Returns true if `account_id` has paid for its storage
+    fn internal_is_registered(&self, account_id: &AccountId) -> bool {
+        self.internal_balance_of(account_id).is_some()
+    }
+
+    /// Writes the balance of `account_id` as shares, moving it out of the legacy map if needed.
+    /// The change in shares is recorded in the balance checkpoints and moves the votes
+    fn internal_set_balance(&mut self, account_id: &AccountId, balance: Balance) {
+        let shares = self.shares_from_balance(account_id, balance);
+        let old_shares = self
+            .balances
+            .insert(account_id, &shares)
+            .or_else(|| self.legacy_balances.remove(account_id))
+            .unwrap_or(0);
+        if shares == old_shares {
+            return;
+        }
+        self.internal_checkpoint_balance(account_id, old_shares, shares);
+        if crate::rebase::is_rebased(account_id) {
+            let delegatee = self.internal_delegatee(account_id);
+            if shares > old_shares {
+                self.internal_move_votes(None, Some(&delegatee), shares - old_shares);
+            } else {
+                self.internal_move_votes(Some(&delegatee), None, old_shares - shares);
+            }
+        }
+    }
+
+    /// Removes the balance entry of `account_id`, unregistering it
+    fn internal_remove_balance(&mut self, account_id: &AccountId) {
+        self.balances.remove(account_id);
+        self.legacy_balances.remove(account_id);
+    }
+
+    /// Returns the balance of a registered account, panics if it isn't registered
+    fn internal_unwrap_balance_of(&self, account_id: &AccountId) -> Balance {
+        match self.internal_balance_of(account_id) {
+            Some(balance) => balance,
+            None => env::panic_str(&format!("The account {} is not registered", account_id)),
+        }
     }
 
     /// Internal implementation of deposit to an account
     fn internal_deposit(&mut self, account_id: &AccountId, amount: Balance) {
-        let balance = self.balances.get(&account_id).unwrap_or(0);
-        self.balances.insert(&account_id, &(balance + amount));
+        let balance = self.internal_unwrap_balance_of(account_id);
+        let new_balance = balance
+            .checked_add(amount)
+            .unwrap_or_else(|| env::panic_str("Balance overflow"));
+        self.internal_set_balance(account_id, new_balance);
     }
 
     /// Internal implementation of withdraw from an account
     fn internal_withdraw(&mut self, account_id: &AccountId, amount: Balance) {
-        let balance = self.balances.get(&account_id).unwrap_or(0);
+        let balance = self.internal_unwrap_balance_of(account_id);
         assert!(balance >= amount, "Insufficient balance");
-        self.balances.insert(&account_id, &(balance - amount));
+        let new_balance = balance - amount;
+        self.internal_set_balance(account_id, new_balance);
+    }
+
+    /// Internal implementation of burning tokens from an account
+    fn internal_burn(&mut self, account_id: &AccountId, amount: Balance) {
+        self.internal_withdraw(account_id, amount);
+        self.total_supply = self
+            .total_supply
+            .checked_sub(amount)
+            .unwrap_or_else(|| env::panic_str("Total supply underflow"));
+        self.total_burned = self.total_burned.saturating_add(amount);
+        self.internal_checkpoint_total_supply();
+        log!("Burned {} tokens from {}", amount, account_id);
+        FtBurn { owner_id: account_id, amount: U128(amount), memo: None }.emit();
+    }
+
+    fn internal_transfer_result(
+        &self,
+        from: AccountId,
+        to: AccountId,
+        received: Balance,
+    ) -> TransferResult {
+        TransferResult {
+            new_sender_balance: U128(self.internal_unwrap_balance_of(&from)),
+            new_receiver_balance: U128(self.internal_unwrap_balance_of(&to)),
+            from,
+            to,
+            amount: U128(received),
+        }
     }
 
-    /// Internal implementation of transfer between accounts
+    /// Internal implementation of transfer between accounts.
+    /// Returns the amount credited to the receiver after the transfer fee
     fn internal_transfer(
         &mut self,
         sender_id: &AccountId,
         receiver_id: &AccountId,
         amount: Balance,
         memo: Option<String>,
-    ) {
+    ) -> Balance {
         assert_ne!(sender_id, receiver_id, "Cannot transfer to yourself");
+        self.assert_not_self_transfer(receiver_id);
         assert!(amount > 0, "The amount should be a positive number");
+        self.assert_not_blacklisted(sender_id);
+        self.assert_not_blacklisted(receiver_id);
+        self.assert_trading_allowed(sender_id, receiver_id);
+        self.internal_enforce_transfer_cooldown(sender_id);
+
+        let fee = self.internal_transfer_fee(sender_id, receiver_id, amount);
+        let received = amount - fee;
+        self.internal_enforce_transfer_limits(sender_id, receiver_id, amount, received);
         self.internal_withdraw(sender_id, amount);
-        self.internal_deposit(receiver_id, amount);
-        if let Some(memo_text) = memo {
-            log!("Memo: {}", memo_text);
+        self.internal_deposit(receiver_id, received);
+        log!("Transfer {} from {} to {}", received, sender_id, receiver_id);
+        FtTransfer {
+            old_owner_id: sender_id,
+            new_owner_id: receiver_id,
+            amount: U128(received),
+            memo: memo.as_deref(),
+        }
+        .emit();
+        self.internal_notify_transfer_hook(sender_id, receiver_id, received);
+
+        if fee > 0 {
+            let fee_collector = self.fee_collector.clone();
+            self.internal_deposit(&fee_collector, fee);
+            log!("Transfer fee {} from {} to {}", fee, sender_id, fee_collector);
+            FtTransfer {
+                old_owner_id: sender_id,
+                new_owner_id: &fee_collector,
+                amount: U128(fee),
+                memo: Some("transfer fee"),
+            }
+            .emit();
         }
-        log!("Transfer {} from {} to {}", amount, sender_id, receiver_id);
+        received
     }
 
-    /// Internal implementation of getting allowance
+    /// Internal implementation of getting allowance, zero once it has expired
     fn internal_get_allowance(&self, owner_id: &AccountId, spender_id: &AccountId) -> U128 {
-        match self.allowances.get(&owner_id) {
-            Some(allowances) => U128(allowances.get(spender_id).cloned().unwrap_or(0)),
-            None => U128(0),
+        let key = allowance_key(owner_id, spender_id);
+        if self.internal_is_allowance_expired(&key) {
+            return U128(0);
+        }
+        let amount = self
+            .allowances
+            .get(&key)
+            .or_else(|| {
+                self.legacy_allowances
+                    .get(owner_id)
+                    .and_then(|allowances| allowances.get(spender_id).cloned())
+            })
+            .unwrap_or(0);
+        U128(amount)
+    }
+
+    fn internal_is_allowance_expired(&self, key: &CryptoHash) -> bool {
+        self.allowance_expiries
+            .get(key)
+            .is_some_and(|expires_at| env::block_timestamp() > expires_at)
+    }
+
+    /// Drop the legacy entry for a pair once it lives under its composite key
+    fn internal_remove_legacy_allowance(&mut self, owner_id: &AccountId, spender_id: &AccountId) {
+        if let Some(mut allowances) = self.legacy_allowances.get(owner_id) {
+            if allowances.remove(spender_id).is_some() {
+                if allowances.is_empty() {
+                    self.legacy_allowances.remove(owner_id);
+                } else {
+                    self.legacy_allowances.insert(owner_id, &allowances);
+                }
+            }
+        }
+    }
+
+    /// Replace the allowance along with its expiry, `None` meaning it never expires
+    pub(crate) fn internal_approve_until(
+        &mut self,
+        owner_id: &AccountId,
+        spender_id: &AccountId,
+        amount: Balance,
+        expires_at: Option<u64>,
+    ) -> bool {
+        let key = allowance_key(owner_id, spender_id);
+        match expires_at {
+            Some(expires_at) => {
+                assert!(expires_at > env::block_timestamp(), "Expiry must be in the future");
+                self.allowance_expiries.insert(&key, &expires_at);
+            }
+            None => {
+                self.allowance_expiries.remove(&key);
+            }
         }
+        self.internal_approve(owner_id, spender_id, amount)
     }
 
     /// Internal implementation of approving allowance
@@ -276,9 +1396,13 @@ impl UnrealToken {
         spender_id: &AccountId,
         amount: Balance,
     ) -> bool {
-        let mut allowances = self.allowances.get(&owner_id).unwrap_or_else(|| HashMap::new());
-        allowances.insert(spender_id.clone(), amount);
-        self.allowances.insert(&owner_id, &allowances);
+        self.assert_not_blacklisted(owner_id);
+        self.assert_not_blacklisted(spender_id);
+        // Registration pays for the allowances an owner approves
+        self.internal_unwrap_balance_of(owner_id);
+        self.allowances.insert(&allowance_key(owner_id, spender_id), &amount);
+        self.internal_remove_legacy_allowance(owner_id, spender_id);
+        self.internal_update_spenders(owner_id, spender_id, amount);
         log!(
             "Approval: Owner: {} approved Spender: {} to use {} tokens",
             owner_id, spender_id, amount
@@ -293,10 +1417,1810 @@ impl UnrealToken {
         spender_id: &AccountId,
         amount: Balance,
     ) {
+        assert!(
+            !self.internal_is_allowance_expired(&allowance_key(owner_id, spender_id)),
+            "Allowance expired"
+        );
         let allowance = self.internal_get_allowance(owner_id, spender_id).0;
-        assert!(allowance >= amount, "Insufficient allowance");
-        let mut allowances = self.allowances.get(&owner_id).unwrap_or_else(|| HashMap::new());
-        allowances.insert(spender_id.clone(), allowance - amount);
-        self.allowances.insert(&owner_id, &allowances);
+        let new_allowance = allowance.checked_sub(amount).unwrap_or_else(|| {
+            env::panic_str(&format!(
+                "Insufficient allowance (allowance: {}, requested: {})",
+                allowance, amount
+            ))
+        });
+        self.allowances.insert(&allowance_key(owner_id, spender_id), &new_allowance);
+        self.internal_remove_legacy_allowance(owner_id, spender_id);
+        self.internal_update_spenders(owner_id, spender_id, new_allowance);
+    }
+
+    /// Lists the spender while its allowance is non-zero, dropping it once used up or revoked.
+    /// An owner can list at most `MAX_SPENDERS_PER_OWNER` spenders
+    fn internal_update_spenders(
+        &mut self,
+        owner_id: &AccountId,
+        spender_id: &AccountId,
+        allowance: Balance,
+    ) {
+        let mut spenders = self
+            .spenders
+            .get(owner_id)
+            .unwrap_or_else(|| UnorderedSet::new(spenders_prefix(owner_id)));
+        if allowance > 0 {
+            assert!(
+                spenders.contains(spender_id) || spenders.len() < MAX_SPENDERS_PER_OWNER,
+                "Too many spenders approved, revoke one first"
+            );
+            spenders.insert(spender_id);
+        } else {
+            spenders.remove(spender_id);
+        }
+        if spenders.is_empty() {
+            self.spenders.remove(owner_id);
+        } else {
+            self.spenders.insert(owner_id, &spenders);
+        }
+    }
+
+    /// Drops every allowance `owner_id` approved, when it unregisters
+    fn internal_clear_allowances(&mut self, owner_id: &AccountId) {
+        if let Some(mut spenders) = self.spenders.remove(owner_id) {
+            for spender_id in spenders.to_vec() {
+                let key = allowance_key(owner_id, &spender_id);
+                self.allowances.remove(&key);
+                self.allowance_expiries.remove(&key);
+            }
+            spenders.clear();
+        }
+        self.legacy_allowances.remove(owner_id);
+    }
+}
+
+/// Rejects metadata that would break listings and price calculations: an empty name,
+/// a symbol that isn't 1 to `MAX_SYMBOL_LENGTH` uppercase alphanumerics, or more than
+/// `MAX_DECIMALS` decimals
+fn assert_valid_metadata(name: &str, symbol: &str, decimals: u8) {
+    assert!(!name.trim().is_empty(), "Name cannot be empty");
+    assert!(!symbol.is_empty(), "Symbol cannot be empty");
+    assert!(
+        symbol.len() <= MAX_SYMBOL_LENGTH,
+        "Symbol cannot be longer than {} characters",
+        MAX_SYMBOL_LENGTH
+    );
+    assert!(
+        symbol.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()),
+        "Symbol must only contain uppercase letters and digits"
+    );
+    assert!(decimals <= MAX_DECIMALS, "Decimals cannot exceed {}", MAX_DECIMALS);
+}
+
+fn encode_base64(bytes: &Base64VecU8) -> String {
+    use near_sdk::base64::{engine::general_purpose::STANDARD, Engine};
+    STANDARD.encode(&bytes.0)
+}
+
+/// Composite storage key for the allowance `spender_id` has over `owner_id`'s tokens.
+/// Account IDs can't contain ':', so the separator keeps the key unambiguous
+fn allowance_key(owner_id: &AccountId, spender_id: &AccountId) -> CryptoHash {
+    env::sha256_array(&[owner_id.as_bytes(), b":", spender_id.as_bytes()].concat())
+}
+
+/// Storage prefix of the `UnorderedSet` of spenders approved by `owner_id`
+fn spenders_prefix(owner_id: &AccountId) -> Vec<u8> {
+    [b"A".as_slice(), &env::sha256_array(owner_id.as_bytes())].concat()
+}
+
+/// Interface a contract must implement to receive tokens via `ft_transfer_call`
+#[ext_contract(ext_ft_receiver)]
+pub trait FungibleTokenReceiver {
+    fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, NearToken};
+
+    /// Deploys the token as `accounts(0)` with `accounts(1)` as the owner
+    fn setup() -> (VMContextBuilder, UnrealToken) {
+        setup_with_max_supply(None)
+    }
+
+    fn setup_with_max_supply(max_supply: Option<U128>) -> (VMContextBuilder, UnrealToken) {
+        let (context, mut contract) = deploy(max_supply);
+        contract.enable_trading();
+        (context, contract)
+    }
+
+    /// Deploys the token without enabling trading
+    fn deploy(max_supply: Option<U128>) -> (VMContextBuilder, UnrealToken) {
+        let mut context = VMContextBuilder::new();
+        context
+            .current_account_id(accounts(0))
+            .predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        (context, UnrealToken::new(max_supply, None))
+    }
+
+    /// Registers `account_id` paying the exact storage cost, leaving the owner as predecessor
+    fn register(context: &mut VMContextBuilder, contract: &mut UnrealToken, account_id: AccountId) {
+        let min_balance = contract.storage_balance_bounds().min.0;
+        testing_env!(context
+            .attached_deposit(NearToken::from_yoctonear(min_balance))
+            .build());
+        contract.storage_deposit(Some(account_id), None);
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(NearToken::from_yoctonear(0))
+            .build());
+    }
+
+    #[test]
+    fn test_mint_up_to_max_supply() {
+        let (_, mut contract) = setup();
+        let remaining = u128::MAX - contract.total_supply().0;
+        contract.mint(accounts(1), U128(remaining));
+        assert_eq!(contract.total_supply().0, u128::MAX);
+        assert_eq!(contract.balance_of(accounts(1)).0, u128::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "Total supply overflow")]
+    fn test_mint_total_supply_overflow() {
+        let (_, mut contract) = setup();
+        let remaining = u128::MAX - contract.total_supply().0;
+        contract.mint(accounts(1), U128(remaining));
+        contract.mint(accounts(1), U128(1));
+    }
+
+    #[test]
+    fn test_mint_exactly_to_max_supply() {
+        let (_, mut contract) = setup_with_max_supply(Some(U128(u128::MAX / 2)));
+        let remaining = u128::MAX / 2 - contract.total_supply().0;
+        contract.mint(accounts(1), U128(remaining));
+        assert_eq!(contract.total_supply(), contract.max_supply().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Max supply exceeded")]
+    fn test_mint_over_max_supply() {
+        let (_, mut contract) = setup_with_max_supply(Some(U128(u128::MAX / 2)));
+        let remaining = u128::MAX / 2 - contract.total_supply().0;
+        contract.mint(accounts(1), U128(remaining + 1));
+    }
+
+    #[test]
+    fn test_burn_then_mint_respects_max_supply() {
+        let (_, mut contract) = setup_with_max_supply(Some(U128(u128::MAX / 2)));
+        let remaining = u128::MAX / 2 - contract.total_supply().0;
+        contract.mint(accounts(1), U128(remaining));
+        contract.burn(accounts(1), U128(100));
+        contract.mint(accounts(1), U128(100));
+        assert_eq!(contract.total_supply().0, u128::MAX / 2);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.mint(accounts(1), U128(1));
+        }));
+        assert!(result.is_err());
+    }
+
+    /// Sets a 1% fee collected by `accounts(3)` and funds `accounts(2)` with 1000 tokens
+    fn setup_with_fee() -> (VMContextBuilder, UnrealToken) {
+        let (mut context, mut contract) = setup();
+        for account_id in [accounts(2), accounts(3), accounts(4)] {
+            register(&mut context, &mut contract, account_id);
+        }
+        contract.set_transfer_fee(100, accounts(3));
+        contract.transfer(accounts(2), U128(1000));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        (context, contract)
+    }
+
+    #[test]
+    fn test_transfer_fee_is_taken() {
+        let (_, mut contract) = setup_with_fee();
+        contract.transfer(accounts(4), U128(500));
+        assert_eq!(contract.balance_of(accounts(4)).0, 495);
+        assert_eq!(contract.balance_of(accounts(3)).0, 5);
+        assert_eq!(contract.balance_of(accounts(2)).0, 500);
+    }
+
+    #[test]
+    fn test_transfer_fee_rounds_down_at_small_amounts() {
+        let (_, mut contract) = setup_with_fee();
+        contract.transfer(accounts(4), U128(99));
+        assert_eq!(contract.balance_of(accounts(4)).0, 99);
+        assert_eq!(contract.balance_of(accounts(3)).0, 0);
+
+        contract.transfer(accounts(4), U128(199));
+        assert_eq!(contract.balance_of(accounts(4)).0, 99 + 198);
+        assert_eq!(contract.balance_of(accounts(3)).0, 1);
+    }
+
+    #[test]
+    fn test_transfer_fee_exempt_account() {
+        let (mut context, mut contract) = setup_with_fee();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.add_fee_exempt(accounts(2));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.transfer(accounts(4), U128(500));
+        assert_eq!(contract.balance_of(accounts(4)).0, 500);
+        assert_eq!(contract.balance_of(accounts(3)).0, 0);
+    }
+
+    #[test]
+    fn test_transfer_fee_collector_is_exempt() {
+        let (mut context, mut contract) = setup_with_fee();
+        assert!(contract.is_fee_exempt(accounts(3)));
+        contract.transfer(accounts(3), U128(500));
+        assert_eq!(contract.balance_of(accounts(3)).0, 500);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.transfer(accounts(4), U128(500));
+        assert_eq!(contract.balance_of(accounts(4)).0, 500);
+        assert_eq!(contract.balance_of(accounts(3)).0, 0);
+    }
+
+    #[test]
+    fn test_update_metadata_partial() {
+        let (_, mut contract) = setup();
+        contract.update_metadata(None, Some("UNRL".to_string()), None, None, None, None);
+
+        let metadata = contract.ft_metadata();
+        assert_eq!(metadata.symbol, "UNRL");
+        assert_eq!(contract.symbol(), "UNRL");
+        assert_eq!(metadata.name, "Unreal Token");
+        assert_eq!(contract.name(), "Unreal Token");
+        assert_eq!(metadata.icon, None);
+        assert_eq!(metadata.decimals, 18);
+
+        let icon = "data:image/svg+xml,<svg/>".to_string();
+        contract.update_metadata(None, None, Some(icon), None, None, None);
+        let metadata = contract.ft_metadata();
+        assert_eq!(metadata.icon.as_deref(), Some("data:image/svg+xml,<svg/>"));
+        assert_eq!(metadata.symbol, "UNRL");
+        assert_eq!(metadata.reference, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Decimals cannot be changed")]
+    fn test_update_metadata_rejects_decimals() {
+        let (_, mut contract) = setup();
+        contract.update_metadata(None, None, None, None, None, Some(6));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can call this method")]
+    fn test_update_metadata_owner_only() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.update_metadata(Some("Fake".to_string()), None, None, None, None, None);
+    }
+
+    #[test]
+    fn test_recover_accidental_near_deposit() {
+        let (mut context, mut contract) = setup();
+        let storage_cost =
+            Balance::from(env::storage_usage()) * env::storage_byte_cost().as_yoctonear();
+        let accidental_deposit = NearToken::from_near(5).as_yoctonear();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .account_balance(NearToken::from_yoctonear(
+                storage_cost + CONTRACT_STORAGE_COST + accidental_deposit
+            ))
+            .build());
+
+        assert_eq!(contract.recoverable_near().0, accidental_deposit);
+        contract.recover_near(U128(accidental_deposit), accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot recover NEAR reserved for storage and operations")]
+    fn test_recover_near_keeps_reserve() {
+        let (mut context, mut contract) = setup();
+        let storage_cost =
+            Balance::from(env::storage_usage()) * env::storage_byte_cost().as_yoctonear();
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .account_balance(NearToken::from_yoctonear(storage_cost + CONTRACT_STORAGE_COST))
+            .build());
+
+        contract.recover_near(U128(1), accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot recover this token's own balances")]
+    fn test_recover_ft_rejects_own_token() {
+        let (_, mut contract) = setup();
+        contract.recover_ft(accounts(0), U128(1), accounts(2));
+    }
+
+    #[test]
+    fn test_transfer_from_to_self_keeps_allowance() {
+        let (mut context, mut contract) = setup();
+        contract.approve(accounts(2), U128(100), None);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer_from(accounts(1), accounts(1), U128(10));
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.allowance(accounts(1), accounts(2)).0, 100);
+    }
+
+    #[test]
+    fn test_transfer_from_zero_amount_keeps_allowance() {
+        let (mut context, mut contract) = setup();
+        contract.approve(accounts(2), U128(100), None);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer_from(accounts(1), accounts(3), U128(0));
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.allowance(accounts(1), accounts(2)).0, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "Balance overflow")]
+    fn test_deposit_balance_overflow() {
+        let (_, mut contract) = setup();
+        contract.internal_deposit(&accounts(1), u128::MAX);
+    }
+
+    /// Funds `accounts(2)` with 1000 tokens and sets the given limits, leaving it as predecessor
+    fn setup_with_limits(
+        max_tx_amount: Option<u128>,
+        max_wallet: Option<u128>,
+    ) -> (VMContextBuilder, UnrealToken) {
+        let (mut context, mut contract) = setup();
+        for account_id in [accounts(2), accounts(3)] {
+            register(&mut context, &mut contract, account_id);
+        }
+        contract.transfer(accounts(2), U128(1000));
+        contract.set_limits(max_tx_amount.map(U128), max_wallet.map(U128));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        (context, contract)
+    }
+
+    #[test]
+    fn test_transfer_at_max_tx_amount() {
+        let (_, mut contract) = setup_with_limits(Some(100), None);
+        contract.transfer(accounts(3), U128(100));
+        assert_eq!(contract.balance_of(accounts(3)).0, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "Transfer amount exceeds the max transaction limit")]
+    fn test_transfer_above_max_tx_amount() {
+        let (_, mut contract) = setup_with_limits(Some(100), None);
+        contract.transfer(accounts(3), U128(101));
+    }
+
+    #[test]
+    fn test_transfer_up_to_max_wallet() {
+        let (_, mut contract) = setup_with_limits(None, Some(300));
+        contract.transfer(accounts(3), U128(200));
+        contract.transfer(accounts(3), U128(100));
+        assert_eq!(contract.balance_of(accounts(3)).0, 300);
+    }
+
+    #[test]
+    #[should_panic(expected = "Receiver balance would exceed the max wallet limit")]
+    fn test_transfer_above_max_wallet() {
+        let (_, mut contract) = setup_with_limits(None, Some(300));
+        contract.transfer(accounts(3), U128(200));
+        contract.transfer(accounts(3), U128(101));
+    }
+
+    #[test]
+    fn test_limits_exempt_accounts() {
+        let (mut context, mut contract) = setup_with_limits(Some(100), Some(300));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.add_limits_exempt(accounts(2));
+        contract.add_limits_exempt(accounts(3));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.transfer(accounts(3), U128(500));
+        assert_eq!(contract.balance_of(accounts(3)).0, 500);
+    }
+
+    /// Funds `accounts(2)` before trading is enabled, leaving it as predecessor
+    fn setup_before_launch() -> (VMContextBuilder, UnrealToken) {
+        let (mut context, mut contract) = deploy(None);
+        for account_id in [accounts(2), accounts(3)] {
+            register(&mut context, &mut contract, account_id);
+        }
+        contract.transfer(accounts(2), U128(1000));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        (context, contract)
+    }
+
+    #[test]
+    #[should_panic(expected = "Trading is not enabled yet")]
+    fn test_transfer_before_trading_enabled() {
+        let (_, mut contract) = setup_before_launch();
+        contract.transfer(accounts(3), U128(100));
+    }
+
+    #[test]
+    fn test_whitelisted_transfer_before_trading_enabled() {
+        let (mut context, mut contract) = setup_before_launch();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.add_to_whitelist(accounts(3));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.transfer(accounts(3), U128(100));
+        assert_eq!(contract.balance_of(accounts(3)).0, 100);
+    }
+
+    #[test]
+    fn test_enable_trading() {
+        let (mut context, mut contract) = setup_before_launch();
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.enable_trading();
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.transfer(accounts(3), U128(100));
+        assert_eq!(contract.balance_of(accounts(3)).0, 100);
+    }
+
+    #[test]
+    fn test_transfer_from_before_allowance_expiry() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(3));
+        contract.approve(accounts(2), U128(100), Some(1_000));
+        assert_eq!(contract.allowance_expires_at(accounts(1), accounts(2)), Some(1_000));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).block_timestamp(1_000).build());
+        contract.transfer_from(accounts(1), accounts(3), U128(40));
+        assert_eq!(contract.balance_of(accounts(3)).0, 40);
+        assert_eq!(contract.allowance(accounts(1), accounts(2)).0, 60);
+    }
+
+    #[test]
+    #[should_panic(expected = "Allowance expired")]
+    fn test_transfer_from_after_allowance_expiry() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(3));
+        contract.approve(accounts(2), U128(100), Some(1_000));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).block_timestamp(1_001).build());
+        assert_eq!(contract.allowance(accounts(1), accounts(2)).0, 0);
+        contract.transfer_from(accounts(1), accounts(3), U128(40));
+    }
+
+    #[test]
+    fn test_increase_expired_allowance() {
+        let (mut context, mut contract) = setup();
+        contract.approve(accounts(2), U128(100), Some(1_000));
+
+        testing_env!(context.block_timestamp(1_001).build());
+        contract.increase_allowance(accounts(2), U128(50));
+        assert_eq!(contract.allowance(accounts(1), accounts(2)).0, 50);
+        assert_eq!(contract.allowance_expires_at(accounts(1), accounts(2)), None);
+    }
+
+    #[test]
+    fn test_transfer_from_over_allowance_reports_amounts() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(3));
+        contract.approve(accounts(2), U128(100), None);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.transfer_from(accounts(1), accounts(3), U128(250));
+        }));
+        let error = result.unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(message.contains("allowance: 100"));
+        assert!(message.contains("requested: 250"));
+        assert_eq!(contract.allowance(accounts(1), accounts(2)).0, 100);
+    }
+
+    #[test]
+    fn test_approve_without_expiry_clears_deadline() {
+        let (mut context, mut contract) = setup();
+        contract.approve(accounts(2), U128(100), Some(1_000));
+        contract.approve(accounts(2), U128(100), None);
+        assert_eq!(contract.allowance_expires_at(accounts(1), accounts(2)), None);
+
+        testing_env!(context.block_timestamp(u64::MAX).build());
+        assert_eq!(contract.allowance(accounts(1), accounts(2)).0, 100);
+    }
+
+    const PERMIT_KEY: &str = "ed25519:GmaDrppBC7P5ARKV8g3djiwP89vz1jLK23V2GBjuAEGB";
+    /// `PERMIT_KEY` signing "alice:permit:bob:charlie:100:0:1000"
+    const PERMIT_SIGNATURE: &str = "2fc21c3d4d52294e7d6e03c6d0ac75e9058e5489d8d31230bc2bddd72e36f2df\
+        277ad4d32c18a9c6f4123572e00c2d89900beec9cb429c39af8ba809937b4206";
+
+    #[test]
+    fn test_permit_sets_allowance() {
+        let (mut context, mut contract) = setup();
+        contract.set_permit_key(Some(PERMIT_KEY.parse().unwrap()));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let permitted = contract.permit(
+            accounts(1),
+            accounts(2),
+            U128(100),
+            1_000,
+            PERMIT_KEY.to_string(),
+            PERMIT_SIGNATURE.to_string(),
+        );
+        assert!(permitted);
+        assert_eq!(contract.allowance(accounts(1), accounts(2)).0, 100);
+        assert_eq!(contract.permit_nonce(accounts(1)), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid signature")]
+    fn test_permit_replay() {
+        let (_, mut contract) = setup();
+        contract.set_permit_key(Some(PERMIT_KEY.parse().unwrap()));
+        assert_eq!(contract.nonces(accounts(1)), 0);
+        let permit = |contract: &mut UnrealToken| {
+            contract.permit(
+                accounts(1),
+                accounts(2),
+                U128(100),
+                1_000,
+                PERMIT_KEY.to_string(),
+                PERMIT_SIGNATURE.to_string(),
+            )
+        };
+        permit(&mut contract);
+        assert_eq!(contract.nonces(accounts(1)), 1);
+        // The signature was for nonce 0, which is consumed
+        permit(&mut contract);
+    }
+
+    #[test]
+    #[should_panic(expected = "Public key is not authorized for owner")]
+    fn test_permit_with_unregistered_key() {
+        let (_, mut contract) = setup();
+        contract.permit(
+            accounts(1),
+            accounts(2),
+            U128(100),
+            1_000,
+            PERMIT_KEY.to_string(),
+            PERMIT_SIGNATURE.to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid signature")]
+    fn test_permit_with_changed_amount() {
+        let (_, mut contract) = setup();
+        contract.set_permit_key(Some(PERMIT_KEY.parse().unwrap()));
+        contract.permit(
+            accounts(1),
+            accounts(2),
+            U128(1_000),
+            1_000,
+            PERMIT_KEY.to_string(),
+            PERMIT_SIGNATURE.to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Permit expired")]
+    fn test_permit_after_deadline() {
+        let (mut context, mut contract) = setup();
+        contract.set_permit_key(Some(PERMIT_KEY.parse().unwrap()));
+        testing_env!(context.block_timestamp(1_001).build());
+        contract.permit(
+            accounts(1),
+            accounts(2),
+            U128(100),
+            1_000,
+            PERMIT_KEY.to_string(),
+            PERMIT_SIGNATURE.to_string(),
+        );
+    }
+
+    /// Caps minting to 1000 tokens per day
+    fn setup_with_mint_cap() -> (VMContextBuilder, UnrealToken) {
+        let (context, mut contract) = setup();
+        contract.set_mint_rate_limit(86_400, U128(1_000));
+        (context, contract)
+    }
+
+    #[test]
+    fn test_mint_up_to_epoch_cap() {
+        let (_, mut contract) = setup_with_mint_cap();
+        contract.mint(accounts(1), U128(600));
+        assert_eq!(contract.remaining_mint_budget().0, 400);
+        contract.mint(accounts(1), U128(400));
+        assert_eq!(contract.remaining_mint_budget().0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mint cap for this epoch exceeded")]
+    fn test_mint_over_epoch_cap() {
+        let (_, mut contract) = setup_with_mint_cap();
+        contract.mint(accounts(1), U128(600));
+        contract.mint(accounts(1), U128(401));
+    }
+
+    #[test]
+    fn test_mint_budget_resets_in_new_epoch() {
+        let (mut context, mut contract) = setup_with_mint_cap();
+        contract.mint(accounts(1), U128(1_000));
+
+        testing_env!(context.block_timestamp(86_400 * 1_000_000_000).build());
+        assert_eq!(contract.remaining_mint_budget().0, 1_000);
+        contract.mint(accounts(1), U128(1_000));
+        assert_eq!(contract.remaining_mint_budget().0, 0);
+    }
+
+    /// Airdrops 100 tokens to `accounts(2)` and 200 to `accounts(3)`, claimable until
+    /// timestamp 1000. Returns the proofs of both, leaving the owner as predecessor
+    fn setup_with_airdrop(
+        context: &mut VMContextBuilder,
+        contract: &mut UnrealToken,
+    ) -> (Vec<CryptoHash>, Vec<CryptoHash>) {
+        use crate::airdrop::{airdrop_leaf, hash_pair};
+
+        register(context, contract, accounts(2));
+        register(context, contract, accounts(3));
+        let leaf_2 = airdrop_leaf(&accounts(2), 100);
+        let leaf_3 = airdrop_leaf(&accounts(3), 200);
+        contract.set_airdrop_root(hash_pair(leaf_2, leaf_3), U128(300), 1_000);
+        (vec![leaf_3], vec![leaf_2])
+    }
+
+    #[test]
+    fn test_claim_airdrop() {
+        let (mut context, mut contract) = setup();
+        let owner_balance = contract.balance_of(accounts(1)).0;
+        let (proof_2, _) = setup_with_airdrop(&mut context, &mut contract);
+        assert_eq!(contract.balance_of(accounts(1)).0, owner_balance - 300);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.claim_airdrop(U128(100), proof_2);
+        assert_eq!(contract.balance_of(accounts(2)).0, 100);
+        assert_eq!(contract.airdrop_remaining().0, 200);
+        assert!(contract.is_airdrop_claimed(accounts(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Airdrop already claimed")]
+    fn test_claim_airdrop_twice() {
+        let (mut context, mut contract) = setup();
+        let (proof_2, _) = setup_with_airdrop(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.claim_airdrop(U128(100), proof_2.clone());
+        contract.claim_airdrop(U128(100), proof_2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid Merkle proof")]
+    fn test_claim_airdrop_with_forged_amount() {
+        let (mut context, mut contract) = setup();
+        let (proof_2, _) = setup_with_airdrop(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.claim_airdrop(U128(200), proof_2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid Merkle proof")]
+    fn test_claim_airdrop_with_someone_elses_proof() {
+        let (mut context, mut contract) = setup();
+        let (_, proof_3) = setup_with_airdrop(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.claim_airdrop(U128(200), proof_3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Transfers are paused")]
+    fn test_claim_airdrop_while_paused() {
+        let (mut context, mut contract) = setup();
+        let (proof_2, _) = setup_with_airdrop(&mut context, &mut contract);
+        contract.pause();
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.claim_airdrop(U128(100), proof_2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account charlie is blacklisted")]
+    fn test_claim_airdrop_blacklisted() {
+        let (mut context, mut contract) = setup();
+        let (proof_2, _) = setup_with_airdrop(&mut context, &mut contract);
+        contract.blacklist_account(accounts(2));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.claim_airdrop(U128(100), proof_2);
+    }
+
+    #[test]
+    fn test_reclaim_unclaimed_airdrop_after_deadline() {
+        let (mut context, mut contract) = setup();
+        let owner_balance = contract.balance_of(accounts(1)).0;
+        let (proof_2, _) = setup_with_airdrop(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.claim_airdrop(U128(100), proof_2);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(1_001)
+            .build());
+        assert_eq!(contract.reclaim_unclaimed_airdrop().0, 200);
+        assert_eq!(contract.balance_of(accounts(1)).0, owner_balance - 100);
+        assert_eq!(contract.airdrop_root(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Airdrop is still open")]
+    fn test_reclaim_airdrop_before_deadline() {
+        let (mut context, mut contract) = setup();
+        setup_with_airdrop(&mut context, &mut contract);
+        contract.reclaim_unclaimed_airdrop();
+    }
+
+    #[test]
+    fn test_circulating_supply_excludes_treasury() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        register(&mut context, &mut contract, accounts(3));
+        contract.transfer(accounts(2), U128(1_000));
+        contract.transfer(accounts(3), U128(300));
+        assert_eq!(contract.treasury(), accounts(1));
+        assert_eq!(contract.circulating_supply().0, 1_300);
+
+        contract.add_circulating_excluded(accounts(3));
+        assert_eq!(contract.circulating_supply().0, 1_000);
+
+        // The owner's balance circulates once it no longer holds the treasury
+        contract.set_treasury(accounts(2));
+        assert_eq!(contract.circulating_supply().0, contract.total_supply().0 - 1_300);
+    }
+
+    #[test]
+    fn test_ft_mint_by_minter() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(3));
+        contract.add_minter(accounts(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.ft_mint(accounts(3), U128(100), Some("swap".to_string()));
+        assert_eq!(contract.balance_of(accounts(3)).0, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+    fn test_ft_transfer_rejects_over_attached_deposit() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(5)).build());
+        contract.ft_transfer(accounts(2), U128(100), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner or a minter can call this method")]
+    fn test_ft_mint_not_minter() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.ft_mint(accounts(2), U128(100), None);
+    }
+
+    #[test]
+    fn test_contract_version() {
+        let (_, contract) = setup();
+        let version = contract.contract_version();
+        let (crate_version, standards) = version.split_once(' ').unwrap();
+        assert_eq!(crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(crate_version.split('.').count(), 3);
+        assert_eq!(standards, "nep141+nep145+nep148+nep297");
+    }
+
+    #[test]
+    #[should_panic(expected = "Decimals cannot exceed 24")]
+    fn test_metadata_rejects_too_many_decimals() {
+        assert_valid_metadata("Unreal Token", "UNREAL", 25);
+    }
+
+    #[test]
+    #[should_panic(expected = "Symbol must only contain uppercase letters and digits")]
+    fn test_update_metadata_rejects_lowercase_symbol() {
+        let (_, mut contract) = setup();
+        contract.update_metadata(None, Some("unreal".to_string()), None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Symbol cannot be longer than 12 characters")]
+    fn test_update_metadata_rejects_long_symbol() {
+        let (_, mut contract) = setup();
+        contract.update_metadata(None, Some("UNREALUNREAL1".to_string()), None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Name cannot be empty")]
+    fn test_update_metadata_rejects_empty_name() {
+        let (_, mut contract) = setup();
+        contract.update_metadata(Some(String::new()), None, None, None, None, None);
+    }
+
+    #[test]
+    fn test_burn_from_within_allowance() {
+        let (mut context, mut contract) = setup();
+        let total_supply = contract.total_supply().0;
+        let owner_balance = contract.balance_of(accounts(1)).0;
+        contract.approve(accounts(2), U128(100), None);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.burn_from(accounts(1), U128(60));
+        assert_eq!(contract.balance_of(accounts(1)).0, owner_balance - 60);
+        assert_eq!(contract.total_supply().0, total_supply - 60);
+        assert_eq!(contract.allowance(accounts(1), accounts(2)).0, 40);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient allowance (allowance: 100, requested: 101)")]
+    fn test_burn_from_over_allowance() {
+        let (mut context, mut contract) = setup();
+        contract.approve(accounts(2), U128(100), None);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.burn_from(accounts(1), U128(101));
+    }
+
+    #[test]
+    fn test_total_minted_and_burned() {
+        let (mut context, mut contract) = setup();
+        let initial_supply = contract.total_supply().0;
+        assert_eq!(contract.total_minted().0, initial_supply);
+        assert_eq!(contract.total_burned().0, 0);
+
+        register(&mut context, &mut contract, accounts(2));
+        contract.mint(accounts(2), U128(500));
+        contract.burn(accounts(2), U128(200));
+        contract.burn_own(U128(100));
+        contract.approve(accounts(2), U128(50), None);
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.burn_from(accounts(1), U128(50));
+
+        assert_eq!(contract.total_minted().0, initial_supply + 500);
+        assert_eq!(contract.total_burned().0, 350);
+        assert_eq!(
+            contract.total_supply().0,
+            contract.total_minted().0 - contract.total_burned().0
+        );
+    }
+
+    #[test]
+    fn test_burn_batch() {
+        let (mut context, mut contract) = setup();
+        let total_supply = setup_with_two_holders(&mut context, &mut contract);
+
+        contract.burn_batch(vec![(accounts(1), U128(300)), (accounts(2), U128(200))]);
+        assert_eq!(contract.balance_of(accounts(1)).0, total_supply / 4 * 3 - 300);
+        assert_eq!(contract.balance_of(accounts(2)).0, total_supply / 4 - 200);
+        assert_eq!(contract.total_supply().0, total_supply - 500);
+
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(logs.iter().filter(|log| log.contains("\"event\":\"ft_burn\"")).count(), 2);
+    }
+
+    #[test]
+    fn test_burn_batch_with_underfunded_account() {
+        let (mut context, mut contract) = setup();
+        let total_supply = setup_with_two_holders(&mut context, &mut contract);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.burn_batch(vec![
+                (accounts(1), U128(300)),
+                (accounts(2), U128(total_supply / 4 + 1)),
+            ]);
+        }));
+        assert!(result.is_err());
+        assert_eq!(contract.balance_of(accounts(1)).0, total_supply / 4 * 3);
+        assert_eq!(contract.balance_of(accounts(2)).0, total_supply / 4);
+        assert_eq!(contract.total_supply().0, total_supply);
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate account")]
+    fn test_burn_batch_with_duplicate_account() {
+        let (mut context, mut contract) = setup();
+        setup_with_two_holders(&mut context, &mut contract);
+        contract.burn_batch(vec![(accounts(2), U128(1)), (accounts(2), U128(1))]);
+    }
+
+    #[test]
+    fn test_get_allowances() {
+        let (mut context, mut contract) = setup();
+        contract.approve(accounts(2), U128(100), None);
+        contract.approve(accounts(3), U128(200), None);
+        let allowances = contract.get_allowances(accounts(1), 0, 10);
+        assert_eq!(allowances.len(), 2);
+        assert!(allowances.contains(&(accounts(2), U128(100))));
+        assert!(allowances.contains(&(accounts(3), U128(200))));
+        assert_eq!(contract.get_allowances(accounts(1), 1, 10).len(), 1);
+        assert!(contract.get_allowances(accounts(2), 0, 10).is_empty());
+
+        // Revoked and used up allowances are pruned
+        contract.decrease_allowance(accounts(3), U128(500));
+        register(&mut context, &mut contract, accounts(4));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.transfer_from(accounts(1), accounts(4), U128(100));
+        assert!(contract.get_allowances(accounts(1), 0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "The account charlie is not registered")]
+    fn test_approve_unregistered_owner() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.approve(accounts(3), U128(100), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many spenders approved, revoke one first")]
+    fn test_approve_too_many_spenders() {
+        let (_, mut contract) = setup();
+        for index in 0..MAX_SPENDERS_PER_OWNER {
+            contract.approve(format!("spender{}.near", index).parse().unwrap(), U128(100), None);
+        }
+        // Changing an existing allowance is still allowed
+        contract.approve("spender0.near".parse().unwrap(), U128(200), None);
+        contract.approve(accounts(2), U128(100), None);
+    }
+
+    #[test]
+    fn test_unregister_drops_allowances() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.approve(accounts(3), U128(100), Some(1_000));
+
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(1)).build());
+        contract.storage_unregister(None);
+        let key = allowance_key(&accounts(2), &accounts(3));
+        assert!(contract.allowances.get(&key).is_none());
+        assert!(contract.allowance_expiries.get(&key).is_none());
+        assert!(contract.get_allowances(accounts(2), 0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_transfer_returns_new_balances() {
+        let (_, mut contract) = setup_with_fee();
+        let result = contract.transfer(accounts(4), U128(500));
+        assert_eq!(result.from, accounts(2));
+        assert_eq!(result.to, accounts(4));
+        assert_eq!(result.amount.0, 495);
+        assert_eq!(result.new_sender_balance.0, 500);
+        assert_eq!(result.new_receiver_balance.0, 495);
+    }
+
+    #[test]
+    fn test_update_metadata_reference_hash() {
+        let (_, mut contract) = setup();
+        let hash = Base64VecU8(env::sha256(b"{}"));
+        contract.update_metadata(
+            None,
+            None,
+            None,
+            Some("https://unreal.art/token.json".to_string()),
+            Some(hash.clone()),
+            None,
+        );
+        assert_eq!(contract.ft_metadata().reference_hash, Some(hash.clone()));
+        assert_eq!(contract.metadata_reference_hash(), Some(encode_base64(&hash)));
+
+        // A new reference without a hash drops the old one
+        contract.update_metadata(
+            None,
+            None,
+            None,
+            Some("https://unreal.art/token-v2.json".to_string()),
+            None,
+            None,
+        );
+        assert_eq!(contract.metadata_reference_hash(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Reference hash must be a 32 byte SHA-256 hash")]
+    fn test_update_metadata_rejects_short_reference_hash() {
+        let (_, mut contract) = setup();
+        contract.update_metadata(
+            None,
+            None,
+            None,
+            Some("https://unreal.art/token.json".to_string()),
+            Some(Base64VecU8(vec![0; 31])),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Reference hash requires a reference")]
+    fn test_update_metadata_reference_hash_without_reference() {
+        let (_, mut contract) = setup();
+        contract.update_metadata(None, None, None, None, Some(Base64VecU8(vec![0; 32])), None);
+    }
+
+    #[test]
+    fn test_balance_at_timestamp() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        let total_supply = contract.total_supply();
+        testing_env!(context.block_timestamp(1_000).build());
+        contract.transfer(accounts(2), U128(100));
+        contract.transfer(accounts(2), U128(200));
+
+        testing_env!(context.block_timestamp(2_000).build());
+        contract.transfer(accounts(2), U128(300));
+        contract.burn(accounts(1), U128(400));
+
+        testing_env!(context.block_timestamp(3_000).build());
+        // Changes within the same block share a single checkpoint
+        assert_eq!(contract.balance_of_at(accounts(2), 1_999).0, 300);
+        assert_eq!(contract.get_past_votes(accounts(2), 1_000).0, 300);
+        assert_eq!(contract.total_supply_at(1_000), total_supply);
+        assert_eq!(contract.balance_of_at(accounts(2), 999).0, 0);
+        assert_eq!(contract.balance_of_at(accounts(2), 2_000).0, 600);
+        assert_eq!(contract.total_supply_at(2_000).0, total_supply.0 - 400);
+    }
+
+    #[test]
+    #[should_panic(expected = "Timestamp 1000 is not in the past")]
+    fn test_balance_at_current_block() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        testing_env!(context.block_timestamp(1_000).build());
+        contract.transfer(accounts(2), U128(100));
+        contract.balance_of_at(accounts(2), 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "don't reach back to 1000")]
+    fn test_balance_at_overwritten_checkpoint() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        for block in 1..=crate::checkpoints::MAX_ACCOUNT_CHECKPOINTS + 1 {
+            testing_env!(context.block_timestamp(block * 1_000).build());
+            contract.transfer(accounts(2), U128(100));
+        }
+
+        testing_env!(context.block_timestamp(100_000).build());
+        // The latest checkpoints are kept, the first one was overwritten
+        let latest = crate::checkpoints::MAX_ACCOUNT_CHECKPOINTS + 1;
+        let balance = contract.balance_of_at(accounts(2), latest * 1_000);
+        assert_eq!(balance.0, Balance::from(latest) * 100);
+        assert_eq!(contract.balance_of_at(accounts(2), 2_000).0, 200);
+        contract.balance_of_at(accounts(2), 1_000);
+    }
+
+    #[test]
+    fn test_unregister_drops_checkpoints() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        contract.transfer(accounts(2), U128(100));
+        assert!(contract.balance_checkpoints.get(&accounts(2)).is_some());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.storage_unregister(Some(true));
+        assert!(contract.balance_checkpoints.get(&accounts(2)).is_none());
+        assert!(contract.vote_checkpoints.get(&accounts(2)).is_none());
+    }
+
+    /// Moves a quarter of the supply from the owner to `accounts(2)`, leaving the owner
+    /// as predecessor. Returns the total supply
+    fn setup_with_two_holders(context: &mut VMContextBuilder, contract: &mut UnrealToken) -> u128 {
+        register(context, contract, accounts(2));
+        let total_supply = contract.total_supply().0;
+        contract.transfer(accounts(2), U128(total_supply / 4));
+        total_supply
+    }
+
+    #[test]
+    fn test_distribute_rewards_pro_rata() {
+        let (mut context, mut contract) = setup();
+        let total_supply = setup_with_two_holders(&mut context, &mut contract);
+
+        contract.distribute_rewards(U128(1_000));
+        let minted = contract.distribute_rewards_batch(0, 10, U128(1_000));
+        assert_eq!(minted.0, 1_000);
+        assert_eq!(contract.balance_of(accounts(1)).0, total_supply / 4 * 3 + 750);
+        assert_eq!(contract.balance_of(accounts(2)).0, total_supply / 4 + 250);
+        assert_eq!(contract.total_supply().0, total_supply + 1_000);
+        assert!(contract.reward_distribution().is_none());
+    }
+
+    #[test]
+    fn test_distribute_rewards_resumes_across_batches() {
+        let (mut context, mut contract) = setup();
+        setup_with_two_holders(&mut context, &mut contract);
+
+        contract.distribute_rewards(U128(1_000));
+        assert_eq!(contract.distribute_rewards_batch(0, 1, U128(1_000)).0, 750);
+        let progress = contract.reward_distribution().unwrap();
+        assert_eq!(progress.next_index, 1);
+        assert_eq!(progress.distributed.0, 750);
+
+        assert_eq!(contract.distribute_rewards_batch(1, 1, U128(1_000)).0, 250);
+        assert!(contract.reward_distribution().is_none());
+    }
+
+    #[test]
+    fn test_distribute_rewards_uses_balances_at_opening() {
+        let (mut context, mut contract) = setup();
+        let total_supply = setup_with_two_holders(&mut context, &mut contract);
+
+        testing_env!(context.block_timestamp(1_000).build());
+        // Changes in the opening block itself don't count either
+        contract.transfer(accounts(2), U128(total_supply / 4));
+        contract.distribute_rewards(U128(1_000));
+        assert_eq!(contract.distribute_rewards_batch(0, 1, U128(1_000)).0, 750);
+        // Moving tokens between batches doesn't change the remaining shares
+        testing_env!(context.predecessor_account_id(accounts(2)).block_timestamp(2_000).build());
+        contract.transfer(accounts(1), U128(total_supply / 4));
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert_eq!(contract.distribute_rewards_batch(1, 1, U128(1_000)).0, 250);
+        assert_eq!(contract.total_supply().0, total_supply + 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't unregister during a reward distribution")]
+    fn test_unregister_during_reward_distribution() {
+        let (mut context, mut contract) = setup();
+        setup_with_two_holders(&mut context, &mut contract);
+        contract.distribute_rewards(U128(1_000));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.storage_unregister(Some(true));
+    }
+
+    #[test]
+    fn test_distribute_rewards_larger_than_supply() {
+        let (mut context, mut contract) = setup();
+        let total_supply = setup_with_two_holders(&mut context, &mut contract);
+
+        // `total_amount * balance` doesn't fit in 128 bits here
+        contract.distribute_rewards(U128(total_supply));
+        contract.distribute_rewards_batch(0, 10, U128(total_supply));
+        assert_eq!(contract.balance_of(accounts(2)).0, total_supply / 2);
+        assert_eq!(contract.total_supply().0, total_supply * 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch must start at the next holder index")]
+    fn test_distribute_rewards_batch_out_of_order() {
+        let (mut context, mut contract) = setup();
+        setup_with_two_holders(&mut context, &mut contract);
+
+        contract.distribute_rewards(U128(1_000));
+        contract.distribute_rewards_batch(0, 1, U128(1_000));
+        contract.distribute_rewards_batch(0, 1, U128(1_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "A reward distribution is already open")]
+    fn test_distribute_rewards_while_open() {
+        let (mut context, mut contract) = setup();
+        setup_with_two_holders(&mut context, &mut contract);
+
+        contract.distribute_rewards(U128(1_000));
+        contract.distribute_rewards(U128(1_000));
+    }
+
+    /// Sets a one hour timelock delay
+    fn setup_with_timelock() -> (VMContextBuilder, UnrealToken) {
+        let (context, mut contract) = setup();
+        contract.set_timelock_delay(3_600);
+        (context, contract)
+    }
+
+    fn queue_mint(contract: &mut UnrealToken) -> String {
+        use crate::timelock::TimelockedAction;
+
+        contract.queue_action(TimelockedAction::Mint { to: accounts(1), amount: U128(500) })
+    }
+
+    #[test]
+    fn test_execute_queued_mint_after_delay() {
+        let (mut context, mut contract) = setup_with_timelock();
+        let total_supply = contract.total_supply().0;
+        let action_id = queue_mint(&mut contract);
+        assert_eq!(contract.pending_action(action_id.clone()).unwrap().eta, 3_600 * 1_000_000_000);
+
+        testing_env!(context.block_timestamp(3_600 * 1_000_000_000).build());
+        contract.execute_action(action_id.clone());
+        assert_eq!(contract.total_supply().0, total_supply + 500);
+        assert!(contract.pending_action(action_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Timelock delay has not passed")]
+    fn test_execute_queued_mint_before_delay() {
+        let (mut context, mut contract) = setup_with_timelock();
+        let action_id = queue_mint(&mut contract);
+
+        testing_env!(context.block_timestamp(3_599 * 1_000_000_000).build());
+        contract.execute_action(action_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Action must be queued through the timelock")]
+    fn test_owner_mint_blocked_by_timelock() {
+        let (_, mut contract) = setup_with_timelock();
+        contract.mint(accounts(1), U128(500));
+    }
+
+    #[test]
+    #[should_panic(expected = "Action must be queued through the timelock")]
+    fn test_owner_ft_mint_blocked_by_timelock() {
+        let (mut context, mut contract) = setup_with_timelock();
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(1)).build());
+        contract.ft_mint(accounts(1), U128(500), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Action must be queued through the timelock")]
+    fn test_add_minter_blocked_by_timelock() {
+        let (_, mut contract) = setup_with_timelock();
+        contract.add_minter(accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Action must be queued through the timelock")]
+    fn test_rebase_blocked_by_timelock() {
+        let (_, mut contract) = setup_with_timelock();
+        contract.rebase(U128(1_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "Action must be queued through the timelock")]
+    fn test_distribute_rewards_blocked_by_timelock() {
+        let (_, mut contract) = setup_with_timelock();
+        contract.distribute_rewards(U128(1_000));
+    }
+
+    #[test]
+    fn test_execute_queued_add_minter() {
+        use crate::timelock::TimelockedAction;
+
+        let (mut context, mut contract) = setup_with_timelock();
+        let action_id =
+            contract.queue_action(TimelockedAction::AddMinter { account_id: accounts(2) });
+        testing_env!(context.block_timestamp(3_600 * 1_000_000_000).build());
+        contract.execute_action(action_id);
+        assert!(contract.is_minter(accounts(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Action must be queued through the timelock")]
+    fn test_timelock_delay_change_blocked_by_timelock() {
+        let (_, mut contract) = setup_with_timelock();
+        contract.set_timelock_delay(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Action is not queued")]
+    fn test_execute_cancelled_action() {
+        let (mut context, mut contract) = setup_with_timelock();
+        let action_id = queue_mint(&mut contract);
+        contract.cancel_action(action_id.clone());
+
+        testing_env!(context.block_timestamp(3_600 * 1_000_000_000).build());
+        contract.execute_action(action_id);
+    }
+
+    #[test]
+    fn test_nep141_view_aliases() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        contract.transfer(accounts(2), U128(100));
+
+        assert_eq!(contract.ft_total_supply(), contract.total_supply());
+        for account_id in [accounts(1), accounts(2), accounts(3)] {
+            assert_eq!(contract.ft_balance_of(account_id.clone()), contract.balance_of(account_id));
+        }
+    }
+
+    #[test]
+    fn test_get_config_matches_views() {
+        let (_, mut contract) = setup_with_max_supply(Some(U128(u128::MAX / 2)));
+        contract.set_transfer_fee(100, accounts(1));
+
+        let config = contract.get_config();
+        assert_eq!(config.name, contract.name());
+        assert_eq!(config.symbol, contract.symbol());
+        assert_eq!(config.decimals, contract.decimals());
+        assert_eq!(config.total_supply, contract.total_supply());
+        assert_eq!(config.max_supply, contract.max_supply());
+        assert_eq!(config.owner_id, contract.owner_id());
+        assert_eq!(config.paused, contract.is_paused());
+        assert!(config.trading_enabled);
+        assert_eq!(config.transfer_fee, 100);
+        assert_eq!(config.fee_collector, accounts(1));
+    }
+
+    #[test]
+    fn test_sponsor_registers_another_account() {
+        let (mut context, mut contract) = setup();
+        let min_balance = contract.storage_balance_bounds().min.0;
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(min_balance + 1_000))
+            .build());
+        let storage_balance = contract.storage_deposit(Some(accounts(3)), Some(true));
+
+        assert_eq!(storage_balance.total.0, min_balance);
+        assert!(contract.storage_balance_of(accounts(3)).is_some());
+        // The sponsor pays but doesn't get registered, and gets the surplus back
+        assert!(contract.storage_balance_of(accounts(2)).is_none());
+        assert!(near_sdk::test_utils::get_logs()
+            .contains(&format!("Refunding 1000 yoctoNEAR to {}", accounts(2))));
+    }
+
+    #[test]
+    fn test_storage_deposit_exact_amount_refunds_nothing() {
+        let (mut context, mut contract) = setup();
+        let min_balance = contract.storage_balance_bounds().min.0;
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(min_balance))
+            .build());
+        contract.storage_deposit(Some(accounts(3)), None);
+        assert!(!near_sdk::test_utils::get_logs().iter().any(|log| log.starts_with("Refunding")));
+    }
+
+    #[test]
+    fn test_storage_deposit_for_registered_account_refunds_everything() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(3));
+        let min_balance = contract.storage_balance_bounds().min.0;
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(min_balance))
+            .build());
+        contract.storage_deposit(Some(accounts(3)), None);
+        assert!(near_sdk::test_utils::get_logs()
+            .contains(&format!("Refunding {} yoctoNEAR to {}", min_balance, accounts(2))));
+    }
+
+    #[test]
+    #[should_panic(expected = "The attached deposit is less than the minimum storage balance")]
+    fn test_sponsor_underpays_registration() {
+        let (mut context, mut contract) = setup();
+        let min_balance = contract.storage_balance_bounds().min.0;
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(min_balance - 1))
+            .build());
+        contract.storage_deposit(Some(accounts(3)), None);
+    }
+
+    #[test]
+    fn test_rebase_expands_every_balance() {
+        let (mut context, mut contract) = setup();
+        let total_supply = setup_with_two_holders(&mut context, &mut contract);
+        let shares = contract.shares_of(accounts(2));
+
+        contract.rebase(U128(total_supply * 2));
+        assert_eq!(contract.total_supply().0, total_supply * 2);
+        assert_eq!(contract.balance_of(accounts(1)).0, total_supply / 4 * 3 * 2);
+        assert_eq!(contract.balance_of(accounts(2)).0, total_supply / 4 * 2);
+        assert_eq!(contract.shares_of(accounts(2)), shares);
+        assert!(contract.get_holders(0, 10).contains(&(accounts(2), U128(total_supply / 2))));
+
+        // Transfers move token amounts, not shares
+        contract.transfer(accounts(2), U128(1_000));
+        assert_eq!(contract.balance_of(accounts(2)).0, total_supply / 2 + 1_000);
+        assert_eq!(contract.shares_of(accounts(2)).0, shares.0 + 500);
+    }
+
+    #[test]
+    fn test_rebase_contracts_every_balance() {
+        let (mut context, mut contract) = setup();
+        let total_supply = setup_with_two_holders(&mut context, &mut contract);
+
+        contract.rebase(U128(total_supply / 2));
+        assert_eq!(contract.scaling_factor().0, SCALING_PRECISION / 2);
+        assert_eq!(contract.balance_of(accounts(1)).0, total_supply / 4 * 3 / 2);
+        assert_eq!(contract.balance_of(accounts(2)).0, total_supply / 8);
+    }
+
+    #[test]
+    fn test_vesting_release_after_negative_rebase() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        contract.create_vesting(accounts(2), U128(1_000), 0, 100);
+        let total_supply = contract.total_supply().0;
+
+        // The vesting pool on the contract's own account isn't scaled down with the rest
+        contract.rebase(U128((total_supply - 1_000) / 2 + 1_000));
+        assert_eq!(contract.scaling_factor().0, SCALING_PRECISION / 2);
+        assert_eq!(contract.balance_of(accounts(1)).0, (total_supply - 1_000) / 2);
+        assert_eq!(contract.balance_of(accounts(0)).0, 1_000);
+        assert_eq!(contract.shares_of(accounts(0)).0, 1_000);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(100 * 1_000_000_000)
+            .build());
+        assert_eq!(contract.release().0, 1_000);
+        assert_eq!(contract.balance_of(accounts(2)).0, 1_000);
+        assert_eq!(contract.balance_of(accounts(0)).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Transfers are paused")]
+    fn test_vesting_release_while_paused() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        contract.create_vesting(accounts(2), U128(1_000), 0, 100);
+        contract.pause();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(100 * 1_000_000_000)
+            .build());
+        contract.release();
+    }
+
+    #[test]
+    #[should_panic(expected = "Account charlie is blacklisted")]
+    fn test_vesting_release_blacklisted() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        contract.create_vesting(accounts(2), U128(1_000), 0, 100);
+        contract.blacklist_account(accounts(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(100 * 1_000_000_000)
+            .build());
+        contract.release();
+    }
+
+    #[test]
+    fn test_votes_follow_rebase() {
+        let (mut context, mut contract) = setup();
+        let total_supply = setup_with_two_holders(&mut context, &mut contract);
+        assert_eq!(contract.get_votes(accounts(2)).0, total_supply / 4);
+
+        testing_env!(context.block_timestamp(1_000).build());
+        contract.rebase(U128(total_supply * 2));
+        assert_eq!(contract.get_votes(accounts(2)).0, total_supply / 2);
+        testing_env!(context.block_timestamp(2_000).build());
+        // Past values convert at the scaling factor of their own timestamp
+        assert_eq!(contract.get_past_votes(accounts(2), 999).0, total_supply / 4);
+        assert_eq!(contract.balance_of_at(accounts(2), 999).0, total_supply / 4);
+        assert_eq!(contract.get_past_votes(accounts(2), 1_000).0, total_supply / 2);
+        assert_eq!(contract.balance_of_at(accounts(2), 1_000).0, total_supply / 2);
+    }
+
+    #[test]
+    fn test_delegate_moves_past_votes() {
+        let (mut context, mut contract) = setup();
+        let total_supply = setup_with_two_holders(&mut context, &mut contract);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).block_timestamp(1_000).build());
+        contract.delegate(accounts(1));
+        assert_eq!(contract.get_votes(accounts(1)).0, total_supply);
+        assert_eq!(contract.get_votes(accounts(2)).0, 0);
+
+        testing_env!(context.block_timestamp(2_000).build());
+        assert_eq!(contract.get_past_votes(accounts(1), 999).0, total_supply / 4 * 3);
+        assert_eq!(contract.get_past_votes(accounts(2), 999).0, total_supply / 4);
+        assert_eq!(contract.get_past_votes(accounts(1), 1_000).0, total_supply);
+    }
+
+    #[test]
+    #[should_panic(expected = "The account danny is not registered")]
+    fn test_delegate_to_unregistered() {
+        let (mut context, mut contract) = setup();
+        setup_with_two_holders(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.delegate(accounts(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Scaling factor out of range")]
+    fn test_rebase_out_of_range() {
+        let (_, mut contract) = setup();
+        contract.rebase(U128(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can call this method")]
+    fn test_rebase_not_owner() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.rebase(U128(1_000));
+    }
+
+    /// Arguments of the `on_token_transfer` calls scheduled so far, standing in for the hook
+    fn recorded_hook_calls(hook: &AccountId) -> Vec<near_sdk::serde_json::Value> {
+        near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .filter(|receipt| &receipt.receiver_id == hook)
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                near_sdk::mock::MockAction::FunctionCallWeight { method_name, args, .. }
+                    if method_name == b"on_token_transfer" =>
+                {
+                    Some(near_sdk::serde_json::from_slice(&args).unwrap())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_transfer_hook_is_notified() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        contract.set_transfer_hook(accounts(3));
+        assert_eq!(contract.transfer_hook(), Some(accounts(3)));
+
+        contract.transfer(accounts(2), U128(1_000));
+        let calls = recorded_hook_calls(&accounts(3));
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0]["from"], accounts(1).to_string());
+        assert_eq!(calls[0]["to"], accounts(2).to_string());
+        assert_eq!(calls[0]["amount"], "1000");
+        // The transfer doesn't wait on the hook
+        assert_eq!(contract.balance_of(accounts(2)).0, 1_000);
+    }
+
+    #[test]
+    fn test_cleared_transfer_hook_is_not_notified() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        contract.set_transfer_hook(accounts(3));
+        contract.clear_transfer_hook();
+
+        contract.transfer(accounts(2), U128(1_000));
+        assert!(recorded_hook_calls(&accounts(3)).is_empty());
+    }
+
+    #[test]
+    fn test_ft_transfer_call_reserves_hook_gas() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        contract.set_transfer_hook(accounts(3));
+        testing_env!(context
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .prepaid_gas(Gas::from_tgas(100))
+            .build());
+        contract.ft_transfer_call(accounts(2), U128(1_000), None, String::new());
+
+        assert_eq!(recorded_hook_calls(&accounts(3)).len(), 1);
+        let scheduled_gas: Vec<(Vec<u8>, Gas)> = near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                near_sdk::mock::MockAction::FunctionCallWeight {
+                    method_name,
+                    prepaid_gas,
+                    ..
+                } => Some((method_name, prepaid_gas)),
+                _ => None,
+            })
+            .collect();
+        // 100 Tgas minus this call's 35, the resolve callback's 10 and the hook's 5
+        assert!(scheduled_gas.contains(&(b"ft_on_transfer".to_vec(), Gas::from_tgas(50))));
+        assert!(scheduled_gas.contains(&(b"on_token_transfer".to_vec(), Gas::from_tgas(5))));
+        assert!(scheduled_gas.contains(&(b"ft_resolve_transfer".to_vec(), Gas::from_tgas(10))));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot transfer to more than 20 recipients at once")]
+    fn test_batch_transfer_limit_with_transfer_hook() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        contract.set_transfer_hook(accounts(3));
+        let transfers = vec![(accounts(2), U128(1)); 21];
+        contract.batch_transfer(transfers);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can call this method")]
+    fn test_set_transfer_hook_not_owner() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.set_transfer_hook(accounts(3));
+    }
+
+    #[test]
+    fn test_ft_transfer_to_registered_receiver() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        assert!(contract.is_registered(accounts(2)));
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(1)).build());
+        contract.ft_transfer(accounts(2), U128(1_000), None);
+        assert_eq!(contract.balance_of(accounts(2)).0, 1_000);
+    }
+
+    #[test]
+    fn test_ft_transfer_auto_registers_receiver() {
+        let (mut context, mut contract) = setup();
+        contract.set_auto_register_receivers(true);
+        assert!(!contract.is_registered(accounts(3)));
+
+        let min_balance = contract.storage_balance_bounds().min.0;
+        testing_env!(context
+            .attached_deposit(NearToken::from_yoctonear(min_balance + 1 + 500))
+            .build());
+        contract.ft_transfer(accounts(3), U128(1_000), None);
+        assert!(contract.is_registered(accounts(3)));
+        assert_eq!(contract.balance_of(accounts(3)).0, 1_000);
+        assert!(near_sdk::test_utils::get_logs()
+            .contains(&format!("Refunding 500 yoctoNEAR to {}", accounts(1))));
+    }
+
+    #[test]
+    #[should_panic(expected = "The attached deposit doesn't cover the receiver's storage")]
+    fn test_ft_transfer_auto_register_without_deposit() {
+        let (mut context, mut contract) = setup();
+        contract.set_auto_register_receivers(true);
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(1)).build());
+        contract.ft_transfer(accounts(3), U128(1_000), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "The account danny is not registered")]
+    fn test_ft_transfer_to_unregistered_receiver() {
+        let (mut context, mut contract) = setup();
+        let min_balance = contract.storage_balance_bounds().min.0;
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(min_balance + 1)).build());
+        contract.ft_transfer(accounts(3), U128(1_000), None);
+    }
+
+    #[test]
+    fn test_resolve_transfer_after_receiver_unregistered() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        contract.transfer(accounts(2), U128(1_000));
+        let owner_balance = contract.balance_of(accounts(1)).0;
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.storage_unregister(Some(true));
+        let total_supply = contract.total_supply().0;
+
+        // The receiver's balance was burned on unregister, so the unused amount isn't re-minted
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"1000\"".to_vec())],
+        );
+        let used = contract.ft_resolve_transfer(accounts(1), accounts(2), U128(1_000));
+        assert_eq!(used.0, 1_000);
+        assert_eq!(contract.total_supply().0, total_supply);
+        assert_eq!(contract.balance_of(accounts(1)).0, owner_balance);
+    }
+
+    #[test]
+    #[should_panic(expected = "Burning is paused")]
+    fn test_force_unregister_when_burns_paused() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        contract.transfer(accounts(2), U128(1_000));
+        contract.set_pause_burns(true);
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.storage_unregister(Some(true));
+    }
+
+    /// NEAR transferred to `account_id` by the receipts created so far
+    fn refunded_to(account_id: &AccountId) -> u128 {
+        near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .filter(|receipt| &receipt.receiver_id == account_id)
+            .flat_map(|receipt| receipt.actions)
+            .map(|action| match action {
+                near_sdk::mock::MockAction::Transfer { deposit, .. } => deposit.as_yoctonear(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_unregister_refunds_paid_deposit() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(2));
+        let min_balance = contract.storage_balance_bounds().min.0;
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        assert!(contract.storage_unregister(None));
+        assert_eq!(refunded_to(&accounts(2)), min_balance + 1);
+    }
+
+    #[test]
+    fn test_unregister_owner_refunds_no_deposit() {
+        let (mut context, mut contract) = setup();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        assert!(contract.storage_unregister(Some(true)));
+        // The owner was registered in `new` without paying, so only the yoctoNEAR comes back
+        assert_eq!(refunded_to(&accounts(1)), 1);
+    }
+
+    #[test]
+    fn test_pause_records_when_and_by_whom() {
+        let (mut context, mut contract) = setup();
+        contract.set_guardian(Some(accounts(2)));
+        testing_env!(context.predecessor_account_id(accounts(2)).block_timestamp(1_000).build());
+        contract.pause();
+        assert_eq!(contract.paused_at(), Some(1_000));
+        assert_eq!(contract.paused_by(), Some(accounts(2)));
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log.contains(&format!(
+            "\"event\":\"pause\",\"data\":[{{\"account_id\":\"{}\",\"timestamp\":1000}}]",
+            accounts(2)
+        ))));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.unpause();
+        assert!(!contract.is_paused());
+        assert_eq!(contract.paused_at(), None);
+        assert_eq!(contract.paused_by(), None);
+        assert!(near_sdk::test_utils::get_logs()
+            .iter()
+            .any(|log| log.contains("\"event\":\"unpause\"")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot transfer to the token contract")]
+    fn test_transfer_to_token_contract() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(0));
+        contract.transfer(accounts(0), U128(1_000));
+    }
+
+    #[test]
+    fn test_transfer_to_token_contract_when_allowed() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(0));
+        contract.set_allow_self_transfers(true);
+        contract.transfer(accounts(0), U128(1_000));
+        assert_eq!(contract.balance_of(accounts(0)).0, 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot transfer to the token contract")]
+    fn test_ft_transfer_call_to_token_contract() {
+        let (mut context, mut contract) = setup();
+        register(&mut context, &mut contract, accounts(0));
+        contract.set_allow_self_transfers(true);
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(1)).build());
+        contract.ft_transfer_call(accounts(0), U128(1_000), None, String::new());
     }
 }
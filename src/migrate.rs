@@ -0,0 +1,210 @@
+//! State migration from the originally deployed contract layout.
+//!
+//! Borsh state has no field names, so any change to `UnrealToken` makes the
+//! stored state unreadable by the new code. To upgrade a deployment, deploy the
+//! new code and call `migrate` in the same transaction. `migrate` reads the
+//! state with the old layout (`OldUnrealToken`) and builds the new struct,
+//! reusing the existing collections and giving every added field a default that
+//! keeps the previous behavior. Whenever a field is added to `UnrealToken`, it
+//! has to be given a default here as well.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::{env, log, near_bindgen, AccountId};
+use std::collections::HashMap;
+
+use crate::{
+    Balance, FungibleTokenMetadata, PauseState, UnrealToken, UnrealTokenExt, FT_METADATA_SPEC,
+    SCALING_PRECISION,
+};
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) struct OldFungibleTokenMetadata {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// Layout of the originally deployed contract
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) struct OldUnrealToken {
+    pub name: String,
+    pub symbol: String,
+    pub total_supply: Balance,
+    pub decimals: u8,
+    pub owner_id: AccountId,
+    pub paused: bool,
+    pub balances: LookupMap<AccountId, Balance>,
+    pub allowances: LookupMap<AccountId, HashMap<AccountId, Balance>>,
+    pub metadata: LazyOption<OldFungibleTokenMetadata>,
+}
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Migrates the state written by the original contract to the current layout
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: OldUnrealToken = env::state_read().expect("Failed to read old state");
+        let old_metadata = old.metadata.get().expect("Metadata is not set");
+
+        let mut this = Self {
+            name: old.name,
+            symbol: old.symbol,
+            total_supply: old.total_supply,
+            decimals: old.decimals,
+            owner_id: old.owner_id.clone(),
+            pause_state: PauseState::all(old.paused),
+            // Holders are moved to the enumerable map on write or via `migrate_holders`
+            balances: UnorderedMap::new(b"h"),
+            legacy_balances: old.balances,
+            allowances: LookupMap::new(b"c"),
+            // Old allowances are moved to composite keys as they are written
+            legacy_allowances: old.allowances,
+            // Overwrites the old metadata entry with the NEP-148 layout
+            metadata: LazyOption::new(
+                b"m",
+                Some(&FungibleTokenMetadata {
+                    spec: FT_METADATA_SPEC.to_string(),
+                    name: old_metadata.name,
+                    symbol: old_metadata.symbol,
+                    icon: None,
+                    reference: None,
+                    reference_hash: None,
+                    decimals: old_metadata.decimals,
+                }),
+            ),
+            account_storage_usage: 0,
+            pending_owner: None,
+            minters: LookupMap::new(b"n"),
+            max_supply: None,
+            blacklist: LookupMap::new(b"l"),
+            fee_basis_points: 0,
+            fee_collector: old.owner_id.clone(),
+            fee_exempt: LookupMap::new(b"f"),
+            balance_checkpoints: LookupMap::new(b"k"),
+            total_supply_checkpoints: Vector::new(b"s"),
+            delegates: LookupMap::new(b"d"),
+            vote_checkpoints: LookupMap::new(b"v"),
+            vesting_schedules: LookupMap::new(b"g"),
+            vesting_counts: LookupMap::new(b"G"),
+            guardian: None,
+            transfer_cooldown_seconds: 0,
+            last_transfer: LookupMap::new(b"t"),
+            cooldown_exempt: LookupMap::new(b"e"),
+            max_tx_amount: None,
+            max_wallet: None,
+            limits_exempt: LookupMap::new(b"x"),
+            // The deployed token is already trading
+            trading_enabled: true,
+            transfer_whitelist: LookupMap::new(b"w"),
+            allowance_expiries: LookupMap::new(b"y"),
+            permit_nonces: LookupMap::new(b"p"),
+            permit_keys: LookupMap::new(b"P"),
+            mint_epoch_length_seconds: 0,
+            mint_cap_per_epoch: 0,
+            current_mint_epoch: 0,
+            minted_this_epoch: 0,
+            airdrop_root: None,
+            airdrop_remaining: 0,
+            airdrop_deadline: 0,
+            airdrop_claimed: LookupMap::new(b"j"),
+            treasury: old.owner_id,
+            circulating_excluded: UnorderedSet::new(b"q"),
+            reward_distribution: None,
+            timelock_delay_seconds: 0,
+            pending_actions: LookupMap::new(b"T"),
+            // A share is one token, which is what the old balances were
+            scaling_factor: SCALING_PRECISION,
+            scaling_factor_checkpoints: Vector::new(b"F"),
+            transfer_hook: None,
+            auto_register_receivers: false,
+            // Whether the old contract was paused is known, but not since when or by whom
+            paused_at: None,
+            paused_by: None,
+            allow_self_transfers: false,
+            spenders: LookupMap::new(b"S"),
+            // Burns of the old contract weren't tracked, so its supply counts as minted
+            total_minted: old.total_supply,
+            total_burned: 0,
+            // Legacy holders registered with the old contract, which didn't record deposits
+            storage_deposits: LookupMap::new(b"D"),
+        };
+        this.measure_account_storage_usage();
+        this.internal_checkpoint_total_supply();
+
+        this
+    }
+
+    /// Moves the given accounts from the legacy balances map into the enumerable one,
+    /// so they show up in `get_holders` - only callable by owner.
+    /// Holders can be collected off-chain from past events and migrated in batches
+    pub fn migrate_holders(&mut self, account_ids: Vec<AccountId>) -> u32 {
+        self.assert_owner();
+        let mut migrated = 0;
+        for account_id in account_ids {
+            // Moved as shares, which a rebase may have made differ from the balance
+            if let Some(shares) = self.legacy_balances.remove(&account_id) {
+                self.balances.insert(&account_id, &shares);
+                migrated += 1;
+            }
+        }
+        log!("Migrated {} holders", migrated);
+        migrated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_migrate_keeps_balances_and_total_supply() {
+        let mut context = VMContextBuilder::new();
+        context
+            .current_account_id(accounts(0))
+            .predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+
+        let mut old = OldUnrealToken {
+            name: "Unreal Token".to_string(),
+            symbol: "UNREAL".to_string(),
+            total_supply: 1_000,
+            decimals: 18,
+            owner_id: accounts(1),
+            paused: false,
+            balances: LookupMap::new(b"b"),
+            allowances: LookupMap::new(b"a"),
+            metadata: LazyOption::new(
+                b"m",
+                Some(&OldFungibleTokenMetadata {
+                    name: "Unreal Token".to_string(),
+                    symbol: "UNREAL".to_string(),
+                    decimals: 18,
+                }),
+            ),
+        };
+        old.balances.insert(&accounts(1), &600);
+        old.balances.insert(&accounts(2), &400);
+        old.allowances.insert(&accounts(1), &HashMap::from([(accounts(2), 50)]));
+        env::state_write(&old);
+
+        let contract = UnrealToken::migrate();
+        assert_eq!(contract.total_supply(), U128(1_000));
+        assert_eq!(contract.total_minted(), U128(1_000));
+        assert_eq!(contract.total_burned(), U128(0));
+        assert_eq!(contract.balance_of(accounts(1)), U128(600));
+        assert_eq!(contract.balance_of(accounts(2)), U128(400));
+        assert_eq!(contract.allowance(accounts(1), accounts(2)), U128(50));
+        assert_eq!(contract.owner_id(), accounts(1));
+        assert!(!contract.is_paused());
+
+        let metadata = contract.ft_metadata();
+        assert_eq!(metadata.spec, FT_METADATA_SPEC);
+        assert_eq!(metadata.symbol, "UNREAL");
+        assert_eq!(metadata.decimals, 18);
+    }
+}
@@ -0,0 +1,72 @@
+//! Rate-limited minting: at most `mint_cap_per_epoch` new tokens per fixed window.
+//!
+//! Epochs are consecutive windows of `mint_epoch_length_seconds` counted from
+//! timestamp zero, so every epoch starts with a fresh budget. A zero epoch length
+//! disables the limit.
+
+use near_sdk::json_types::U128;
+use near_sdk::{env, log, near_bindgen};
+
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the length of a mint epoch in seconds, zero if minting isn't rate limited
+    pub fn mint_epoch_length(&self) -> u64 {
+        self.mint_epoch_length_seconds
+    }
+
+    /// Returns the most that can be minted in a single epoch
+    pub fn mint_cap_per_epoch(&self) -> U128 {
+        U128(self.mint_cap_per_epoch)
+    }
+
+    /// Returns what can still be minted in the current epoch,
+    /// `u128::MAX` if minting isn't rate limited
+    pub fn remaining_mint_budget(&self) -> U128 {
+        if self.mint_epoch_length_seconds == 0 {
+            return U128(Balance::MAX);
+        }
+        let minted = if self.internal_mint_epoch() == self.current_mint_epoch {
+            self.minted_this_epoch
+        } else {
+            0
+        };
+        U128(self.mint_cap_per_epoch.saturating_sub(minted))
+    }
+
+    /// Cap minting to `cap_per_epoch` every `epoch_length_seconds`, a zero length
+    /// disables the limit - only callable by owner
+    pub fn set_mint_rate_limit(&mut self, epoch_length_seconds: u64, cap_per_epoch: U128) {
+        self.assert_owner();
+        self.mint_epoch_length_seconds = epoch_length_seconds;
+        self.mint_cap_per_epoch = cap_per_epoch.0;
+        log!(
+            "Mint rate limit set to {} per {} seconds",
+            cap_per_epoch.0,
+            epoch_length_seconds
+        );
+    }
+
+    fn internal_mint_epoch(&self) -> u64 {
+        env::block_timestamp() / (self.mint_epoch_length_seconds * NANOS_PER_SECOND)
+    }
+
+    /// Panics if minting `amount` would exceed the current epoch's cap, otherwise
+    /// counts it against the cap. Starts a fresh budget once a new epoch begins
+    pub(crate) fn internal_consume_mint_budget(&mut self, amount: Balance) {
+        if self.mint_epoch_length_seconds == 0 {
+            return;
+        }
+        let epoch = self.internal_mint_epoch();
+        if epoch != self.current_mint_epoch {
+            self.current_mint_epoch = epoch;
+            self.minted_this_epoch = 0;
+        }
+        let minted = self.minted_this_epoch.saturating_add(amount);
+        assert!(minted <= self.mint_cap_per_epoch, "Mint cap for this epoch exceeded");
+        self.minted_this_epoch = minted;
+    }
+}
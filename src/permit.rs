@@ -0,0 +1,113 @@
+//! Gasless approvals signed off-chain by the token owner.
+//!
+//! The owner signs the UTF-8 message
+//! `{token}:permit:{owner}:{spender}:{amount}:{nonce}:{deadline}` with an ed25519 key,
+//! where `token` is this contract's account id, `amount` is in the smallest unit,
+//! `nonce` is the owner's current `nonces` value and `deadline` is a block timestamp
+//! in nanoseconds, e.g. `token.near:permit:alice.near:bob.near:100:0:1000`. Anyone
+//! can then submit it through `permit`. Every accepted permit increments the nonce
+//! in the same call that checks it, so a signed message can only be used once.
+//!
+//! A contract can't read another account's access keys, so the signing key must
+//! either be the one behind an implicit `owner` account or have been registered
+//! by the owner through `set_permit_key`.
+
+use near_sdk::json_types::U128;
+use near_sdk::{env, log, near_bindgen, AccountId, CurveType, PublicKey};
+
+use crate::{UnrealToken, UnrealTokenExt};
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the nonce the next permit signed by `owner_id` must use
+    pub fn permit_nonce(&self, owner_id: AccountId) -> u64 {
+        self.permit_nonces.get(&owner_id).unwrap_or(0)
+    }
+
+    /// EIP-2612 name of `permit_nonce`, zero for accounts that never signed a permit
+    pub fn nonces(&self, account_id: AccountId) -> u64 {
+        self.permit_nonce(account_id)
+    }
+
+    /// Returns the key registered by `owner_id` for signing permits
+    pub fn permit_key(&self, owner_id: AccountId) -> Option<PublicKey> {
+        self.permit_keys.get(&owner_id)
+    }
+
+    /// Register the ed25519 key the caller signs permits with, `None` removes it
+    pub fn set_permit_key(&mut self, public_key: Option<PublicKey>) {
+        let owner_id = env::predecessor_account_id();
+        match public_key {
+            Some(public_key) => {
+                assert_ed25519(&public_key);
+                self.permit_keys.insert(&owner_id, &public_key);
+            }
+            None => {
+                self.permit_keys.remove(&owner_id);
+            }
+        }
+        log!("Permit key updated for {}", owner_id);
+    }
+
+    /// Set the allowance of `spender` over `owner`'s tokens from a message signed by `owner`.
+    /// `public_key` is `ed25519:<base58>` and `signature` is the hex encoded signature
+    pub fn permit(
+        &mut self,
+        owner: AccountId,
+        spender: AccountId,
+        amount: U128,
+        deadline: u64,
+        public_key: String,
+        signature: String,
+    ) -> bool {
+        self.assert_transfers_not_paused();
+        assert!(env::block_timestamp() <= deadline, "Permit expired");
+
+        let public_key: PublicKey =
+            public_key.parse().unwrap_or_else(|_| env::panic_str("Invalid public key"));
+        assert_ed25519(&public_key);
+        let key_bytes: [u8; 32] = public_key.as_bytes()[1..]
+            .try_into()
+            .unwrap_or_else(|_| env::panic_str("Invalid public key"));
+        assert!(
+            self.permit_keys.get(&owner).as_ref() == Some(&public_key)
+                || owner.as_str() == hex::encode(key_bytes),
+            "Public key is not authorized for owner"
+        );
+
+        let signature: [u8; 64] = hex::decode(signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .unwrap_or_else(|| env::panic_str("Invalid signature"));
+        let nonce = self.permit_nonce(owner.clone());
+        let message = permit_message(&owner, &spender, amount.0, nonce, deadline);
+        assert!(env::ed25519_verify(&signature, &message, &key_bytes), "Invalid signature");
+
+        self.permit_nonces.insert(&owner, &(nonce + 1));
+        self.internal_approve_until(&owner, &spender, amount.0, None)
+    }
+}
+
+fn assert_ed25519(public_key: &PublicKey) {
+    assert!(public_key.curve_type() == CurveType::ED25519, "Only ed25519 keys are supported");
+}
+
+/// The message an owner signs to approve `amount` for `spender`
+fn permit_message(
+    owner: &AccountId,
+    spender: &AccountId,
+    amount: u128,
+    nonce: u64,
+    deadline: u64,
+) -> Vec<u8> {
+    format!(
+        "{}:permit:{}:{}:{}:{}:{}",
+        env::current_account_id(),
+        owner,
+        spender,
+        amount,
+        nonce,
+        deadline
+    )
+    .into_bytes()
+}
@@ -0,0 +1,116 @@
+//! Elastic supply: the owner rebases every balance by the same ratio at once.
+//!
+//! `balances` stores shares rather than tokens. A balance is `shares *
+//! scaling_factor / SCALING_PRECISION`, and `rebase` moves the total supply by
+//! scaling the factor, so no balance entry has to be written. The factor starts
+//! at `SCALING_PRECISION`, where a share is one token and the stored values of
+//! existing deployments stay valid. Everything outside the balance layer, from
+//! transfers to allowances, works on token amounts. Shares are rounded down when
+//! written, so after a rebase a balance can end up a few units below the amount
+//! credited.
+//!
+//! The contract's own account holds the vesting and airdrop pools, which are owed
+//! as fixed token amounts. It isn't rebased: its entry stores tokens, and a
+//! rebase scales the rest of the supply around it. Balance and vote checkpoints
+//! record shares, and every rebase checkpoints the scaling factor, so a past
+//! balance converts at the factor of its own timestamp.
+
+use near_sdk::json_types::U128;
+use near_sdk::{env, log, near_bindgen, AccountId};
+
+use crate::events::Rebase;
+use crate::rewards::mul_div;
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+/// Scaling factor at which a share is worth exactly one token
+pub(crate) const SCALING_PRECISION: Balance = 1_000_000_000_000_000_000;
+/// Bounds on the scaling factor, keeping shares of any balance within 128 bits
+const MIN_SCALING_FACTOR: Balance = SCALING_PRECISION / 1_000_000;
+const MAX_SCALING_FACTOR: Balance = SCALING_PRECISION * 1_000_000;
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the number of tokens per share, scaled by 10^18
+    pub fn scaling_factor(&self) -> U128 {
+        U128(self.scaling_factor)
+    }
+
+    /// Returns the shares held by `account_id`, which rebases don't change.
+    /// The contract's own account isn't rebased, so its shares are its tokens
+    pub fn shares_of(&self, account_id: AccountId) -> U128 {
+        U128(self.internal_shares_of(&account_id))
+    }
+
+    /// Expand or contract every balance so the total supply becomes `new_total_supply` -
+    /// only callable by owner. While a timelock delay is set, this has to be queued instead
+    pub fn rebase(&mut self, new_total_supply: U128) {
+        self.assert_owner();
+        self.assert_not_timelocked();
+        self.internal_rebase(new_total_supply);
+    }
+
+    pub(crate) fn internal_rebase(&mut self, new_total_supply: U128) {
+        let new_total_supply: Balance = new_total_supply.into();
+        // The contract's own balance stays as it is, the rest of the supply is scaled
+        let unrebased = self.internal_balance_of(&env::current_account_id()).unwrap_or(0);
+        let rebased_supply = self.total_supply.saturating_sub(unrebased);
+        assert!(rebased_supply > 0, "There is no supply to rebase");
+        assert!(
+            new_total_supply > unrebased,
+            "The total supply should exceed the contract's own balance"
+        );
+        if let Some(max_supply) = self.max_supply {
+            assert!(new_total_supply <= max_supply, "Max supply exceeded");
+        }
+        let scaling_factor =
+            mul_div(self.scaling_factor, new_total_supply - unrebased, rebased_supply);
+        assert!(
+            (MIN_SCALING_FACTOR..=MAX_SCALING_FACTOR).contains(&scaling_factor),
+            "Scaling factor out of range"
+        );
+
+        let old_total_supply = self.total_supply;
+        self.scaling_factor = scaling_factor;
+        self.total_supply = new_total_supply;
+        self.internal_checkpoint_total_supply();
+        self.internal_checkpoint_scaling_factor();
+        log!("Rebased the total supply from {} to {}", old_total_supply, new_total_supply);
+        Rebase {
+            old_total_supply: U128(old_total_supply),
+            new_total_supply: U128(new_total_supply),
+            scaling_factor: U128(scaling_factor),
+        }
+        .emit();
+    }
+
+    /// Stored shares of `account_id`, zero if it isn't registered
+    pub(crate) fn internal_shares_of(&self, account_id: &AccountId) -> Balance {
+        self.balances
+            .get(account_id)
+            .or_else(|| self.legacy_balances.get(account_id))
+            .unwrap_or(0)
+    }
+
+    /// Converts the shares stored for `account_id` to a token amount
+    pub(crate) fn balance_from_shares(&self, account_id: &AccountId, shares: Balance) -> Balance {
+        if is_rebased(account_id) {
+            mul_div(shares, self.scaling_factor, SCALING_PRECISION)
+        } else {
+            shares
+        }
+    }
+
+    /// Converts a token amount of `account_id` to the shares to store, rounding down
+    pub(crate) fn shares_from_balance(&self, account_id: &AccountId, balance: Balance) -> Balance {
+        if is_rebased(account_id) {
+            mul_div(balance, SCALING_PRECISION, self.scaling_factor)
+        } else {
+            balance
+        }
+    }
+}
+
+/// Whether the balance of `account_id` follows rebases, which is all but the contract's own
+pub(crate) fn is_rebased(account_id: &AccountId) -> bool {
+    account_id != &env::current_account_id()
+}
@@ -0,0 +1,53 @@
+//! Recovery of NEAR and foreign fungible tokens sent to the contract by mistake.
+
+use near_sdk::json_types::U128;
+use near_sdk::{env, ext_contract, log, near_bindgen, AccountId, NearToken, Promise};
+
+use crate::{Balance, UnrealToken, UnrealTokenExt, CONTRACT_STORAGE_COST, GAS_FOR_FT_TRANSFER};
+
+/// NEP-141 interface of the foreign tokens being recovered
+#[ext_contract(ext_ft_core)]
+pub trait FungibleTokenCore {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the NEAR that can be recovered, i.e. everything above what the
+    /// contract needs to cover its storage plus an operating reserve
+    pub fn recoverable_near(&self) -> U128 {
+        let storage_cost =
+            Balance::from(env::storage_usage()) * env::storage_byte_cost().as_yoctonear();
+        let reserve = storage_cost + CONTRACT_STORAGE_COST;
+        U128(env::account_balance().as_yoctonear().saturating_sub(reserve))
+    }
+
+    /// Send NEAR that was deposited by mistake to `to` - only callable by owner
+    pub fn recover_near(&mut self, amount: U128, to: AccountId) -> Promise {
+        self.assert_owner();
+        assert!(amount.0 > 0, "The amount should be a positive number");
+        assert!(
+            amount.0 <= self.recoverable_near().0,
+            "Cannot recover NEAR reserved for storage and operations"
+        );
+        log!("Recovering {} yoctoNEAR to {}", amount.0, to);
+        Promise::new(to).transfer(NearToken::from_yoctonear(amount.0))
+    }
+
+    /// Send foreign NEP-141 tokens held by this contract to `to` - only callable by owner.
+    /// This token's own balances are accounting entries, not holdings, and can't be recovered
+    pub fn recover_ft(&mut self, token: AccountId, amount: U128, to: AccountId) -> Promise {
+        self.assert_owner();
+        assert_ne!(
+            token,
+            env::current_account_id(),
+            "Cannot recover this token's own balances"
+        );
+        assert!(amount.0 > 0, "The amount should be a positive number");
+        log!("Recovering {} of {} to {}", amount.0, token, to);
+        ext_ft_core::ext(token)
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .ft_transfer(to, amount, Some("Recovered by owner".to_string()))
+    }
+}
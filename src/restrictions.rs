@@ -0,0 +1,215 @@
+//! Anti-bot transfer restrictions for token launches.
+//!
+//! Every restriction is off by default and checked from `internal_transfer`.
+
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, log, near_bindgen, AccountId};
+
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Anti-whale limits, `None` meaning unlimited
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransferLimits {
+    /// Largest amount a single transfer can move
+    pub max_tx_amount: Option<U128>,
+    /// Largest balance a receiving wallet can end up with
+    pub max_wallet: Option<U128>,
+}
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns true once the owner has opened trading to everyone
+    pub fn trading_enabled(&self) -> bool {
+        self.trading_enabled
+    }
+
+    /// Returns true if the account can transfer before trading is enabled
+    pub fn is_whitelisted(&self, account_id: AccountId) -> bool {
+        self.transfer_whitelist.get(&account_id).unwrap_or(false)
+    }
+
+    /// Open trading to everyone - only callable by owner. This can't be undone
+    pub fn enable_trading(&mut self) {
+        self.assert_owner();
+        assert!(!self.trading_enabled, "Trading is already enabled");
+        self.trading_enabled = true;
+        log!("Trading enabled");
+    }
+
+    /// Allow an account to send and receive tokens before trading is enabled - only callable by owner
+    pub fn add_to_whitelist(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.transfer_whitelist.insert(&account_id, &true);
+        log!("Added to whitelist: {}", account_id);
+    }
+
+    /// Remove an account from the launch whitelist - only callable by owner
+    pub fn remove_from_whitelist(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.transfer_whitelist.remove(&account_id);
+        log!("Removed from whitelist: {}", account_id);
+    }
+
+    /// Returns the minimum number of seconds between two transfers from the same account
+    pub fn transfer_cooldown(&self) -> u64 {
+        self.transfer_cooldown_seconds
+    }
+
+    /// Returns true if the account isn't subject to the transfer cooldown
+    pub fn is_cooldown_exempt(&self, account_id: AccountId) -> bool {
+        self.internal_is_cooldown_exempt(&account_id)
+    }
+
+    /// Set the transfer cooldown in seconds, zero disables it - only callable by owner.
+    /// While it is active, a `batch_transfer` to several recipients needs an exempt sender
+    pub fn set_transfer_cooldown(&mut self, cooldown_seconds: u64) {
+        self.assert_owner();
+        self.transfer_cooldown_seconds = cooldown_seconds;
+        log!("Transfer cooldown set to {} seconds", cooldown_seconds);
+    }
+
+    /// Exempt an account (e.g. a DEX pool or treasury) from the cooldown - only callable by owner
+    pub fn add_cooldown_exempt(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.cooldown_exempt.insert(&account_id, &true);
+        log!("Added cooldown exemption: {}", account_id);
+    }
+
+    /// Remove an account's cooldown exemption - only callable by owner
+    pub fn remove_cooldown_exempt(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.cooldown_exempt.remove(&account_id);
+        log!("Removed cooldown exemption: {}", account_id);
+    }
+
+    /// Returns the current max-transaction and max-wallet limits
+    pub fn limits(&self) -> TransferLimits {
+        TransferLimits {
+            max_tx_amount: self.max_tx_amount.map(U128),
+            max_wallet: self.max_wallet.map(U128),
+        }
+    }
+
+    /// Returns true if the account isn't subject to the max-transaction and max-wallet limits
+    pub fn is_limits_exempt(&self, account_id: AccountId) -> bool {
+        self.internal_is_limits_exempt(&account_id)
+    }
+
+    /// Set the max-transaction and max-wallet limits, `None` removes a limit - only callable by owner
+    pub fn set_limits(&mut self, max_tx_amount: Option<U128>, max_wallet: Option<U128>) {
+        self.assert_owner();
+        self.max_tx_amount = max_tx_amount.map(|amount| amount.0);
+        self.max_wallet = max_wallet.map(|amount| amount.0);
+        log!(
+            "Transfer limits set to {:?} per transaction, {:?} per wallet",
+            self.max_tx_amount,
+            self.max_wallet
+        );
+    }
+
+    /// Exempt an account (e.g. the treasury or an AMM pair) from the limits - only callable by owner
+    pub fn add_limits_exempt(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.limits_exempt.insert(&account_id, &true);
+        log!("Added limits exemption: {}", account_id);
+    }
+
+    /// Remove an account's limits exemption - only callable by owner
+    pub fn remove_limits_exempt(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.limits_exempt.remove(&account_id);
+        log!("Removed limits exemption: {}", account_id);
+    }
+
+    /// Returns true if transfers to the token contract's own account are allowed
+    pub fn self_transfers_allowed(&self) -> bool {
+        self.allow_self_transfers
+    }
+
+    /// Allow transfers to the token contract's own account, for setups that hold a balance
+    /// there on purpose - only callable by owner. Off by default, as such transfers are
+    /// usually mistakes that strand the tokens
+    pub fn set_allow_self_transfers(&mut self, allowed: bool) {
+        self.assert_owner();
+        self.allow_self_transfers = allowed;
+        log!("Transfers to the token contract allowed: {}", allowed);
+    }
+
+    /// Rejects transfers to the token contract's own account unless they are allowed
+    pub(crate) fn assert_not_self_transfer(&self, receiver_id: &AccountId) {
+        assert!(
+            self.allow_self_transfers || receiver_id != &env::current_account_id(),
+            "Cannot transfer to the token contract"
+        );
+    }
+
+    /// Before trading is enabled, either side of a transfer must be whitelisted or the owner
+    pub(crate) fn assert_trading_allowed(&self, sender_id: &AccountId, receiver_id: &AccountId) {
+        if self.trading_enabled {
+            return;
+        }
+        let allowed = [sender_id, receiver_id].into_iter().any(|account_id| {
+            account_id == &self.owner_id
+                || self.transfer_whitelist.get(account_id).unwrap_or(false)
+        });
+        assert!(allowed, "Trading is not enabled yet");
+    }
+
+    /// The owner is always exempt
+    fn internal_is_cooldown_exempt(&self, account_id: &AccountId) -> bool {
+        account_id == &self.owner_id || self.cooldown_exempt.get(account_id).unwrap_or(false)
+    }
+
+    /// Panics if `sender_id` transferred less than the cooldown ago, then records
+    /// this transfer. Does nothing (and writes nothing) while the cooldown is zero
+    pub(crate) fn internal_enforce_transfer_cooldown(&mut self, sender_id: &AccountId) {
+        if self.transfer_cooldown_seconds == 0 || self.internal_is_cooldown_exempt(sender_id) {
+            return;
+        }
+        let now = env::block_timestamp();
+        if let Some(last_transfer) = self.last_transfer.get(sender_id) {
+            assert!(
+                now - last_transfer >= self.transfer_cooldown_seconds * NANOS_PER_SECOND,
+                "Transfer cooldown active"
+            );
+        }
+        self.last_transfer.insert(sender_id, &now);
+    }
+
+    /// The owner is always exempt
+    fn internal_is_limits_exempt(&self, account_id: &AccountId) -> bool {
+        account_id == &self.owner_id || self.limits_exempt.get(account_id).unwrap_or(false)
+    }
+
+    /// Panics if a transfer of `amount`, of which the receiver gets `received`,
+    /// breaks the max-transaction limit for the sender or the max-wallet limit for the receiver
+    pub(crate) fn internal_enforce_transfer_limits(
+        &self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: Balance,
+        received: Balance,
+    ) {
+        if let Some(max_tx_amount) = self.max_tx_amount {
+            if !self.internal_is_limits_exempt(sender_id) {
+                assert!(
+                    amount <= max_tx_amount,
+                    "Transfer amount exceeds the max transaction limit"
+                );
+            }
+        }
+        if let Some(max_wallet) = self.max_wallet {
+            if !self.internal_is_limits_exempt(receiver_id) {
+                let balance = self.internal_balance_of(receiver_id).unwrap_or(0);
+                assert!(
+                    balance.saturating_add(received) <= max_wallet,
+                    "Receiver balance would exceed the max wallet limit"
+                );
+            }
+        }
+    }
+}
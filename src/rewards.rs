@@ -0,0 +1,203 @@
+//! Pro-rata distribution of newly minted staking rewards to every holder.
+//!
+//! `distribute_rewards` opens a distribution, which
+//! `distribute_rewards_batch` then works through the holders of `balances` in
+//! pages small enough to fit in a transaction. The next index is kept in state,
+//! so a distribution can be resumed across any number of transactions. Each
+//! holder gets `total_amount * balance / total_supply`, with both read from the
+//! checkpoints as of the block before the opening, so transfers between batches
+//! don't change anyone's share. A holder whose checkpoints no longer reach back
+//! that far is skipped. Only the
+//! holders registered when the distribution opened are rewarded, and accounts
+//! can't unregister until it closes, which keeps their order in `balances`
+//! fixed. The contract's own account (vesting and airdrop pools) isn't
+//! rewarded, and its share is never minted.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, log, near_bindgen, AccountId};
+
+use crate::checkpoints::checkpoint_value_at;
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct RewardDistribution {
+    /// Amount to share between all holders
+    pub total_amount: Balance,
+    /// Total supply when the distribution was opened
+    pub total_supply: Balance,
+    /// Timestamp just before the block the distribution was opened in, holders are
+    /// rewarded by their balance at it
+    pub timestamp: u64,
+    /// Number of holders when the distribution was opened
+    pub holder_count: u64,
+    /// Index in `balances` of the next holder to reward
+    pub next_index: u64,
+    /// Amount minted so far
+    pub distributed: Balance,
+}
+
+/// Progress of the open reward distribution
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RewardDistributionView {
+    pub total_amount: U128,
+    pub total_supply: U128,
+    pub timestamp: u64,
+    pub holder_count: u64,
+    pub next_index: u64,
+    pub distributed: U128,
+}
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the progress of the open reward distribution, if any
+    pub fn reward_distribution(&self) -> Option<RewardDistributionView> {
+        self.reward_distribution.as_ref().map(|distribution| RewardDistributionView {
+            total_amount: U128(distribution.total_amount),
+            total_supply: U128(distribution.total_supply),
+            timestamp: distribution.timestamp,
+            holder_count: distribution.holder_count,
+            next_index: distribution.next_index,
+            distributed: U128(distribution.distributed),
+        })
+    }
+
+    /// Open a distribution of `total_amount` newly minted tokens to all holders,
+    /// pro rata to their balance - only callable by owner.
+    /// Holders are rewarded through `distribute_rewards_batch`. While a timelock delay
+    /// is set, opening the distribution has to be queued instead
+    pub fn distribute_rewards(&mut self, total_amount: U128) {
+        self.assert_owner();
+        self.assert_not_timelocked();
+        self.internal_distribute_rewards(total_amount);
+    }
+
+    pub(crate) fn internal_distribute_rewards(&mut self, total_amount: U128) {
+        self.assert_mints_not_paused();
+        assert!(self.reward_distribution.is_none(), "A reward distribution is already open");
+        let amount: Balance = total_amount.into();
+        assert!(amount > 0, "The amount should be a positive number");
+        // Changes later in this block could still move the checkpoints at its own timestamp
+        let timestamp = env::block_timestamp().saturating_sub(1);
+        let total_supply = checkpoint_value_at(&self.total_supply_checkpoints, timestamp);
+        assert!(total_supply > 0, "There are no holders to reward");
+        if let Some(max_supply) = self.max_supply {
+            assert!(
+                self.total_supply.saturating_add(amount) <= max_supply,
+                "Max supply exceeded"
+            );
+        }
+
+        self.reward_distribution = Some(RewardDistribution {
+            total_amount: amount,
+            total_supply,
+            timestamp,
+            holder_count: self.balances.len(),
+            next_index: 0,
+            distributed: 0,
+        });
+        log!("Opened a distribution of {} reward tokens", amount);
+    }
+
+    /// Mint the rewards of up to `limit` holders starting at `from_index`, which must be
+    /// where the previous batch stopped - only callable by owner. `total_amount` must be
+    /// the amount of the open distribution, so a stale call can't be replayed against a
+    /// newer one. Closes the distribution once every holder is rewarded and returns the
+    /// amount minted by this batch
+    pub fn distribute_rewards_batch(
+        &mut self,
+        from_index: u64,
+        limit: u64,
+        total_amount: U128,
+    ) -> U128 {
+        self.assert_owner();
+        let mut distribution = self
+            .reward_distribution
+            .take()
+            .unwrap_or_else(|| env::panic_str("No open reward distribution"));
+        assert_eq!(
+            distribution.total_amount, total_amount.0,
+            "Total amount doesn't match the open distribution"
+        );
+        assert_eq!(
+            distribution.next_index, from_index,
+            "Batch must start at the next holder index"
+        );
+
+        let current_account_id = env::current_account_id();
+        let end = std::cmp::min(from_index.saturating_add(limit), distribution.holder_count);
+        let keys = self.balances.keys_as_vector();
+        let holders: Vec<AccountId> = (from_index..end).filter_map(|index| keys.get(index)).collect();
+        let mut minted: Balance = 0;
+        for account_id in &holders {
+            if account_id == &current_account_id {
+                continue;
+            }
+            let balance = match self.internal_balance_at(account_id, distribution.timestamp) {
+                Some(balance) => balance,
+                None => {
+                    log!("Skipped {}, its checkpoints don't reach back far enough", account_id);
+                    continue;
+                }
+            };
+            let remaining = distribution.total_amount - distribution.distributed;
+            let share = std::cmp::min(
+                mul_div(distribution.total_amount, balance, distribution.total_supply),
+                remaining,
+            );
+            if share > 0 {
+                self.internal_mint(account_id, U128(share), Some("staking rewards"));
+                distribution.distributed += share;
+                minted += share;
+            }
+        }
+        distribution.next_index = from_index + holders.len() as u64;
+
+        if distribution.next_index >= distribution.holder_count {
+            log!(
+                "Reward distribution complete, {} of {} tokens minted",
+                distribution.distributed,
+                distribution.total_amount
+            );
+        } else {
+            self.reward_distribution = Some(distribution);
+        }
+        U128(minted)
+    }
+}
+
+/// `a * b / c` rounded down, with the product computed on 256 bits so it can't overflow.
+/// Panics if the result doesn't fit in 128 bits
+pub(crate) fn mul_div(a: u128, b: u128, c: u128) -> u128 {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_high, a_low) = (a >> 64, a & MASK);
+    let (b_high, b_low) = (b >> 64, b & MASK);
+    let low_low = a_low * b_low;
+    let high_low = a_high * b_low;
+    let low_high = a_low * b_high;
+    let middle = (low_low >> 64) + (high_low & MASK) + (low_high & MASK);
+    let low = (low_low & MASK) | (middle << 64);
+    let high = a_high * b_high + (high_low >> 64) + (low_high >> 64) + (middle >> 64);
+    assert!(high < c, "Multiplication overflow");
+
+    // Long division of `high:low` by `c`, one bit at a time
+    let mut quotient: u128 = 0;
+    let mut remainder: u128 = 0;
+    for bit_index in (0..256).rev() {
+        let bit = if bit_index >= 128 {
+            (high >> (bit_index - 128)) & 1
+        } else {
+            (low >> bit_index) & 1
+        };
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | bit;
+        quotient <<= 1;
+        if carry == 1 || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+    quotient
+}
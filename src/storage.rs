@@ -0,0 +1,233 @@
+//! NEP-145 storage management.
+//!
+//! Every account has to be registered (i.e. pay for its own `balances` entry,
+//! full rings of balance and vote checkpoints and the allowances it can approve)
+//! before it can hold tokens or approve spenders, so the contract never pays for
+//! user storage.
+//! With `auto_register_receivers` on, an NEP-141 transfer can register its
+//! receiver by attaching the storage deposit on top of the usual yoctoNEAR.
+
+use near_sdk::json_types::U128;
+use near_sdk::{assert_one_yocto, env, log, near_bindgen, AccountId, NearToken, Promise};
+
+use crate::events::FtBurn;
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Registers `account_id` (or the caller) by paying for its storage. The caller
+    /// pays, so a sponsor can register someone else. Storage cost is fixed per account,
+    /// so every registration is registration-only: the caller pays exactly the minimum
+    /// and any surplus is refunded to them, regardless of `registration_only`
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let _ = registration_only;
+        let amount = env::attached_deposit().as_yoctonear();
+        let payer_id = env::predecessor_account_id();
+        let account_id = account_id.unwrap_or_else(|| payer_id.clone());
+
+        if self.internal_is_registered(&account_id) {
+            log!("The account {} is already registered, refunding the deposit", account_id);
+            if amount > 0 {
+                log!("Refunding {} yoctoNEAR to {}", amount, payer_id);
+                Promise::new(payer_id).transfer(NearToken::from_yoctonear(amount));
+            }
+        } else {
+            let min_balance = self.storage_balance_bounds().min.0;
+            assert!(
+                amount >= min_balance,
+                "The attached deposit is less than the minimum storage balance"
+            );
+
+            self.internal_set_balance(&account_id, 0);
+            self.storage_deposits.insert(&account_id, &min_balance);
+            log!("Registered account {}", account_id);
+
+            let refund = amount - min_balance;
+            if refund > 0 {
+                log!("Refunding {} yoctoNEAR to {}", refund, payer_id);
+                Promise::new(payer_id).transfer(NearToken::from_yoctonear(refund));
+            }
+        }
+
+        self.internal_storage_balance_of(&account_id).unwrap()
+    }
+
+    /// Withdraws unused storage deposit. Registration costs a fixed amount,
+    /// so nothing above the minimum bound is ever available and only a zero
+    /// (or omitted) `amount` succeeds
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let storage_balance = self
+            .internal_storage_balance_of(&account_id)
+            .unwrap_or_else(|| env::panic_str(&format!("The account {} is not registered", account_id)));
+
+        let amount = amount.map(|a| a.0).unwrap_or(storage_balance.available.0);
+        assert!(
+            amount <= storage_balance.available.0,
+            "The amount is greater than the available storage balance"
+        );
+        if amount > 0 {
+            Promise::new(account_id).transfer(NearToken::from_yoctonear(amount));
+        }
+        storage_balance
+    }
+
+    /// Unregisters the caller and refunds the storage deposit paid to register it, nothing
+    /// for accounts registered without one (the owner, holders migrated from the old contract).
+    /// Panics if the caller still holds tokens, unless `force` is set, in which
+    /// case the remaining balance is burned (so burns mustn't be paused).
+    /// Returns false if the caller wasn't registered
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let force = force.unwrap_or(false);
+
+        let balance = match self.internal_balance_of(&account_id) {
+            Some(balance) => balance,
+            None => {
+                log!("The account {} is not registered", account_id);
+                return false;
+            }
+        };
+        assert!(
+            balance == 0 || force,
+            "Can't unregister the account with a positive balance without force"
+        );
+        // Removing an entry reorders `balances`, which the open distribution walks by index
+        assert!(
+            self.reward_distribution.is_none(),
+            "Can't unregister during a reward distribution"
+        );
+        if balance > 0 {
+            // Force-unregistering burns the balance, which is subject to the burn pause
+            self.assert_burns_not_paused();
+        }
+
+        // Zeroing the balance first records it in the checkpoints and takes back its votes
+        self.internal_set_balance(&account_id, 0);
+        self.internal_remove_balance(&account_id);
+        self.internal_clear_balance_checkpoints(&account_id);
+        self.internal_clear_vote_checkpoints(&account_id);
+        self.internal_clear_allowances(&account_id);
+        self.delegates.remove(&account_id);
+        if balance > 0 {
+            self.total_supply = self
+                .total_supply
+                .checked_sub(balance)
+                .unwrap_or_else(|| env::panic_str("Total supply underflow"));
+            self.total_burned = self.total_burned.saturating_add(balance);
+            self.internal_checkpoint_total_supply();
+            FtBurn {
+                owner_id: &account_id,
+                amount: U128(balance),
+                memo: Some("storage_unregister"),
+            }
+            .emit();
+        }
+        log!("Unregistered account {}", account_id);
+
+        // Refund the storage deposit paid at registration, if any, along with the attached
+        // yoctoNEAR
+        let refund = self.storage_deposits.remove(&account_id).unwrap_or(0) + 1;
+        Promise::new(account_id).transfer(NearToken::from_yoctonear(refund));
+        true
+    }
+
+    /// Returns the storage deposit required to register one account
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        let required_storage_balance: Balance =
+            Balance::from(self.account_storage_usage) * env::storage_byte_cost().as_yoctonear();
+        StorageBalanceBounds {
+            min: U128(required_storage_balance),
+            max: Some(U128(required_storage_balance)),
+        }
+    }
+
+    /// Returns true if `account_id` has paid for its storage
+    pub fn is_registered(&self, account_id: AccountId) -> bool {
+        self.internal_is_registered(&account_id)
+    }
+
+    /// Returns true if NEP-141 transfers register unregistered receivers
+    pub fn auto_register_receivers(&self) -> bool {
+        self.auto_register_receivers
+    }
+
+    /// Let NEP-141 transfers register their receiver out of the attached deposit -
+    /// only callable by owner
+    pub fn set_auto_register_receivers(&mut self, enabled: bool) {
+        self.assert_owner();
+        self.auto_register_receivers = enabled;
+        log!("Auto-registration of receivers set to {}", enabled);
+    }
+
+    /// Returns the storage balance of `account_id`, or `None` if it isn't registered
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.internal_storage_balance_of(&account_id)
+    }
+
+    /// Internal implementation of storage balance lookup
+    fn internal_storage_balance_of(&self, account_id: &AccountId) -> Option<StorageBalance> {
+        if self.internal_is_registered(account_id) {
+            Some(StorageBalance {
+                total: self.storage_balance_bounds().min,
+                available: U128(0),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Checks the deposit attached to an NEP-141 transfer to `receiver_id`. A registered
+    /// receiver needs exactly 1 yoctoNEAR. An unregistered one is registered out of the
+    /// deposit while `auto_register_receivers` is on, the surplus over the storage cost
+    /// and the 1 yoctoNEAR being refunded to the caller
+    pub(crate) fn internal_prepare_receiver(&mut self, receiver_id: &AccountId) {
+        if self.internal_is_registered(receiver_id) {
+            assert_one_yocto();
+            return;
+        }
+        assert!(
+            self.auto_register_receivers,
+            "The account {} is not registered",
+            receiver_id
+        );
+
+        let amount = env::attached_deposit().as_yoctonear();
+        let min_balance = self.storage_balance_bounds().min.0;
+        let required = min_balance + 1;
+        assert!(amount >= required, "The attached deposit doesn't cover the receiver's storage");
+        self.internal_set_balance(receiver_id, 0);
+        self.storage_deposits.insert(receiver_id, &min_balance);
+        log!("Registered account {}", receiver_id);
+
+        let refund = amount - required;
+        if refund > 0 {
+            let payer_id = env::predecessor_account_id();
+            log!("Refunding {} yoctoNEAR to {}", refund, payer_id);
+            Promise::new(payer_id).transfer(NearToken::from_yoctonear(refund));
+        }
+    }
+}
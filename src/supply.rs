@@ -0,0 +1,69 @@
+//! Circulating supply for market-data providers.
+//!
+//! Circulating supply is the total supply minus what the treasury, the
+//! contract's own account (vesting and airdrop pools) and any account the owner
+//! excluded (team wallets, burn addresses, the HTLC escrow) hold.
+
+use near_sdk::json_types::U128;
+use near_sdk::{env, log, near_bindgen, AccountId};
+
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+/// Maximum number of excluded accounts, so `circulating_supply` stays cheap to compute
+const MAX_CIRCULATING_EXCLUDED: u64 = 20;
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the treasury account
+    pub fn treasury(&self) -> AccountId {
+        self.treasury.clone()
+    }
+
+    /// Returns the accounts whose balances don't count towards the circulating supply,
+    /// besides the treasury and the contract itself
+    pub fn circulating_excluded(&self) -> Vec<AccountId> {
+        self.circulating_excluded.to_vec()
+    }
+
+    /// Returns the total supply minus the balances of the treasury, the contract
+    /// itself and the excluded accounts
+    pub fn circulating_supply(&self) -> U128 {
+        let mut excluded = vec![self.treasury.clone(), env::current_account_id()];
+        for account_id in self.circulating_excluded.iter() {
+            if !excluded.contains(&account_id) {
+                excluded.push(account_id);
+            }
+        }
+        let held: Balance = excluded
+            .iter()
+            .map(|account_id| self.internal_balance_of(account_id).unwrap_or(0))
+            .sum();
+        U128(self.total_supply.saturating_sub(held))
+    }
+
+    /// Set the treasury account - only callable by owner
+    pub fn set_treasury(&mut self, treasury: AccountId) {
+        self.assert_owner();
+        log!("Treasury changed from {} to {}", self.treasury, treasury);
+        self.treasury = treasury;
+    }
+
+    /// Exclude an account's balance from the circulating supply - only callable by owner
+    pub fn add_circulating_excluded(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        assert!(
+            self.circulating_excluded.len() < MAX_CIRCULATING_EXCLUDED,
+            "Cannot exclude more than {} accounts",
+            MAX_CIRCULATING_EXCLUDED
+        );
+        self.circulating_excluded.insert(&account_id);
+        log!("Excluded from circulating supply: {}", account_id);
+    }
+
+    /// Count an account's balance towards the circulating supply again - only callable by owner
+    pub fn remove_circulating_excluded(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.circulating_excluded.remove(&account_id);
+        log!("Included in circulating supply: {}", account_id);
+    }
+}
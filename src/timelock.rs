@@ -0,0 +1,174 @@
+//! Timelock on sensitive owner actions.
+//!
+//! While `timelock_delay_seconds` is non-zero, the owner can't mint, add a
+//! minter, rebase, open a reward distribution, update the metadata, change the
+//! transfer fee or change the delay itself directly. The
+//! action has to be queued instead and can only be executed once the delay has
+//! passed, giving holders time to react (or exit) before it takes effect. Queued
+//! actions are keyed by the SHA-256 of their Borsh encoding, so the same action
+//! can't be queued twice. Minters other than the owner keep minting directly.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base64VecU8, U128};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, log, near_bindgen, AccountId, CryptoHash};
+
+use crate::events::{TimelockCancelled, TimelockExecuted, TimelockQueued};
+use crate::{UnrealToken, UnrealTokenExt};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// An owner action that has to go through the timelock while it is enabled
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TimelockedAction {
+    Mint {
+        to: AccountId,
+        amount: U128,
+    },
+    UpdateMetadata {
+        name: Option<String>,
+        symbol: Option<String>,
+        icon: Option<String>,
+        reference: Option<String>,
+        reference_hash: Option<Base64VecU8>,
+        decimals: Option<u8>,
+    },
+    SetTransferFee {
+        fee_basis_points: u16,
+        fee_collector: AccountId,
+    },
+    SetTimelockDelay {
+        delay_seconds: u64,
+    },
+    AddMinter {
+        account_id: AccountId,
+    },
+    Rebase {
+        new_total_supply: U128,
+    },
+    DistributeRewards {
+        total_amount: U128,
+    },
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingAction {
+    pub action: TimelockedAction,
+    /// Earliest timestamp (in nanoseconds) the action can be executed at
+    pub eta: u64,
+}
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the delay in seconds between queueing an owner action and executing it
+    pub fn timelock_delay(&self) -> u64 {
+        self.timelock_delay_seconds
+    }
+
+    /// Returns the queued action with the given hex encoded id
+    pub fn pending_action(&self, action_id: String) -> Option<PendingAction> {
+        self.pending_actions.get(&parse_action_id(&action_id))
+    }
+
+    /// Set the timelock delay in seconds, zero disables it - only callable by owner.
+    /// While a delay is set, changing it has to be queued like the other actions
+    pub fn set_timelock_delay(&mut self, delay_seconds: u64) {
+        self.assert_owner();
+        self.assert_not_timelocked();
+        self.internal_set_timelock_delay(delay_seconds);
+    }
+
+    /// Queue `action` for execution once the timelock delay has passed - only callable
+    /// by owner. Returns the hex encoded id to execute or cancel it with
+    pub fn queue_action(&mut self, action: TimelockedAction) -> String {
+        self.assert_owner();
+        assert!(self.timelock_delay_seconds > 0, "Timelock is disabled");
+        let id = env::sha256_array(&near_sdk::borsh::to_vec(&action).unwrap());
+        assert!(self.pending_actions.get(&id).is_none(), "Action is already queued");
+
+        let eta = env::block_timestamp()
+            .saturating_add(self.timelock_delay_seconds.saturating_mul(NANOS_PER_SECOND));
+        let action_id = hex::encode(id);
+        TimelockQueued { action_id: &action_id, action: &action, eta }.emit();
+        self.pending_actions.insert(&id, &PendingAction { action, eta });
+        log!("Queued action {} executable at {}", action_id, eta);
+        action_id
+    }
+
+    /// Execute a queued action whose delay has passed - only callable by owner
+    pub fn execute_action(&mut self, action_id: String) {
+        self.assert_owner();
+        let id = parse_action_id(&action_id);
+        let pending = self
+            .pending_actions
+            .get(&id)
+            .unwrap_or_else(|| env::panic_str("Action is not queued"));
+        assert!(env::block_timestamp() >= pending.eta, "Timelock delay has not passed");
+        self.pending_actions.remove(&id);
+
+        match pending.action {
+            TimelockedAction::Mint { to, amount } => self.internal_mint(&to, amount, None),
+            TimelockedAction::UpdateMetadata {
+                name,
+                symbol,
+                icon,
+                reference,
+                reference_hash,
+                decimals,
+            } => self.internal_update_metadata(
+                name,
+                symbol,
+                icon,
+                reference,
+                reference_hash,
+                decimals,
+            ),
+            TimelockedAction::SetTransferFee { fee_basis_points, fee_collector } => {
+                self.internal_set_transfer_fee(fee_basis_points, fee_collector)
+            }
+            TimelockedAction::SetTimelockDelay { delay_seconds } => {
+                self.internal_set_timelock_delay(delay_seconds)
+            }
+            TimelockedAction::AddMinter { account_id } => self.internal_add_minter(account_id),
+            TimelockedAction::Rebase { new_total_supply } => self.internal_rebase(new_total_supply),
+            TimelockedAction::DistributeRewards { total_amount } => {
+                self.internal_distribute_rewards(total_amount)
+            }
+        }
+        log!("Executed action {}", action_id);
+        TimelockExecuted { action_id: &action_id }.emit();
+    }
+
+    /// Drop a queued action without executing it - only callable by owner
+    pub fn cancel_action(&mut self, action_id: String) {
+        self.assert_owner();
+        self.pending_actions
+            .remove(&parse_action_id(&action_id))
+            .unwrap_or_else(|| env::panic_str("Action is not queued"));
+        log!("Cancelled action {}", action_id);
+        TimelockCancelled { action_id: &action_id }.emit();
+    }
+
+    /// Panics while a timelock delay is set, for owner actions that must be queued
+    pub(crate) fn assert_not_timelocked(&self) {
+        assert!(
+            self.timelock_delay_seconds == 0,
+            "Action must be queued through the timelock"
+        );
+    }
+
+    fn internal_set_timelock_delay(&mut self, delay_seconds: u64) {
+        self.timelock_delay_seconds = delay_seconds;
+        log!("Timelock delay set to {} seconds", delay_seconds);
+    }
+}
+
+fn parse_action_id(action_id: &str) -> CryptoHash {
+    hex::decode(action_id)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .unwrap_or_else(|| env::panic_str("Invalid action id"))
+}
@@ -0,0 +1,67 @@
+//! Best-effort transfer notifications to an observer contract.
+//!
+//! While a hook is set, every transfer schedules `on_token_transfer(from, to,
+//! amount)` on the hook contract with a fixed gas budget. The call is detached:
+//! nothing waits for it, so the hook can't veto or revert a transfer and a
+//! failing or out-of-gas hook just misses the notification. Mints and burns
+//! aren't reported.
+
+use near_sdk::json_types::U128;
+use near_sdk::{ext_contract, log, near_bindgen, AccountId, Gas};
+
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+/// Gas given to each hook call
+const GAS_FOR_TRANSFER_HOOK: Gas = Gas::from_tgas(5);
+
+/// Interface the hook contract implements
+#[ext_contract(ext_transfer_hook)]
+pub trait TransferHook {
+    fn on_token_transfer(&mut self, from: AccountId, to: AccountId, amount: U128);
+}
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the contract notified of every transfer, if any
+    pub fn transfer_hook(&self) -> Option<AccountId> {
+        self.transfer_hook.clone()
+    }
+
+    /// Notify `hook` of every transfer from now on - only callable by owner
+    pub fn set_transfer_hook(&mut self, hook: AccountId) {
+        self.assert_owner();
+        log!("Transfer hook set to {}", hook);
+        self.transfer_hook = Some(hook);
+    }
+
+    /// Stop notifying the transfer hook - only callable by owner
+    pub fn clear_transfer_hook(&mut self) {
+        self.assert_owner();
+        self.transfer_hook = None;
+        log!("Transfer hook cleared");
+    }
+
+    /// Gas to reserve for the hook call of a single transfer
+    pub(crate) fn internal_transfer_hook_gas(&self) -> Gas {
+        if self.transfer_hook.is_some() {
+            GAS_FOR_TRANSFER_HOOK
+        } else {
+            Gas::from_gas(0)
+        }
+    }
+
+    /// Schedules the hook call for a transfer of `amount` received by `to`
+    pub(crate) fn internal_notify_transfer_hook(
+        &self,
+        from: &AccountId,
+        to: &AccountId,
+        amount: Balance,
+    ) {
+        if let Some(hook) = &self.transfer_hook {
+            // Dropping the promise schedules it without tying its outcome to the transfer
+            ext_transfer_hook::ext(hook.clone())
+                .with_static_gas(GAS_FOR_TRANSFER_HOOK)
+                .on_token_transfer(from.clone(), to.clone(), U128(amount));
+        }
+    }
+}
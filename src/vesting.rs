@@ -0,0 +1,150 @@
+//! Linear vesting schedules with a cliff.
+//!
+//! Vested tokens are held on the contract's own account until the beneficiary
+//! releases them. A beneficiary can have any number of schedules, stored under
+//! `(beneficiary, index)`.
+
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::{env, log, near_bindgen, AccountId};
+
+use crate::events::FtTransfer;
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct VestingSchedule {
+    pub beneficiary: AccountId,
+    /// Total amount granted
+    pub total: Balance,
+    /// Start of the schedule, in nanoseconds
+    pub start: u64,
+    /// Nothing vests before this timestamp, in nanoseconds
+    pub cliff: u64,
+    /// Time for the whole grant to vest, in nanoseconds
+    pub duration: u64,
+    /// Amount already released to the beneficiary
+    pub released: Balance,
+}
+
+impl VestingSchedule {
+    /// Amount vested at `timestamp`, unlocking linearly from `start` once the cliff has passed
+    fn vested_amount(&self, timestamp: u64) -> Balance {
+        if timestamp < self.cliff {
+            return 0;
+        }
+        let elapsed = Balance::from(timestamp - self.start);
+        let duration = Balance::from(self.duration);
+        if elapsed >= duration {
+            return self.total;
+        }
+        // Same as `total * elapsed / duration` without overflowing
+        self.total / duration * elapsed + self.total % duration * elapsed / duration
+    }
+
+    fn releasable_amount(&self, timestamp: u64) -> Balance {
+        self.vested_amount(timestamp) - self.released
+    }
+}
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Grant `amount` tokens to `beneficiary`, vesting linearly over `duration_seconds`
+    /// with nothing released before `cliff_seconds` - only callable by owner.
+    /// The tokens are moved from the owner to the contract account right away
+    pub fn create_vesting(
+        &mut self,
+        beneficiary: AccountId,
+        amount: U128,
+        cliff_seconds: u64,
+        duration_seconds: u64,
+    ) -> u32 {
+        self.assert_owner();
+        let amount: Balance = amount.into();
+        assert!(amount > 0, "The amount should be a positive number");
+        assert!(duration_seconds > 0, "Duration must be greater than 0");
+        assert!(cliff_seconds <= duration_seconds, "Cliff cannot be longer than the duration");
+
+        let start = env::block_timestamp();
+        let schedule = VestingSchedule {
+            beneficiary: beneficiary.clone(),
+            total: amount,
+            start,
+            cliff: start + cliff_seconds * NANOS_PER_SECOND,
+            duration: duration_seconds * NANOS_PER_SECOND,
+            released: 0,
+        };
+        let index = self.vesting_counts.get(&beneficiary).unwrap_or(0);
+        self.vesting_schedules.insert(&(beneficiary.clone(), index), &schedule);
+        self.vesting_counts.insert(&beneficiary, &(index + 1));
+
+        // Move the grant into the contract's own account
+        let owner_id = self.owner_id.clone();
+        let treasury_id = env::current_account_id();
+        if !self.internal_is_registered(&treasury_id) {
+            self.internal_set_balance(&treasury_id, 0);
+        }
+        self.internal_withdraw(&owner_id, amount);
+        self.internal_deposit(&treasury_id, amount);
+        FtTransfer {
+            old_owner_id: &owner_id,
+            new_owner_id: &treasury_id,
+            amount: U128(amount),
+            memo: Some("vesting"),
+        }
+        .emit();
+        log!("Created vesting schedule {} of {} tokens for {}", index, amount, beneficiary);
+
+        index
+    }
+
+    /// Returns the amount `beneficiary` can release right now across all of its schedules
+    pub fn releasable(&self, beneficiary: AccountId) -> U128 {
+        let timestamp = env::block_timestamp();
+        let count = self.vesting_counts.get(&beneficiary).unwrap_or(0);
+        let amount = (0..count)
+            .filter_map(|index| self.vesting_schedules.get(&(beneficiary.clone(), index)))
+            .map(|schedule| schedule.releasable_amount(timestamp))
+            .sum();
+        U128(amount)
+    }
+
+    /// Transfer everything vested so far to the caller. Returns the released amount.
+    /// Subject to the transfer pause and the blacklist like any other transfer
+    pub fn release(&mut self) -> U128 {
+        self.assert_transfers_not_paused();
+        let beneficiary = env::predecessor_account_id();
+        self.assert_not_blacklisted(&beneficiary);
+        let timestamp = env::block_timestamp();
+        let count = self.vesting_counts.get(&beneficiary).unwrap_or(0);
+
+        let mut amount: Balance = 0;
+        for index in 0..count {
+            let key = (beneficiary.clone(), index);
+            if let Some(mut schedule) = self.vesting_schedules.get(&key) {
+                let releasable = schedule.releasable_amount(timestamp);
+                if releasable > 0 {
+                    schedule.released += releasable;
+                    self.vesting_schedules.insert(&key, &schedule);
+                    amount += releasable;
+                }
+            }
+        }
+        assert!(amount > 0, "Nothing to release");
+
+        let treasury_id = env::current_account_id();
+        self.internal_withdraw(&treasury_id, amount);
+        self.internal_deposit(&beneficiary, amount);
+        FtTransfer {
+            old_owner_id: &treasury_id,
+            new_owner_id: &beneficiary,
+            amount: U128(amount),
+            memo: Some("vesting release"),
+        }
+        .emit();
+        log!("Released {} vested tokens to {}", amount, beneficiary);
+
+        U128(amount)
+    }
+}
@@ -0,0 +1,131 @@
+//! ERC20Votes-style delegation of voting power.
+//!
+//! Every account delegates its voting weight to itself unless it calls `delegate`.
+//! Each balance change moves votes between delegatees, and votes are checkpointed
+//! the same way balances are in `checkpoints`. Votes are counted in shares, so a
+//! rebase scales them along with the balances behind them. The contract's own
+//! account holds the vesting and airdrop pools, which don't vote. Only registered
+//! accounts, which paid for their vote checkpoints, can be delegated to.
+
+use near_sdk::json_types::U128;
+use near_sdk::{env, log, near_bindgen, AccountId};
+
+use crate::rebase::{is_rebased, SCALING_PRECISION};
+use crate::rewards::mul_div;
+use crate::checkpoints::AccountCheckpoints;
+use crate::{Balance, UnrealToken, UnrealTokenExt};
+
+#[near_bindgen]
+impl UnrealToken {
+    /// Returns the account `account_id` delegates its votes to
+    pub fn delegates(&self, account_id: AccountId) -> AccountId {
+        self.internal_delegatee(&account_id)
+    }
+
+    /// Returns the current votes of `account_id`
+    pub fn get_votes(&self, account_id: AccountId) -> U128 {
+        let shares = self.internal_get_votes(&account_id);
+        U128(mul_div(shares, self.scaling_factor, SCALING_PRECISION))
+    }
+
+    /// Returns the votes `account_id` had at `timestamp` (in nanoseconds), which must be
+    /// before the current block
+    pub fn get_past_votes(&self, account_id: AccountId, timestamp: u64) -> U128 {
+        self.assert_past_timestamp(timestamp);
+        let shares = match self.vote_checkpoints.get(&account_id) {
+            Some(checkpoints) => checkpoints.value_at(timestamp).unwrap_or_else(|| {
+                env::panic_str(&format!(
+                    "The checkpoints of {} don't reach back to {}",
+                    account_id, timestamp
+                ))
+            }),
+            None => 0,
+        };
+        let scaling_factor = self.internal_scaling_factor_at(timestamp);
+        U128(mul_div(shares, scaling_factor, SCALING_PRECISION))
+    }
+
+    /// Delegate the caller's voting weight to `delegatee` without moving tokens.
+    /// Delegating to yourself restores the default. The delegatee has to be registered,
+    /// which pays for its vote checkpoints
+    pub fn delegate(&mut self, delegatee: AccountId) {
+        let delegator = env::predecessor_account_id();
+        assert!(
+            self.internal_is_registered(&delegatee),
+            "The account {} is not registered",
+            delegatee
+        );
+        let old_delegatee = self.internal_delegatee(&delegator);
+        if old_delegatee == delegatee {
+            return;
+        }
+
+        if delegatee == delegator {
+            self.delegates.remove(&delegator);
+        } else {
+            self.delegates.insert(&delegator, &delegatee);
+        }
+        if is_rebased(&delegator) {
+            let shares = self.internal_shares_of(&delegator);
+            self.internal_move_votes(Some(&old_delegatee), Some(&delegatee), shares);
+        }
+        log!("{} delegated votes from {} to {}", delegator, old_delegatee, delegatee);
+    }
+
+    /// Account receiving the votes of `account_id`, itself by default
+    pub(crate) fn internal_delegatee(&self, account_id: &AccountId) -> AccountId {
+        self.delegates.get(account_id).unwrap_or_else(|| account_id.clone())
+    }
+
+    fn internal_get_votes(&self, account_id: &AccountId) -> Balance {
+        self.vote_checkpoints.get(account_id).map(|checkpoints| checkpoints.latest()).unwrap_or(0)
+    }
+
+    /// Moves `amount` votes (in shares) from one delegatee to another, `None` standing
+    /// for tokens entering or leaving circulation
+    pub(crate) fn internal_move_votes(
+        &mut self,
+        from: Option<&AccountId>,
+        to: Option<&AccountId>,
+        amount: Balance,
+    ) {
+        if amount == 0 || from == to {
+            return;
+        }
+        if let Some(from) = from {
+            // Holders from before delegation existed have no recorded votes,
+            // so saturate instead of blocking their transfers
+            let votes = self.internal_get_votes(from).saturating_sub(amount);
+            self.internal_write_votes(from, votes);
+        }
+        if let Some(to) = to {
+            let votes = self
+                .internal_get_votes(to)
+                .checked_add(amount)
+                .unwrap_or_else(|| env::panic_str("Votes overflow"));
+            self.internal_write_votes(to, votes);
+        }
+    }
+
+    /// Records the votes of `account_id`. An account that isn't registered hasn't paid
+    /// for vote checkpoints, so votes still delegated to it after it unregistered are
+    /// dropped until their delegators delegate again
+    fn internal_write_votes(&mut self, account_id: &AccountId, votes: Balance) {
+        if !self.internal_is_registered(account_id) {
+            return;
+        }
+        let mut checkpoints = self
+            .vote_checkpoints
+            .get(account_id)
+            .unwrap_or_else(|| AccountCheckpoints::new(b"V", account_id));
+        checkpoints.write(env::block_timestamp(), votes);
+        self.vote_checkpoints.insert(account_id, &checkpoints);
+    }
+
+    /// Drops the vote checkpoints of an account being unregistered
+    pub(crate) fn internal_clear_vote_checkpoints(&mut self, account_id: &AccountId) {
+        if let Some(mut checkpoints) = self.vote_checkpoints.remove(account_id) {
+            checkpoints.clear();
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Token and HTLC together: a swap locked through `ft_transfer_call`.
+//!
+//! Both contracts are compiled with `cargo near` from their workspace crates, the token from
+//! the root and the HTLC from `htlc/`.
+
+use near_workspaces::network::Sandbox;
+use near_workspaces::types::NearToken;
+use near_workspaces::{Account, Contract, Worker};
+use serde_json::{json, Value};
+
+const SECRET_HASH: &str = "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b";
+
+struct Setup {
+    token: Contract,
+    htlc: Contract,
+    user: Account,
+}
+
+/// Deploys the token and the HTLC, and funds a registered user with 1000 tokens
+async fn setup(sandbox: &Worker<Sandbox>) -> Result<Setup, Box<dyn std::error::Error>> {
+    let token_wasm = near_workspaces::compile_project("./").await?;
+    let htlc_wasm = near_workspaces::compile_project("./htlc").await?;
+
+    let token = sandbox.dev_deploy(&token_wasm).await?;
+    token
+        .call("new")
+        .args_json(json!({ "max_supply": null, "treasury": null }))
+        .transact()
+        .await?
+        .into_result()?;
+    token.call("enable_trading").transact().await?.into_result()?;
+
+    let htlc = sandbox.dev_deploy(&htlc_wasm).await?;
+    htlc.call("new").transact().await?.into_result()?;
+    htlc.call("add_supported_token")
+        .args_json(json!({ "token": token.id() }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    let user = sandbox.dev_create_account().await?;
+    let bounds = token.view("storage_balance_bounds").await?.json::<Value>()?;
+    let min_balance: u128 = bounds["min"].as_str().unwrap().parse()?;
+    for account_id in [user.id(), htlc.id()] {
+        token
+            .call("storage_deposit")
+            .args_json(json!({ "account_id": account_id }))
+            .deposit(NearToken::from_yoctonear(min_balance))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+    token
+        .call("ft_transfer")
+        .args_json(json!({ "receiver_id": user.id(), "amount": "1000" }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?
+        .into_result()?;
+
+    Ok(Setup { token, htlc, user })
+}
+
+async fn balance_of(
+    token: &Contract,
+    account: &Account,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let balance = token
+        .view("ft_balance_of")
+        .args_json(json!({ "account_id": account.id() }))
+        .await?
+        .json::<String>()?;
+    Ok(balance)
+}
+
+#[tokio::test]
+async fn test_ft_transfer_call_creates_lock() -> Result<(), Box<dyn std::error::Error>> {
+    let sandbox = near_workspaces::sandbox().await?;
+    let Setup { token, htlc, user } = setup(&sandbox).await?;
+
+    let msg = json!({
+        "secret_hash": hex::decode(SECRET_HASH)?,
+        "recipient": "recipient.test.near",
+        "timeout_hours": 24,
+        "target_chain": "mainnet:1",
+        "target_address": "0x0000000000000000000000000000000000000001",
+        "salt": 7,
+    });
+    user.call(token.id(), "ft_transfer_call")
+        .args_json(json!({
+            "receiver_id": htlc.id(),
+            "amount": "1000",
+            "msg": msg.to_string(),
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+
+    let locks = htlc
+        .view("get_lock_contracts_by_sender")
+        .args_json(json!({ "account_id": user.id() }))
+        .await?
+        .json::<Vec<Value>>()?;
+    assert_eq!(locks.len(), 1);
+    assert_eq!(locks[0]["secret_hash"], SECRET_HASH);
+    assert_eq!(locks[0]["recipient"], "recipient.test.near");
+    assert_eq!(locks[0]["amount"], "1000");
+    assert_eq!(locks[0]["target_chain"], "mainnet:1");
+    assert_eq!(locks[0]["target_address"], "0x0000000000000000000000000000000000000001");
+    assert_eq!(locks[0]["token"], token.id().to_string());
+    assert_eq!(balance_of(&token, &user).await?, "0");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ft_transfer_call_with_invalid_msg() -> Result<(), Box<dyn std::error::Error>> {
+    let sandbox = near_workspaces::sandbox().await?;
+    let Setup { token, htlc, user } = setup(&sandbox).await?;
+
+    let outcome = user
+        .call(token.id(), "ft_transfer_call")
+        .args_json(json!({
+            "receiver_id": htlc.id(),
+            "amount": "1000",
+            "msg": "not a swap",
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .max_gas()
+        .transact()
+        .await?;
+    // The transfer itself succeeds, the HTLC's panic is resolved by a full refund
+    assert!(outcome.is_success());
+    assert!(!outcome.receipt_failures().is_empty());
+
+    assert_eq!(balance_of(&token, &user).await?, "1000");
+    assert_eq!(balance_of(&token, htlc.as_account()).await?, "0");
+
+    Ok(())
+}